@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE item DROP avg_price_7d;
+
+DROP TABLE price_history
\ No newline at end of file
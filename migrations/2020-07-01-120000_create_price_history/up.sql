@@ -0,0 +1,10 @@
+-- Every price observed for an item, used to compute the rolling average
+CREATE TABLE price_history(
+    id int not null auto_increment primary key,
+    order_id int not null,
+    price int not null,
+    recorded_at datetime not null,
+    index (order_id, recorded_at)
+);
+
+ALTER TABLE item ADD avg_price_7d int null
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Duration of each run, used as a baseline to detect slow runs
+CREATE TABLE run_history(
+    id int not null auto_increment primary key,
+    duration_ms int not null,
+    finished_at datetime not null
+)
\ No newline at end of file
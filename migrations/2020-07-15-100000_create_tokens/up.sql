@@ -0,0 +1,5 @@
+-- Registration tokens notifications are sent to, pruned when FCM reports them dead
+CREATE TABLE tokens(
+    id int not null auto_increment primary key,
+    registration_id varchar(255) not null unique
+)
\ No newline at end of file
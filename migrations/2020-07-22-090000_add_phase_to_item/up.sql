@@ -0,0 +1,2 @@
+-- Phase of Doppler and Gamma Doppler skins, e.g. Phase 2 or Ruby
+ALTER TABLE item ADD phase varchar(16) null
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE item DROP is_souvenir
\ No newline at end of file
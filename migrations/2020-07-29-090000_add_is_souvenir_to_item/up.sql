@@ -0,0 +1,2 @@
+-- Souvenir items, whose prefix is removed from the name like StatTrak
+ALTER TABLE item ADD is_souvenir tinyint(1) default 0 not null
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE item MODIFY price int not null, MODIFY avg_price_7d int null;
+
+ALTER TABLE price_history MODIFY price int not null
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Prices above 2,147,483,647 yen did not fit in an int
+ALTER TABLE item MODIFY price bigint not null, MODIFY avg_price_7d bigint null;
+
+ALTER TABLE price_history MODIFY price bigint not null
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Wear value of the skin, when the site lists it
+ALTER TABLE item ADD float_value float null
\ No newline at end of file
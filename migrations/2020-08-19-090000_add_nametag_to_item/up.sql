@@ -0,0 +1,2 @@
+-- Name tag the owner put on the item, at most 20 characters in game
+ALTER TABLE item ADD nametag varchar(32) null
\ No newline at end of file
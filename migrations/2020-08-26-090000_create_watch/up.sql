@@ -0,0 +1,9 @@
+-- Items to be notified about when their price drops to the target.
+-- The name may start with StatTrak™ or Souvenir, kind is null for vanilla and exterior is null for any.
+CREATE TABLE watch(
+    id int not null auto_increment primary key,
+    name varchar(48) not null,
+    kind varchar(36) null,
+    exterior enum('BS', 'WW', 'FT', 'MW', 'FN') null,
+    target_price bigint not null
+)
\ No newline at end of file
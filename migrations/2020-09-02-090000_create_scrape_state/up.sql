@@ -0,0 +1,8 @@
+-- The layout of the listing as of the last normal run, to tell a redesign of the site from an
+-- ordinary change of the items. There is only ever the row with id 1.
+CREATE TABLE scrape_state(
+    id int not null primary key,
+    layout_hash char(16) not null,
+    item_count int not null,
+    updated_at datetime not null
+)
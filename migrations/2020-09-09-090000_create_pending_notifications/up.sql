@@ -0,0 +1,10 @@
+-- Notifications committed with the changes they are about and not sent yet, as JSON, so the next run
+-- sends the ones a run which stopped halfway left behind
+CREATE TABLE pending_notifications(
+    id int not null auto_increment primary key,
+    event text not null,
+    attempts int not null default 0,
+    -- The bits of the sinks which sent it already, see `notifier::SinkKind`
+    sent_to int not null default 0,
+    created_at datetime not null
+)
@@ -0,0 +1,56 @@
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+
+use super::events::ItemEvent;
+use super::models::{Item, Watch};
+use super::notifier::NotificationEvent;
+use super::parsers;
+use super::schema::watch::dsl as table;
+
+pub fn load(conn: &MysqlConnection) -> QueryResult<Vec<Watch>> {
+    table::watch.order(table::id.asc()).load(conn)
+}
+
+// Whether the item is the skin the watch is for. The name, kind and exterior are compared
+// exactly, except that a watch without an exterior takes any.
+pub fn matches(watch: &Watch, item: &Item) -> bool {
+    let mut name = watch.name.trim().to_owned();
+    let (is_stattrak, is_souvenir) = parsers::strip_prefixes(&mut name);
+
+    name == item.name
+        && is_stattrak == item.is_stattrak
+        && is_souvenir == item.is_souvenir
+        && watch.kind == item.kind
+        && (watch.exterior.is_none() || watch.exterior == item.exterior)
+}
+
+// Finds the watched items which came down to their target in this run, from the diff's events.
+// An item is only notified when it is listed or relisted at or below the target, or its price
+// crosses the target, so it isn't notified again on every run while it stays below.
+pub fn reached(watches: &[Watch], events: &[ItemEvent]) -> Vec<NotificationEvent> {
+    let mut notifications = vec![];
+
+    for event in events {
+        let (item, old_price) = match event {
+            ItemEvent::Added { item } | ItemEvent::Relisted { item } => (item, None),
+            ItemEvent::PriceChanged {
+                item, old_price, ..
+            } => (item, Some(*old_price)),
+            _ => continue,
+        };
+
+        let watch = watches.iter().find(|watch| {
+            matches(watch, item)
+                && item.price <= watch.target_price
+                && old_price.is_none_or(|old| old > watch.target_price)
+        });
+        if let Some(watch) = watch {
+            notifications.push(NotificationEvent::TargetReached {
+                item: item.clone(),
+                target_price: watch.target_price,
+            });
+        }
+    }
+
+    notifications
+}
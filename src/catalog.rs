@@ -0,0 +1,458 @@
+use std::time::Duration;
+
+use super::config::{Lang, TrendDirection};
+use super::digest::{DigestBuilder, DigestChange, TOP_ITEMS};
+use super::models::Item;
+use super::runs;
+
+// The notification texts of one language. `{0}`, `{1}`, ... are replaced by the arguments,
+// in the same order as the `*_loc_args` sent with `USE_LOC_KEYS`, so adding a language
+// only takes another table.
+pub struct Messages {
+    pub item_added: &'static str,
+    pub price_changed: &'static str,
+    pub price_changed_body: &'static str,
+    pub price_changed_body_with_average: &'static str,
+    pub item_sold: &'static str,
+    pub item_deleted: &'static str,
+    pub item_relisted: &'static str,
+    pub item_relisted_body: &'static str,
+    pub item_details_changed: &'static str,
+    pub item_details_changed_body: &'static str,
+    pub item_listing: &'static str,
+    pub item_listing_body: &'static str,
+    pub price_trend_up: &'static str,
+    pub price_trend_down: &'static str,
+    pub price_trend_body: &'static str,
+    pub digest: &'static str,
+    pub digest_added: &'static str,
+    pub digest_price_drops: &'static str,
+    pub digest_price_rises: &'static str,
+    pub digest_sold: &'static str,
+    pub digest_deleted: &'static str,
+    pub digest_relisted: &'static str,
+    pub digest_details_changed: &'static str,
+    pub digest_listed: &'static str,
+    pub digest_minor_price_changes: &'static str,
+    pub digest_top_drops: &'static str,
+    pub digest_drop: &'static str,
+    pub listing_digest: &'static str,
+    pub digest_more: &'static str,
+    pub list_separator: &'static str,
+    pub unknown_sold: &'static str,
+    pub unknown_sold_body: &'static str,
+    pub slow_run: &'static str,
+    pub slow_run_body: &'static str,
+    pub slow_run_body_with_baseline: &'static str,
+    pub scrape_failed: &'static str,
+    pub scrape_failed_body: &'static str,
+    pub layout_changed: &'static str,
+    pub layout_changed_body: &'static str,
+    pub target_reached: &'static str,
+    pub target_reached_body: &'static str,
+    pub price: &'static str,
+    // The column headings of the table of changes in an email, and the kinds of change in it.
+    pub table_change: &'static str,
+    pub table_item: &'static str,
+    pub table_price: &'static str,
+    pub table_delta: &'static str,
+    pub change_added: &'static str,
+    pub change_price_drop: &'static str,
+    pub change_price_rise: &'static str,
+    pub change_sold: &'static str,
+    pub change_deleted: &'static str,
+    pub change_relisted: &'static str,
+    pub change_details_changed: &'static str,
+    pub change_listed: &'static str,
+}
+
+pub static JA: Messages = Messages {
+    item_added: "{0} が新たに追加されました",
+    price_changed: "{0} の価格が変更されました",
+    price_changed_body: "{0} 円 → {1} 円 ({2} 円, {3}%)",
+    price_changed_body_with_average: "{0} 円 → {1} 円 ({3} 円, {4}%)\n7日間の平均: {2} 円",
+    item_sold: "{0} が売約済みになりました",
+    item_deleted: "{0} が削除されました",
+    item_relisted: "{0} が再出品されました",
+    item_relisted_body: "販売価格は {0} 円です。",
+    item_details_changed: "アイテム情報が更新されました",
+    item_details_changed_body: "#{0} は {1} から {2} に変わりました。",
+    item_listing: "{0} が現在出品中です",
+    item_listing_body: "販売価格は {0} 円です。",
+    price_trend_up: "{0} の価格が {1} 回連続で上がっています",
+    price_trend_down: "{0} の価格が {1} 回連続で下がっています",
+    price_trend_body: "{0} 円",
+    digest: "{0} 件の変更がありました",
+    digest_added: "新着 {0} 件",
+    digest_price_drops: "値下げ {0} 件",
+    digest_price_rises: "値上げ {0} 件",
+    digest_sold: "売約済み {0} 件",
+    digest_deleted: "削除 {0} 件",
+    digest_relisted: "再出品 {0} 件",
+    digest_details_changed: "情報更新 {0} 件",
+    digest_listed: "出品中 {0} 件",
+    digest_minor_price_changes: "小幅な価格変更 {0} 件",
+    digest_top_drops: "値下げ幅の大きい順: {0}",
+    digest_drop: "{0} ({1})",
+    listing_digest: "現在出品中のアイテムは {0} 件です",
+    digest_more: " 他 {0} 件",
+    list_separator: "、",
+    unknown_sold: "#{0} が追跡前に売約済みになりました",
+    unknown_sold_body: "販売価格は {0} 円でした。",
+    slow_run: "実行に時間がかかっています",
+    slow_run_body: "今回は {0} 秒かかりました。",
+    slow_run_body_with_baseline: "今回は {0} 秒かかりました。(直近 {1} 回の平均: {2} 秒)",
+    scrape_failed: "スクレイピングに失敗しました",
+    scrape_failed_body: "{0}",
+    layout_changed: "サイトのレイアウトが変わった可能性があります",
+    layout_changed_body: "前回は {1} 件ありましたが、今回は {0} 件しか見つかりませんでした。見つからなかったアイテムは削除していません。",
+    target_reached: "{0} が目標価格に到達しました",
+    target_reached_body: "販売価格は {0} 円です。(目標価格: {1} 円)",
+    price: "{0} 円",
+    table_change: "変更",
+    table_item: "アイテム",
+    table_price: "価格",
+    table_delta: "差額",
+    change_added: "新着",
+    change_price_drop: "値下げ",
+    change_price_rise: "値上げ",
+    change_sold: "売約済み",
+    change_deleted: "削除",
+    change_relisted: "再出品",
+    change_details_changed: "情報更新",
+    change_listed: "出品中",
+};
+
+pub static EN: Messages = Messages {
+    item_added: "{0} was added",
+    price_changed: "The price of {0} changed",
+    price_changed_body: "{0} yen → {1} yen ({2} yen, {3}%)",
+    price_changed_body_with_average: "{0} yen → {1} yen ({3} yen, {4}%)\n7-day average: {2} yen",
+    item_sold: "{0} was sold",
+    item_deleted: "{0} was removed",
+    item_relisted: "{0} was relisted",
+    item_relisted_body: "It is listed for {0} yen.",
+    item_details_changed: "Item details were updated",
+    item_details_changed_body: "#{0} changed from {1} to {2}.",
+    item_listing: "{0} is listed",
+    item_listing_body: "It is listed for {0} yen.",
+    price_trend_up: "The price of {0} went up {1} times in a row",
+    price_trend_down: "The price of {0} went down {1} times in a row",
+    price_trend_body: "{0} yen",
+    digest: "{0} changes",
+    digest_added: "{0} new",
+    digest_price_drops: "{0} price drops",
+    digest_price_rises: "{0} price rises",
+    digest_sold: "{0} sold",
+    digest_deleted: "{0} removed",
+    digest_relisted: "{0} relisted",
+    digest_details_changed: "{0} updated",
+    digest_listed: "{0} listed",
+    digest_minor_price_changes: "{0} minor price changes",
+    digest_top_drops: "Biggest drops: {0}",
+    digest_drop: "{0} ({1})",
+    listing_digest: "{0} items are listed",
+    digest_more: " and {0} more",
+    list_separator: ", ",
+    unknown_sold: "#{0} was sold before it was tracked",
+    unknown_sold_body: "It sold for {0} yen.",
+    slow_run: "The run is taking long",
+    slow_run_body: "It took {0} seconds.",
+    slow_run_body_with_baseline: "It took {0} seconds. (average of the last {1} runs: {2} seconds)",
+    scrape_failed: "Scraping failed",
+    scrape_failed_body: "{0}",
+    layout_changed: "The layout of the site may have changed",
+    layout_changed_body:
+        "Only {0} items were found, down from {1} last time. The missing items were not removed.",
+    target_reached: "{0} reached the target price",
+    target_reached_body: "It is listed for {0} yen. (target: {1} yen)",
+    price: "{0} yen",
+    table_change: "Change",
+    table_item: "Item",
+    table_price: "Price",
+    table_delta: "Difference",
+    change_added: "New",
+    change_price_drop: "Price drop",
+    change_price_rise: "Price rise",
+    change_sold: "Sold",
+    change_deleted: "Removed",
+    change_relisted: "Relisted",
+    change_details_changed: "Updated",
+    change_listed: "Listed",
+};
+
+impl Messages {
+    pub fn for_lang(lang: Lang) -> &'static Messages {
+        match lang {
+            Lang::Ja => &JA,
+            Lang::En => &EN,
+        }
+    }
+
+    pub fn added(&self, item: &Item) -> String {
+        fill(self.item_added, &[item.full_name()])
+    }
+
+    pub fn price_changed(&self, item: &Item, old: i64, new: i64) -> (String, String) {
+        let body = match item.avg_price_7d {
+            Some(avg) => fill(
+                self.price_changed_body_with_average,
+                &[
+                    format_yen(old),
+                    format_yen(new),
+                    format_yen(avg),
+                    format_yen_delta(new - old),
+                    format_percent_change(old, new),
+                ],
+            ),
+            None => self.price_change(old, new),
+        };
+        (fill(self.price_changed, &[item.full_name()]), body)
+    }
+
+    // The prices with the difference and the percentage, e.g. `12,000 円 → 9,500 円 (-2,500 円, -20.8%)`.
+    pub fn price_change(&self, old: i64, new: i64) -> String {
+        fill(
+            self.price_changed_body,
+            &[
+                format_yen(old),
+                format_yen(new),
+                format_yen_delta(new - old),
+                format_percent_change(old, new),
+            ],
+        )
+    }
+
+    pub fn sold(&self, item: &Item) -> String {
+        fill(self.item_sold, &[item.full_name()])
+    }
+
+    pub fn deleted(&self, item: &Item) -> String {
+        fill(self.item_deleted, &[item.full_name()])
+    }
+
+    pub fn relisted(&self, item: &Item) -> (String, String) {
+        (
+            fill(self.item_relisted, &[item.full_name()]),
+            fill(self.item_relisted_body, &[item.price.to_string()]),
+        )
+    }
+
+    pub fn details_changed(&self, item: &Item, previous: &Item) -> (String, String) {
+        (
+            self.item_details_changed.to_owned(),
+            fill(
+                self.item_details_changed_body,
+                &[
+                    item.order_id.to_string(),
+                    previous.full_name(),
+                    item.full_name(),
+                ],
+            ),
+        )
+    }
+
+    pub fn listing(&self, item: &Item) -> (String, String) {
+        (
+            fill(self.item_listing, &[item.full_name()]),
+            fill(self.item_listing_body, &[item.price.to_string()]),
+        )
+    }
+
+    pub fn trend(
+        &self,
+        item: &Item,
+        direction: TrendDirection,
+        prices: &[i64],
+    ) -> (String, String) {
+        let title = match direction {
+            TrendDirection::Up => self.price_trend_up,
+            _ => self.price_trend_down,
+        };
+        (
+            fill(title, &[item.full_name(), (prices.len() - 1).to_string()]),
+            fill(self.price_trend_body, &[join_prices(prices)]),
+        )
+    }
+
+    pub fn digest(&self, digest: &DigestBuilder) -> (String, String) {
+        let counts = [
+            (self.digest_added, digest.added),
+            (self.digest_price_drops, digest.price_drops),
+            (self.digest_price_rises, digest.price_rises),
+            (self.digest_sold, digest.sold),
+            (self.digest_deleted, digest.deleted),
+            (self.digest_relisted, digest.relisted),
+            (self.digest_details_changed, digest.details_changed),
+            (self.digest_listed, digest.listed),
+            (self.digest_minor_price_changes, digest.minor_price_changes),
+        ]
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(template, count)| fill(template, &[count.to_string()]))
+        .collect::<Vec<_>>()
+        .join(self.list_separator);
+
+        let mut items = digest
+            .items
+            .iter()
+            .take(TOP_ITEMS)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(self.list_separator);
+        if digest.total() > TOP_ITEMS {
+            items.push_str(&fill(
+                self.digest_more,
+                &[(digest.total() - TOP_ITEMS).to_string()],
+            ));
+        }
+
+        let mut body = format!("{}\n{}", counts, items);
+        let top_drops = digest
+            .top_drops()
+            .into_iter()
+            .map(|(name, delta)| fill(self.digest_drop, &[name.clone(), self.price_delta(-delta)]))
+            .collect::<Vec<_>>();
+        if !top_drops.is_empty() {
+            body.push('\n');
+            body.push_str(&fill(
+                self.digest_top_drops,
+                &[top_drops.join(self.list_separator)],
+            ));
+        }
+
+        let title = if digest.is_listing() {
+            self.listing_digest
+        } else {
+            self.digest
+        };
+        (fill(title, &[digest.total().to_string()]), body)
+    }
+
+    pub fn unknown_sold(&self, order_id: i32, price: i64) -> (String, String) {
+        (
+            fill(self.unknown_sold, &[order_id.to_string()]),
+            fill(self.unknown_sold_body, &[price.to_string()]),
+        )
+    }
+
+    pub fn slow_run(&self, elapsed: Duration, baseline: Option<Duration>) -> (String, String) {
+        let elapsed = format_secs(elapsed);
+        let body = match baseline {
+            Some(baseline) => fill(
+                self.slow_run_body_with_baseline,
+                &[
+                    elapsed,
+                    runs::BASELINE_RUNS.to_string(),
+                    format_secs(baseline),
+                ],
+            ),
+            None => fill(self.slow_run_body, &[elapsed]),
+        };
+        (self.slow_run.to_owned(), body)
+    }
+
+    pub fn scrape_failed(&self, error: &str) -> (String, String) {
+        (
+            self.scrape_failed.to_owned(),
+            fill(self.scrape_failed_body, &[error.to_owned()]),
+        )
+    }
+
+    pub fn layout_changed(&self, found: usize, previous: usize) -> (String, String) {
+        (
+            self.layout_changed.to_owned(),
+            fill(
+                self.layout_changed_body,
+                &[found.to_string(), previous.to_string()],
+            ),
+        )
+    }
+
+    pub fn target_reached(&self, item: &Item, target_price: i64) -> (String, String) {
+        (
+            fill(self.target_reached, &[item.full_name()]),
+            fill(
+                self.target_reached_body,
+                &[item.price.to_string(), target_price.to_string()],
+            ),
+        )
+    }
+
+    pub fn price(&self, price: i64) -> String {
+        fill(self.price, &[format_yen(price)])
+    }
+
+    pub fn change(&self, change: DigestChange) -> &'static str {
+        match change {
+            DigestChange::Added => self.change_added,
+            DigestChange::PriceDrop => self.change_price_drop,
+            DigestChange::PriceRise => self.change_price_rise,
+            DigestChange::Sold => self.change_sold,
+            DigestChange::Deleted => self.change_deleted,
+            DigestChange::Relisted => self.change_relisted,
+            DigestChange::DetailsChanged => self.change_details_changed,
+            DigestChange::Listed => self.change_listed,
+        }
+    }
+
+    // A price difference, always with its sign, e.g. `-2,500 円`.
+    pub fn price_delta(&self, delta: i64) -> String {
+        fill(self.price, &[format_yen_delta(delta)])
+    }
+}
+
+// Replaces `{0}`, `{1}`, ... in the template with the arguments.
+fn fill(template: &str, args: &[String]) -> String {
+    args.iter()
+        .enumerate()
+        .fold(template.to_owned(), |text, (i, arg)| {
+            text.replace(&format!("{{{}}}", i), arg)
+        })
+}
+
+pub fn join_prices(prices: &[i64]) -> String {
+    prices
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+// Groups the digits by thousands, e.g. `12,000`.
+pub fn format_yen(amount: i64) -> String {
+    let digits = amount.unsigned_abs().to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+    if amount < 0 {
+        grouped.push('-');
+    }
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+// A difference, always with its sign, e.g. `+2,500` or `-2,500`.
+pub fn format_yen_delta(delta: i64) -> String {
+    if delta < 0 {
+        format_yen(delta)
+    } else {
+        format!("+{}", format_yen(delta))
+    }
+}
+
+// How much `new` differs from `old` in percent, with its sign and one decimal, e.g. `-20.8`.
+// A change from a price of 0 has no percentage, and is shown as infinite.
+pub fn format_percent_change(old: i64, new: i64) -> String {
+    if old == 0 {
+        return if new == 0 { "+0.0" } else { "+∞" }.to_owned();
+    }
+    format!("{:+.1}", (new - old) as f64 / old as f64 * 100.0)
+}
+
+pub fn format_secs(duration: Duration) -> String {
+    format!("{:.1}", duration.as_secs_f64())
+}
@@ -0,0 +1,684 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use isahc::http::Uri;
+use serde_derive::{Deserialize, Serialize};
+use strum_macros::EnumString;
+
+use super::db::RetryPolicy;
+use super::diff::{DiffOptions, TrendOptions};
+use super::filter::NotifyFilter;
+use super::http_client::ProxyConfig;
+use super::ignore::IgnoreList;
+use super::notifier;
+use super::parsers::ParserConfig;
+use super::scrape::{ScrapeConfig, DEFAULT_SELECTOR, DEFAULT_URL};
+use super::smtp;
+use super::summary::SummaryOutput;
+
+// The variables which each turn on a sink, as listed in `ConfigError::NoSink`.
+pub const SINK_VARS: &[&str] = &[
+    "FCM_SERVER_KEY",
+    "FCM_SERVICE_ACCOUNT_FILE",
+    "FCM_SERVICE_ACCOUNT_JSON",
+    "DISCORD_WEBHOOK_URL",
+    "LINE_NOTIFY_TOKEN",
+    "SLACK_WEBHOOK_URL",
+    "TELEGRAM_BOT_TOKEN",
+    "NTFY_TOPIC",
+    "EMAIL_TO",
+    "MATRIX_ROOM_ID",
+    "WEBHOOK_URL",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("environment variable `{0}` must be set")]
+    Missing(&'static str),
+    #[error("invalid value for `{key}`: `{value}`")]
+    InvalidValue { key: &'static str, value: String },
+    #[error("no notification sink is configured, set at least one of {}", SINK_VARS.join(", "))]
+    NoSink,
+}
+
+// What to do with an item which is already sold when it first appears on the site.
+#[derive(EnumString, PartialEq, Debug, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum UnknownSoldAction {
+    Ignore,
+    Log,
+    Notify,
+}
+
+// Whether each event is notified on its own or summarized into one notification per run.
+#[derive(EnumString, PartialEq, Debug, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum NotifyMode {
+    PerEvent,
+    Digest,
+}
+
+// What to do with an item whose price was parsed as 0, which almost always means broken markup.
+#[derive(EnumString, PartialEq, Debug, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum ZeroPriceAction {
+    Skip,
+    Accept,
+}
+
+// `--dry-run=readonly` never writes to the database, while `--dry-run=rollback`
+// applies the changes in a transaction and rolls it back. Neither sends notifications.
+#[derive(EnumString, PartialEq, Debug, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum DryRunMode {
+    #[strum(serialize = "readonly")]
+    ReadOnly,
+    Rollback,
+}
+
+#[derive(EnumString, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TrendDirection {
+    Down,
+    Up,
+    Both,
+}
+
+// The language of the notification texts.
+#[derive(EnumString, PartialEq, Debug, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum Lang {
+    Ja,
+    En,
+}
+
+// How to authenticate against FCM. A service-account key selects the HTTP v1 API.
+pub enum FcmAuth {
+    // `FCM_SERVER_KEY`
+    ServerKey(String),
+    // `FCM_SERVICE_ACCOUNT_FILE`
+    ServiceAccountFile(String),
+    // `FCM_SERVICE_ACCOUNT_JSON`
+    ServiceAccountJson(String),
+}
+
+pub struct FcmConfig {
+    pub auth: FcmAuth,
+    // `FCM_REGISTRATION_IDS`, comma-separated, or the single `FCM_REGISTRATION_ID`
+    pub registration_ids: Vec<String>,
+    // `HTTPS_PROXY`, since FCM is only reached over https
+    pub proxy: Option<Uri>,
+}
+
+// The most notifications each sink is sent per second, to stay within the quota of its service.
+// None sends as fast as `NOTIFY_CONCURRENCY` allows.
+#[derive(Default, Debug)]
+pub struct RateLimits {
+    // `FCM_RATE_PER_SEC`
+    pub fcm: Option<f64>,
+    // `DISCORD_RATE_PER_SEC`
+    pub discord: Option<f64>,
+    // `LINE_RATE_PER_SEC`
+    pub line: Option<f64>,
+    // `SLACK_RATE_PER_SEC`
+    pub slack: Option<f64>,
+    // `TELEGRAM_RATE_PER_SEC`
+    pub telegram: Option<f64>,
+    // `NTFY_RATE_PER_SEC`
+    pub ntfy: Option<f64>,
+    // `WEBHOOK_RATE_PER_SEC`
+    pub webhook: Option<f64>,
+    // `EMAIL_RATE_PER_SEC`
+    pub email: Option<f64>,
+    // `MATRIX_RATE_PER_SEC`
+    pub matrix: Option<f64>,
+}
+
+// The sound of the FCM notifications of each kind of `notifier::PRIORITY_KINDS`.
+#[derive(Debug)]
+pub struct Sounds {
+    // `SOUND_<KIND>`, e.g. `SOUND_PRICE_DROP`, with None for `none`, which sends that kind silently
+    pub by_kind: HashMap<String, Option<String>>,
+    // `SOUND_DEFAULT`, for the kinds without a sound of their own, with None for `none`
+    pub default: Option<String>,
+}
+
+impl Default for Sounds {
+    // FCM's own `default` sound for every kind.
+    fn default() -> Self {
+        Sounds {
+            by_kind: HashMap::new(),
+            default: Some("default".to_owned()),
+        }
+    }
+}
+
+pub struct TelegramConfig {
+    // `TELEGRAM_BOT_TOKEN`
+    pub bot_token: String,
+    // `TELEGRAM_CHAT_ID`
+    pub chat_id: String,
+}
+
+pub struct EmailConfig {
+    // `SMTP_URL`
+    pub server: smtp::Server,
+    // `SMTP_USERNAME` and `SMTP_PASSWORD`, to log in with
+    pub credentials: Option<smtp::Credentials>,
+    // `EMAIL_FROM`
+    pub from: String,
+    // `EMAIL_TO`, comma-separated
+    pub to: Vec<String>,
+}
+
+pub struct MatrixConfig {
+    // `MATRIX_HOMESERVER`, e.g. `https://matrix.example.com`
+    pub homeserver: String,
+    // `MATRIX_ROOM_ID`, e.g. `!abcdefg:example.com`
+    pub room_id: String,
+    // `MATRIX_ACCESS_TOKEN`, of a user who has joined the room
+    pub access_token: String,
+}
+
+pub struct NtfyConfig {
+    // `NTFY_SERVER`, ntfy.sh unless set
+    pub server: String,
+    // `NTFY_TOPIC`
+    pub topic: String,
+    // `NTFY_TOKEN`, for a server which requires an access token
+    pub token: Option<String>,
+}
+
+// Turns every notification off for a while, e.g. during a redesign of the site,
+// while the items are still scraped and written to the database.
+#[derive(Default, Debug)]
+pub struct Silence {
+    // `SILENCE_UNTIL`, an RFC 3339 timestamp
+    pub until: Option<DateTime<FixedOffset>>,
+    // `SILENCE_FILE`, silencing while this file exists
+    pub file: Option<PathBuf>,
+}
+
+impl Silence {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| now < until)
+            || self.file.as_ref().is_some_and(|file| file.exists())
+    }
+}
+
+pub struct Config {
+    // `DATABASE_URL`
+    pub database_url: String,
+    // `DB_POOL_SIZE`
+    pub db_pool_size: u32,
+    pub db_retry: RetryPolicy,
+    pub scrape: ScrapeConfig,
+    // None unless FCM credentials are set.
+    pub fcm: Option<FcmConfig>,
+    // `DISCORD_WEBHOOK_URL`
+    pub discord_webhook_url: Option<String>,
+    // `LINE_NOTIFY_TOKEN`
+    pub line_notify_token: Option<String>,
+    // `SLACK_WEBHOOK_URL`
+    pub slack_webhook_url: Option<String>,
+    // Set with `TELEGRAM_BOT_TOKEN`
+    pub telegram: Option<TelegramConfig>,
+    // Set with `NTFY_TOPIC`
+    pub ntfy: Option<NtfyConfig>,
+    // Set with `EMAIL_TO`
+    pub email: Option<EmailConfig>,
+    // Set with `MATRIX_ROOM_ID`
+    pub matrix: Option<MatrixConfig>,
+    // `WEBHOOK_URL`
+    pub webhook_url: Option<String>,
+    // `WEBHOOK_HEADERS`, comma-separated `Name: value` pairs
+    pub webhook_headers: Vec<(&'static str, String)>,
+    // `NOTIFY_CONCURRENCY`, the number of notifications in flight to each sink at once, at least 1.
+    pub notify_concurrency: usize,
+    pub silence: Silence,
+    // `--emit-events`
+    pub emit_events: bool,
+    // `--announce-current`, to notify every listed item once instead of the changes
+    pub announce_current: bool,
+    // `--summary` or `SUMMARY_PATH`
+    pub summary: Option<SummaryOutput>,
+    // `--watch`, `--watch N` or `WATCH_INTERVAL_SECS`
+    pub watch: bool,
+    // The N of `--watch N`, `WATCH_INTERVAL_SECS` or `POLL_INTERVAL_SECS`, in that order
+    pub poll_interval: Duration,
+    // `POLL_JITTER_SECS`
+    pub poll_jitter: Duration,
+    // `--dry-run[=readonly|rollback]`, or `DRY_RUN` for a read-only run
+    pub dry_run: Option<DryRunMode>,
+    // `ON_UNKNOWN_SOLD`
+    pub on_unknown_sold: UnknownSoldAction,
+    // `SLOW_RUN_THRESHOLD_SECS`
+    pub slow_run_threshold: Option<Duration>,
+    // `NOTIFY_ON_SLOW_RUN`
+    pub notify_on_slow_run: bool,
+    // `ALERT_ON_FAILURE`, to notify when the site could not be fetched or parsed
+    pub alert_on_failure: bool,
+    // `USE_LOC_KEYS`
+    pub use_loc_keys: bool,
+    // `NOTIFY_LANG`
+    pub lang: Lang,
+    // `COLLAPSE_NOTIFICATIONS`, so only the latest FCM notification of each item stays on the device
+    pub collapse_notifications: bool,
+    // `FCM_SEND_DATA`, to send every change as a data-only FCM message as well
+    pub fcm_send_data: bool,
+    // `FCM_HIGH_PRIORITY`, comma-separated kinds of `notifier::PRIORITY_KINDS`
+    pub fcm_high_priority: Vec<String>,
+    pub sounds: Sounds,
+    pub rate_limits: RateLimits,
+    // `NOTIFY_MODE`
+    pub notify_mode: NotifyMode,
+    pub diff: DiffOptions,
+    pub filter: NotifyFilter,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Config, ConfigError> {
+        let proxy = parse_proxy()?;
+        let fcm = parse_fcm(&proxy)?;
+        let discord_webhook_url = parse_var("DISCORD_WEBHOOK_URL")?;
+        let line_notify_token = parse_var("LINE_NOTIFY_TOKEN")?;
+        let slack_webhook_url = parse_var("SLACK_WEBHOOK_URL")?;
+        let telegram = match parse_var("TELEGRAM_BOT_TOKEN")? {
+            Some(bot_token) => Some(TelegramConfig {
+                bot_token,
+                chat_id: required_var("TELEGRAM_CHAT_ID")?,
+            }),
+            None => None,
+        };
+        let ntfy = match parse_var("NTFY_TOPIC")? {
+            Some(topic) => Some(NtfyConfig {
+                server: parse_var("NTFY_SERVER")?
+                    .unwrap_or_else(|| notifier::NTFY_DEFAULT_SERVER.to_owned()),
+                topic,
+                token: parse_var("NTFY_TOKEN")?,
+            }),
+            None => None,
+        };
+        let email = parse_email()?;
+        let matrix = match parse_var("MATRIX_ROOM_ID")? {
+            Some(room_id) => Some(MatrixConfig {
+                homeserver: required_var("MATRIX_HOMESERVER")?,
+                room_id,
+                access_token: required_var("MATRIX_ACCESS_TOKEN")?,
+            }),
+            None => None,
+        };
+        let webhook_url = parse_var("WEBHOOK_URL")?;
+        // At least one sink is needed.
+        if fcm.is_none()
+            && discord_webhook_url.is_none()
+            && line_notify_token.is_none()
+            && slack_webhook_url.is_none()
+            && telegram.is_none()
+            && ntfy.is_none()
+            && email.is_none()
+            && matrix.is_none()
+            && webhook_url.is_none()
+        {
+            return Err(ConfigError::NoSink);
+        }
+        let (watch, watch_interval) = parse_watch()?;
+
+        Ok(Config {
+            database_url: required_var("DATABASE_URL")?,
+            db_pool_size: parse_var("DB_POOL_SIZE")?.unwrap_or(4),
+            db_retry: RetryPolicy {
+                max_retries: parse_var("DB_MAX_RETRIES")?.unwrap_or(3),
+                base_delay: Duration::from_millis(parse_var("DB_RETRY_BASE_MS")?.unwrap_or(500)),
+            },
+            scrape: ScrapeConfig {
+                urls: list_var("TARGET_URL").unwrap_or_else(|| vec![DEFAULT_URL.to_owned()]),
+                selectors: list_var("TARGET_SELECTOR")
+                    .or_else(|| list_var("SCRAPE_SELECTOR"))
+                    .unwrap_or_else(|| vec![DEFAULT_SELECTOR.to_owned()]),
+                user_agent: required_var("USER_AGENT")?,
+                retry_on_body_read_failure: parse_flag("RETRY_ON_BODY_READ_FAILURE")?,
+                fetch_timeout: Duration::from_secs(parse_var("FETCH_TIMEOUT_SECS")?.unwrap_or(30)),
+                proxy,
+                parser: {
+                    let default = ParserConfig::default();
+                    ParserConfig {
+                        max_name_len: parse_var("MAX_NAME_LEN")?.unwrap_or(default.max_name_len),
+                        truncate_long_names: parse_flag("TRUNCATE_LONG_NAMES")?,
+                        section_delimiter: parse_var("SECTION_DELIMITER")?
+                            .unwrap_or(default.section_delimiter),
+                        blank_line_before_price: parse_var("BLANK_LINE_BEFORE_PRICE")?
+                            .unwrap_or(default.blank_line_before_price),
+                        scan_for_price: parse_flag("SCAN_FOR_PRICE")?,
+                    }
+                },
+            },
+            fcm,
+            discord_webhook_url,
+            line_notify_token,
+            slack_webhook_url,
+            telegram,
+            ntfy,
+            matrix,
+            email,
+            webhook_url,
+            webhook_headers: parse_headers("WEBHOOK_HEADERS")?,
+            notify_concurrency: parse_var("NOTIFY_CONCURRENCY")?.unwrap_or(16).max(1),
+            silence: Silence {
+                until: parse_timestamp("SILENCE_UNTIL")?,
+                file: parse_var("SILENCE_FILE")?,
+            },
+            emit_events: env::args().skip(1).any(|arg| arg == "--emit-events"),
+            announce_current: env::args().skip(1).any(|arg| arg == "--announce-current"),
+            summary: match parse_var("SUMMARY_PATH")? {
+                Some(path) => Some(SummaryOutput::File(path)),
+                None if env::args().skip(1).any(|arg| arg == "--summary") => {
+                    Some(SummaryOutput::Stdout)
+                }
+                None => None,
+            },
+            watch,
+            poll_interval: Duration::from_secs(match watch_interval {
+                Some(secs) => secs,
+                None => parse_var("POLL_INTERVAL_SECS")?.unwrap_or(300),
+            }),
+            poll_jitter: Duration::from_secs(parse_var("POLL_JITTER_SECS")?.unwrap_or(0)),
+            dry_run: parse_dry_run()?,
+            on_unknown_sold: parse_var("ON_UNKNOWN_SOLD")?.unwrap_or(UnknownSoldAction::Ignore),
+            slow_run_threshold: parse_var("SLOW_RUN_THRESHOLD_SECS")?.map(Duration::from_secs),
+            notify_on_slow_run: parse_flag("NOTIFY_ON_SLOW_RUN")?,
+            alert_on_failure: parse_flag("ALERT_ON_FAILURE")?,
+            use_loc_keys: parse_flag("USE_LOC_KEYS")?,
+            lang: parse_var("NOTIFY_LANG")?.unwrap_or(Lang::Ja),
+            collapse_notifications: parse_flag("COLLAPSE_NOTIFICATIONS")?,
+            fcm_send_data: parse_flag("FCM_SEND_DATA")?,
+            fcm_high_priority: parse_high_priority()?,
+            sounds: parse_sounds(),
+            rate_limits: RateLimits {
+                fcm: parse_rate("FCM_RATE_PER_SEC")?,
+                discord: parse_rate("DISCORD_RATE_PER_SEC")?,
+                line: parse_rate("LINE_RATE_PER_SEC")?,
+                slack: parse_rate("SLACK_RATE_PER_SEC")?,
+                telegram: parse_rate("TELEGRAM_RATE_PER_SEC")?,
+                ntfy: parse_rate("NTFY_RATE_PER_SEC")?,
+                webhook: parse_rate("WEBHOOK_RATE_PER_SEC")?,
+                email: parse_rate("EMAIL_RATE_PER_SEC")?,
+                matrix: parse_rate("MATRIX_RATE_PER_SEC")?,
+            },
+            notify_mode: parse_var("NOTIFY_MODE")?.unwrap_or(NotifyMode::PerEvent),
+            diff: DiffOptions {
+                zero_price: parse_var("ZERO_PRICE")?.unwrap_or(ZeroPriceAction::Skip),
+                trend: match parse_var("TREND_WINDOW")? {
+                    Some(window) => Some(TrendOptions {
+                        window,
+                        direction: parse_var("TREND_DIRECTION")?.unwrap_or(TrendDirection::Down),
+                    }),
+                    None => None,
+                },
+                min_expected_items: parse_var("MIN_EXPECTED_ITEMS")?.unwrap_or(0),
+                min_found_ratio: parse_var("MIN_FOUND_RATIO")?.unwrap_or(0.5),
+                notify_details_change: parse_flag("NOTIFY_ON_DETAILS_CHANGE")?,
+                ignore: IgnoreList::from_list(&env::var("IGNORE_PATTERNS").unwrap_or_default())
+                    .map_err(|value| ConfigError::InvalidValue {
+                        key: "IGNORE_PATTERNS",
+                        value,
+                    })?,
+            },
+            filter: NotifyFilter {
+                price_drop_threshold_percent: parse_var("PRICE_DROP_THRESHOLD_PERCENT")?
+                    .unwrap_or(0.0),
+                notify_on_increase: parse_flag("NOTIFY_ON_INCREASE")?,
+                min_price_delta_yen: parse_var("MIN_PRICE_DELTA_YEN")?.unwrap_or(0),
+                min_price_delta_per_event: parse_flag("MIN_PRICE_DELTA_PER_EVENT")?,
+                min_item_price_yen: parse_var("MIN_ITEM_PRICE_YEN")?.unwrap_or(0),
+                min_item_price_on_removal: parse_flag("MIN_ITEM_PRICE_ON_REMOVAL")?,
+            },
+        })
+    }
+}
+
+fn parse_fcm(proxy: &ProxyConfig) -> Result<Option<FcmConfig>, ConfigError> {
+    let auth = if let Some(path) = parse_var("FCM_SERVICE_ACCOUNT_FILE")? {
+        FcmAuth::ServiceAccountFile(path)
+    } else if let Some(json) = parse_var("FCM_SERVICE_ACCOUNT_JSON")? {
+        FcmAuth::ServiceAccountJson(json)
+    } else if let Some(key) = parse_var("FCM_SERVER_KEY")? {
+        FcmAuth::ServerKey(key)
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(FcmConfig {
+        auth,
+        registration_ids: match list_var("FCM_REGISTRATION_IDS") {
+            Some(ids) => ids,
+            None => vec![required_var("FCM_REGISTRATION_ID")?],
+        },
+        proxy: proxy.https.clone(),
+    }))
+}
+
+// `none` sends everything with normal priority, since an empty list falls back to the default.
+fn parse_high_priority() -> Result<Vec<String>, ConfigError> {
+    let kinds = match list_var("FCM_HIGH_PRIORITY") {
+        Some(kinds) if kinds == ["none"] => return Ok(vec![]),
+        Some(kinds) => kinds,
+        None => {
+            return Ok(notifier::DEFAULT_HIGH_PRIORITY
+                .iter()
+                .map(|&kind| kind.to_owned())
+                .collect())
+        }
+    };
+
+    match kinds
+        .iter()
+        .find(|kind| !notifier::PRIORITY_KINDS.contains(&kind.as_str()))
+    {
+        Some(kind) => Err(ConfigError::InvalidValue {
+            key: "FCM_HIGH_PRIORITY",
+            value: kind.clone(),
+        }),
+        None => Ok(kinds),
+    }
+}
+
+// The sound names are up to the app, so anything but `none` is taken as it is.
+fn parse_sounds() -> Sounds {
+    let sound = |key: &str| match env::var(key) {
+        Ok(value) if value.trim() == "none" => Some(None),
+        Ok(value) if !value.trim().is_empty() => Some(Some(value.trim().to_owned())),
+        _ => None,
+    };
+
+    let mut sounds = Sounds::default();
+    if let Some(default) = sound("SOUND_DEFAULT") {
+        sounds.default = default;
+    }
+    for &kind in notifier::PRIORITY_KINDS {
+        if let Some(value) = sound(&format!("SOUND_{}", kind.to_uppercase())) {
+            sounds.by_kind.insert(kind.to_owned(), value);
+        }
+    }
+    sounds
+}
+
+// Both spellings are common, and the upper case one is taken when both are set.
+fn parse_proxy() -> Result<ProxyConfig, ConfigError> {
+    Ok(ProxyConfig {
+        http: parse_var("HTTP_PROXY")?.or(parse_var("http_proxy")?),
+        https: parse_var("HTTPS_PROXY")?.or(parse_var("https_proxy")?),
+    })
+}
+
+// `--watch` alone polls every `POLL_INTERVAL_SECS`, while `--watch N`, `--watch=N` or
+// `WATCH_INTERVAL_SECS` also sets the interval.
+fn parse_watch() -> Result<(bool, Option<u64>), ConfigError> {
+    let mut args = env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        let value = if let Some(value) = arg.strip_prefix("--watch=") {
+            value.to_owned()
+        } else if arg == "--watch" {
+            match args.next_if(|next| !next.starts_with("--")) {
+                Some(value) => value,
+                None => return Ok((true, parse_var("WATCH_INTERVAL_SECS")?)),
+            }
+        } else {
+            continue;
+        };
+
+        return value.parse().map(|secs| (true, Some(secs))).map_err(|_| {
+            ConfigError::InvalidValue {
+                key: "--watch",
+                value,
+            }
+        });
+    }
+
+    let interval = parse_var("WATCH_INTERVAL_SECS")?;
+    Ok((interval.is_some(), interval))
+}
+
+fn parse_dry_run() -> Result<Option<DryRunMode>, ConfigError> {
+    for arg in env::args().skip(1) {
+        if arg == "--dry-run" {
+            return Ok(Some(DryRunMode::ReadOnly));
+        }
+        if let Some(mode) = arg.strip_prefix("--dry-run=") {
+            return mode
+                .parse()
+                .map(Some)
+                .map_err(|_| ConfigError::InvalidValue {
+                    key: "--dry-run",
+                    value: mode.to_owned(),
+                });
+        }
+    }
+
+    if parse_flag("DRY_RUN")? {
+        return Ok(Some(DryRunMode::ReadOnly));
+    }
+
+    Ok(None)
+}
+
+fn required_var(key: &'static str) -> Result<String, ConfigError> {
+    env::var(key).map_err(|_| ConfigError::Missing(key))
+}
+
+// Unset or empty variables are treated as None, so .env files can keep a blank placeholder.
+fn parse_var<T: FromStr>(key: &'static str) -> Result<Option<T>, ConfigError> {
+    match env::var(key) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidValue { key, value }),
+        _ => Ok(None),
+    }
+}
+
+// Splits a comma-separated variable, returning None if it has no entries.
+fn list_var(key: &'static str) -> Option<Vec<String>> {
+    let list = env::var(key)
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+
+    if list.is_empty() {
+        None
+    } else {
+        Some(list)
+    }
+}
+
+// The email sink is set up by `EMAIL_TO`, and then needs `SMTP_URL` and `EMAIL_FROM` too.
+// `SMTP_USERNAME` and `SMTP_PASSWORD` only make sense together.
+fn parse_email() -> Result<Option<EmailConfig>, ConfigError> {
+    let to = match list_var("EMAIL_TO") {
+        Some(to) => to,
+        None => return Ok(None),
+    };
+    let url = required_var("SMTP_URL")?;
+    let server = smtp::Server::from_url(&url).ok_or(ConfigError::InvalidValue {
+        key: "SMTP_URL",
+        value: url,
+    })?;
+    let credentials = match (
+        parse_var::<String>("SMTP_USERNAME")?,
+        parse_var::<String>("SMTP_PASSWORD")?,
+    ) {
+        (Some(username), Some(password)) => Some(smtp::Credentials { username, password }),
+        (None, None) => None,
+        (Some(_), None) => return Err(ConfigError::Missing("SMTP_PASSWORD")),
+        (None, Some(_)) => return Err(ConfigError::Missing("SMTP_USERNAME")),
+    };
+
+    Ok(Some(EmailConfig {
+        server,
+        credentials,
+        from: required_var("EMAIL_FROM")?,
+        to,
+    }))
+}
+
+// A rate of sends per second, which has to be above 0, e.g. `0.5` for one every two seconds.
+fn parse_rate(key: &'static str) -> Result<Option<f64>, ConfigError> {
+    match parse_var::<f64>(key)? {
+        Some(rate) if !(rate > 0.0 && rate.is_finite()) => Err(ConfigError::InvalidValue {
+            key,
+            value: rate.to_string(),
+        }),
+        rate => Ok(rate),
+    }
+}
+
+fn parse_timestamp(key: &'static str) -> Result<Option<DateTime<FixedOffset>>, ConfigError> {
+    match env::var(key) {
+        Ok(value) if !value.trim().is_empty() => DateTime::parse_from_rfc3339(value.trim())
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidValue { key, value }),
+        _ => Ok(None),
+    }
+}
+
+// Parses comma-separated `Name: value` pairs, returning an empty list when unset.
+// surf only takes `&'static str` header names, so the names are leaked, once at startup.
+fn parse_headers(key: &'static str) -> Result<Vec<(&'static str, String)>, ConfigError> {
+    use surf::http::header::{HeaderName, HeaderValue};
+
+    list_var(key)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|header| match header.split_once(':') {
+            Some((name, value))
+                if HeaderName::from_bytes(name.trim().as_bytes()).is_ok()
+                    && HeaderValue::from_str(value.trim()).is_ok() =>
+            {
+                let name: &'static str = Box::leak(name.trim().to_owned().into_boxed_str());
+                Ok((name, value.trim().to_owned()))
+            }
+            _ => Err(ConfigError::InvalidValue { key, value: header }),
+        })
+        .collect()
+}
+
+// Accepts `1`/`true`/`yes`/`on` and `0`/`false`/`no`/`off`, defaulting to false when unset.
+fn parse_flag(key: &'static str) -> Result<bool, ConfigError> {
+    match env::var(key) {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "" | "0" | "false" | "no" | "off" => Ok(false),
+            "1" | "true" | "yes" | "on" => Ok(true),
+            _ => Err(ConfigError::InvalidValue { key, value }),
+        },
+        Err(_) => Ok(false),
+    }
+}
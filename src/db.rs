@@ -0,0 +1,147 @@
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+use std::time::Duration;
+
+use diesel::r2d2::{ConnectionManager, PoolError, PooledConnection};
+use tracing::warn;
+
+use super::diff::Change;
+use super::history;
+use super::models::Item;
+use super::notifier::NotificationEvent;
+use super::pending;
+use super::schema::item::dsl as table;
+
+pub type Pool = diesel::r2d2::Pool<ConnectionManager<MysqlConnection>>;
+pub type Conn = PooledConnection<ConnectionManager<MysqlConnection>>;
+
+// How connecting to the database is retried, so a brief restart of MySQL doesn't lose a run.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    // `DB_MAX_RETRIES`, the attempts after the first one
+    pub max_retries: u32,
+    // `DB_RETRY_BASE_MS`, the wait before the first retry, doubled before each one after it
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    // Calls `f` until it succeeds or has been retried `max_retries` times, returning the last error.
+    // Diesel blocks anyway, so the wait blocks the thread as well.
+    pub fn retry<T, E: std::fmt::Display>(
+        &self,
+        what: &str,
+        mut f: impl FnMut() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut retries = 0;
+        loop {
+            match f() {
+                Err(e) if retries < self.max_retries => {
+                    let delay = self.base_delay.saturating_mul(2u32.saturating_pow(retries));
+                    retries += 1;
+                    warn!(error = %e, retry = retries, ?delay, "failed to {}, retrying", what);
+                    std::thread::sleep(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+pub fn build_pool(database_url: &str, size: u32, retry: &RetryPolicy) -> Result<Pool, PoolError> {
+    retry.retry("connect to the database", || {
+        Pool::builder()
+            .max_size(size)
+            .build(ConnectionManager::new(database_url))
+    })
+}
+
+// Takes a connection from the pool, retrying while the database is unreachable.
+pub fn get(pool: &Pool, retry: &RetryPolicy) -> Result<Conn, PoolError> {
+    retry.retry("get a database connection", || pool.get())
+}
+
+pub fn load_db_items(conn: &MysqlConnection) -> QueryResult<Vec<Item>> {
+    table::item.load(conn)
+}
+
+// Writes the changes computed by `diff` to the database in a single transaction,
+// so a failure halfway through leaves every item as it was.
+//
+// The notifications need the rows as they were before this run, so `diff` compares them in
+// memory first and only then are the found items written, inserted or updated alike by one
+// `REPLACE INTO`. A row which another overlapping run has inserted in the meantime is then
+// overwritten instead of failing the whole transaction on its duplicated key.
+pub fn apply(conn: &MysqlConnection, changes: &[Change]) -> QueryResult<()> {
+    conn.transaction(|| apply_changes(conn, changes))
+}
+
+// `apply`, and in the same transaction, queues the notifications about the changes in `pending`.
+// Either both are committed or neither, so a run which stops after the commit leaves the
+// notifications for the next run to send, while a rolled back change is never notified.
+pub fn apply_and_enqueue(
+    conn: &MysqlConnection,
+    changes: &[Change],
+    notifications: &[NotificationEvent],
+) -> QueryResult<()> {
+    conn.transaction(|| {
+        apply_changes(conn, changes)?;
+        pending::enqueue(conn, notifications)
+    })
+}
+
+fn apply_changes(conn: &MysqlConnection, changes: &[Change]) -> QueryResult<()> {
+    let mut prices = Vec::new();
+
+    for change in changes {
+        match change {
+            Change::Insert(_) | Change::Update(_) => {}
+            Change::MarkSold { order_id, price } => {
+                diesel::update(table::item.find(order_id))
+                    .set((table::has_sold.eq(true), table::price.eq(price)))
+                    .execute(conn)?;
+            }
+            Change::Delete(order_id) => {
+                diesel::delete(table::item.find(order_id)).execute(conn)?;
+            }
+            Change::RecordPrice { order_id, price } => prices.push((*order_id, *price)),
+        }
+    }
+
+    let items = upserts(changes);
+    if !items.is_empty() {
+        diesel::replace_into(table::item)
+            .values(items)
+            .execute(conn)?;
+    }
+    if !prices.is_empty() {
+        history::record_prices(conn, &prices)?;
+    }
+
+    Ok(())
+}
+
+// The rows to write whole, whether they are new or not, in the order of the changes.
+// Nothing references an item, so replacing its row loses nothing.
+pub fn upserts(changes: &[Change]) -> Vec<&Item> {
+    changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::Insert(item) | Change::Update(item) => Some(item),
+            _ => None,
+        })
+        .collect()
+}
+
+// Applies the changes inside a transaction which is always rolled back,
+// so a dry run exercises the same queries without writing anything.
+pub fn apply_and_rollback(conn: &MysqlConnection, changes: &[Change]) -> QueryResult<()> {
+    let result = conn.transaction(|| {
+        apply_changes(conn, changes)?;
+        Err(diesel::result::Error::RollbackTransaction)
+    });
+
+    match result {
+        Err(diesel::result::Error::RollbackTransaction) => Ok(()),
+        result => result,
+    }
+}
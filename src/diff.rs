@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDateTime;
+use tracing::{debug, error, info_span, warn};
+
+use super::config::{TrendDirection, ZeroPriceAction};
+use super::events::ItemEvent;
+use super::history;
+use super::ignore::IgnoreList;
+use super::models::{Item, PriceHistory};
+use super::parsers::ItemSection;
+
+// A write to the database which has to happen for the diff to be recorded.
+#[derive(Debug)]
+pub enum Change {
+    Insert(Item),
+    Update(Item),
+    MarkSold { order_id: i32, price: i64 },
+    Delete(i32),
+    RecordPrice { order_id: i32, price: i64 },
+}
+
+pub struct DiffOptions {
+    // `ZERO_PRICE`
+    pub zero_price: ZeroPriceAction,
+    // Trend notifications are disabled unless `TREND_WINDOW` is set.
+    pub trend: Option<TrendOptions>,
+    // `MIN_EXPECTED_ITEMS`, below which nothing is deleted.
+    pub min_expected_items: usize,
+    // `MIN_FOUND_RATIO`, the fraction of the items in the database which has to be found
+    // for the missing ones to be deleted.
+    pub min_found_ratio: f64,
+    // `NOTIFY_ON_DETAILS_CHANGE`, the row is updated either way.
+    pub notify_details_change: bool,
+    // `IGNORE_PATTERNS`, the items whose events are dropped
+    pub ignore: IgnoreList,
+}
+
+pub struct TrendOptions {
+    // `TREND_WINDOW`, the number of consecutive price changes in the same direction.
+    pub window: usize,
+    // `TREND_DIRECTION`
+    pub direction: TrendDirection,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            zero_price: ZeroPriceAction::Skip,
+            trend: None,
+            min_expected_items: 0,
+            min_found_ratio: 0.5,
+            notify_details_change: false,
+            ignore: IgnoreList::default(),
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Diff {
+    // Every change to notify, also written by `--emit-events`.
+    pub events: Vec<ItemEvent>,
+    pub changes: Vec<Change>,
+    // Items which were already sold when they first appeared on the site.
+    pub unknown_sold: Vec<ItemSection>,
+}
+
+impl Diff {
+    // Drops the deletions of the items missing from the site, when the site can't be trusted
+    // to list every item, and returns how many there were.
+    pub fn skip_deletions(&mut self) -> usize {
+        let before = self.changes.len();
+        self.changes
+            .retain(|change| !matches!(change, Change::Delete(_)));
+        self.events
+            .retain(|event| !matches!(event, ItemEvent::Deleted { .. }));
+
+        before - self.changes.len()
+    }
+
+    // Drops the events of the ignored items, while keeping their changes to the database.
+    fn drop_ignored(&mut self, ignore: &IgnoreList) {
+        if ignore.is_empty() {
+            return;
+        }
+
+        self.events.retain(|event| {
+            let item = event.item();
+            let ignored = ignore.matches(item);
+            if ignored {
+                debug!(order_id = item.order_id, name = %item.full_name(), ?event, "ignored an event");
+            }
+            !ignored
+        });
+        self.unknown_sold.retain(|section| {
+            let ignored = ignore.matches_order_id(section.order_id);
+            if ignored {
+                debug!(order_id = section.order_id, "ignored an unknown sold item");
+            }
+            !ignored
+        });
+    }
+}
+
+// The order ids of the items found at another price than in the database, the only ones whose
+// history `diff` looks at.
+pub fn repriced(found: &[ItemSection], db_items: &[Item]) -> Vec<i32> {
+    let prices = db_items
+        .iter()
+        .map(|item| (item.order_id, item.price))
+        .collect::<HashMap<_, _>>();
+    found
+        .iter()
+        .filter_map(|section| section.item.as_ref())
+        .filter(|item| prices.get(&item.order_id).is_some_and(|&price| price != item.price))
+        .map(|item| item.order_id)
+        .collect()
+}
+
+// Compares the items found on the site with the ones in the database.
+// `history` is the price history of the items in the database, oldest first, keyed by order id.
+pub fn diff(
+    found: Vec<ItemSection>,
+    db_items: Vec<Item>,
+    history: &HashMap<i32, Vec<PriceHistory>>,
+    options: &DiffOptions,
+) -> Diff {
+    let _span = info_span!("diff", found = found.len(), db_items = db_items.len()).entered();
+    let mut diff = Diff::default();
+    let average_since = history::average_since();
+
+    let by_order_id = db_items
+        .iter()
+        .map(|item| (item.order_id, item))
+        .collect::<HashMap<_, _>>();
+    let mut seen = HashSet::new();
+    let found_count = found.len();
+
+    for section in found {
+        seen.insert(section.order_id);
+
+        if section.price == 0 && options.zero_price == ZeroPriceAction::Skip {
+            // Leave the item untouched rather than notifying "now 0 yen" or deleting it.
+            warn!(
+                order_id = section.order_id,
+                "skipped item since its price was parsed as 0"
+            );
+            continue;
+        }
+
+        if let (Some(db_item), Some(found_item)) =
+            (by_order_id.get(&section.order_id), &section.item)
+        {
+            if options.notify_details_change && details_changed(db_item, found_item) {
+                diff.events.push(ItemEvent::DetailsChanged {
+                    item: found_item.clone(),
+                    previous: (*db_item).clone(),
+                });
+            }
+        }
+
+        match (by_order_id.get(&section.order_id), section.item) {
+            (Some(db_item), Some(mut found_item)) if db_item.has_sold => {
+                // 売約済みだった出品が再開された
+                if found_item.price != db_item.price {
+                    let item_history = history
+                        .get(&found_item.order_id)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    found_item.avg_price_7d =
+                        average_with(item_history, average_since, found_item.price);
+                    diff.changes.push(Change::RecordPrice {
+                        order_id: found_item.order_id,
+                        price: found_item.price,
+                    });
+                } else {
+                    found_item.avg_price_7d = db_item.avg_price_7d;
+                }
+
+                diff.changes.push(Change::Update(found_item.clone()));
+                diff.events.push(ItemEvent::Relisted { item: found_item });
+            }
+            (Some(db_item), Some(mut found_item)) => {
+                if found_item.price != db_item.price {
+                    // price changed
+                    let item_history = history
+                        .get(&found_item.order_id)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+
+                    found_item.avg_price_7d =
+                        average_with(item_history, average_since, found_item.price);
+
+                    let trend = options.trend.as_ref().and_then(|trend| {
+                        let mut prices = item_history.iter().map(|h| h.price).collect::<Vec<_>>();
+                        prices.push(found_item.price);
+                        find_trend(&prices, trend)
+                    });
+
+                    diff.changes.push(Change::RecordPrice {
+                        order_id: found_item.order_id,
+                        price: found_item.price,
+                    });
+                    diff.changes.push(Change::Update(found_item.clone()));
+                    if let Some((direction, prices)) = trend {
+                        diff.events.push(ItemEvent::Trend {
+                            item: found_item.clone(),
+                            direction,
+                            prices,
+                        });
+                    }
+                    diff.events.push(ItemEvent::PriceChanged {
+                        old_price: db_item.price,
+                        new_price: found_item.price,
+                        item: found_item,
+                        image_url: section.image_url,
+                    });
+                } else {
+                    found_item.avg_price_7d = db_item.avg_price_7d;
+                    // Rows that did not change at all are not written back.
+                    if found_item != **db_item {
+                        diff.changes.push(Change::Update(found_item));
+                    }
+                }
+            }
+            (Some(db_item), None) => {
+                if !db_item.has_sold {
+                    diff.changes.push(Change::MarkSold {
+                        order_id: section.order_id,
+                        price: section.price,
+                    });
+                    diff.events.push(ItemEvent::Sold {
+                        item: (*db_item).clone(),
+                    });
+                }
+            }
+            (None, Some(mut found_item)) => {
+                found_item.avg_price_7d = Some(found_item.price);
+
+                diff.changes.push(Change::RecordPrice {
+                    order_id: found_item.order_id,
+                    price: found_item.price,
+                });
+                diff.changes.push(Change::Insert(found_item.clone()));
+                diff.events.push(ItemEvent::Added { item: found_item });
+            }
+            (None, None) => {
+                // 売却済みかつDBにも情報がない場合、呼び出し元に任せる
+                diff.unknown_sold.push(ItemSection {
+                    item: None,
+                    order_id: section.order_id,
+                    price: section.price,
+                    image_url: section.image_url,
+                });
+            }
+        }
+    }
+
+    // A broken selector or a truncated page looks like almost every item was deleted.
+    if found_count < options.min_expected_items
+        || (found_count as f64) < db_items.len() as f64 * options.min_found_ratio
+    {
+        error!(
+            found = found_count,
+            db_items = db_items.len(),
+            "found suspiciously few items, skipped deleting the missing ones"
+        );
+    } else {
+        // dbにはあるけどサイトにはないアイテムは削除されたとみなす
+        for item in &db_items {
+            if !seen.contains(&item.order_id) {
+                diff.changes.push(Change::Delete(item.order_id));
+                diff.events.push(ItemEvent::Deleted { item: item.clone() });
+            }
+        }
+    }
+
+    diff.drop_ignored(&options.ignore);
+    diff
+}
+
+// Whether the row stored for the order id describes another skin than the one found now.
+fn details_changed(db_item: &Item, found_item: &Item) -> bool {
+    db_item.name != found_item.name
+        || db_item.kind != found_item.kind
+        || db_item.exterior != found_item.exterior
+}
+
+// The average of the prices since `since`, including the new `price`.
+fn average_with(item_history: &[PriceHistory], since: NaiveDateTime, price: i64) -> Option<i64> {
+    let mut recent = history::prices_since(item_history, since);
+    recent.push(price);
+    history::average(&recent)
+}
+
+// Returns the direction and the prices if the last `window` changes all went the same way.
+fn find_trend(prices: &[i64], options: &TrendOptions) -> Option<(TrendDirection, Vec<i64>)> {
+    if options.window == 0 || prices.len() <= options.window {
+        return None;
+    }
+
+    let recent = &prices[prices.len() - options.window - 1..];
+    let down = recent.windows(2).all(|w| w[1] < w[0]);
+    let up = recent.windows(2).all(|w| w[1] > w[0]);
+
+    let direction = match options.direction {
+        TrendDirection::Down | TrendDirection::Both if down => TrendDirection::Down,
+        TrendDirection::Up | TrendDirection::Both if up => TrendDirection::Up,
+        _ => return None,
+    };
+
+    Some((direction, recent.to_vec()))
+}
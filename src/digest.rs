@@ -0,0 +1,162 @@
+use serde_derive::{Deserialize, Serialize};
+
+use super::events::ItemEvent;
+use super::models::Item;
+
+// Number of item names listed in the body of a digest.
+pub const TOP_ITEMS: usize = 3;
+
+// What happened to an item, as listed in a digest.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum DigestChange {
+    Added,
+    PriceDrop,
+    PriceRise,
+    Sold,
+    Deleted,
+    Relisted,
+    DetailsChanged,
+    // Pushed by `--announce-current`.
+    Listed,
+}
+
+// One item of a digest, with its price now and how many yen it moved for a price change.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DigestRow {
+    pub change: DigestChange,
+    pub name: String,
+    pub price: i64,
+    pub delta: Option<i64>,
+}
+
+impl DigestRow {
+    // The row of the event, or None for a trend, which comes with a price change of its own.
+    pub fn from_event(event: &ItemEvent) -> Option<Self> {
+        let (change, delta) = match event {
+            ItemEvent::Added { .. } => (DigestChange::Added, None),
+            ItemEvent::PriceChanged {
+                old_price,
+                new_price,
+                ..
+            } if new_price < old_price => (DigestChange::PriceDrop, Some(new_price - old_price)),
+            ItemEvent::PriceChanged {
+                old_price,
+                new_price,
+                ..
+            } => (DigestChange::PriceRise, Some(new_price - old_price)),
+            ItemEvent::Sold { .. } => (DigestChange::Sold, None),
+            ItemEvent::Deleted { .. } => (DigestChange::Deleted, None),
+            ItemEvent::Relisted { .. } => (DigestChange::Relisted, None),
+            ItemEvent::DetailsChanged { .. } => (DigestChange::DetailsChanged, None),
+            ItemEvent::Trend { .. } => return None,
+        };
+        let item = event.item();
+
+        Some(DigestRow {
+            change,
+            name: item.full_name(),
+            price: item.price,
+            delta,
+        })
+    }
+}
+
+// Accumulates the events of a run into a single summary notification.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct DigestBuilder {
+    pub added: usize,
+    pub price_drops: usize,
+    pub price_rises: usize,
+    pub sold: usize,
+    pub deleted: usize,
+    pub relisted: usize,
+    pub details_changed: usize,
+    // Items which are only listed, pushed by `--announce-current`.
+    pub listed: usize,
+    // Price changes smaller than `min_price_delta`, only counted and not listed.
+    pub minor_price_changes: usize,
+    // Names of the items in the order their events came in.
+    pub items: Vec<String>,
+    // Every change listed, in the same order, for the sinks which show them all.
+    pub rows: Vec<DigestRow>,
+    // The names of the items whose price dropped, with how many yen, in the order they came in.
+    pub drops: Vec<(String, i64)>,
+    // `MIN_PRICE_DELTA_YEN`, the smallest change in yen which is listed, 0 to list every one.
+    pub min_price_delta: i64,
+}
+
+impl DigestBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_min_price_delta(min_price_delta: i64) -> Self {
+        DigestBuilder {
+            min_price_delta,
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, event: &ItemEvent) -> &mut Self {
+        let row = match DigestRow::from_event(event) {
+            Some(row) => row,
+            // Already counted by the price change that comes with it.
+            None => return self,
+        };
+
+        match (row.change, row.delta) {
+            (DigestChange::PriceDrop, Some(delta)) | (DigestChange::PriceRise, Some(delta))
+                if delta.abs() < self.min_price_delta =>
+            {
+                self.minor_price_changes += 1;
+                return self;
+            }
+            (DigestChange::PriceDrop, delta) => {
+                self.price_drops += 1;
+                self.drops
+                    .push((row.name.clone(), -delta.unwrap_or_default()));
+            }
+            (DigestChange::PriceRise, _) => self.price_rises += 1,
+            (DigestChange::Added, _) => self.added += 1,
+            (DigestChange::Sold, _) => self.sold += 1,
+            (DigestChange::Deleted, _) => self.deleted += 1,
+            (DigestChange::Relisted, _) => self.relisted += 1,
+            (DigestChange::DetailsChanged, _) => self.details_changed += 1,
+            (DigestChange::Listed, _) => self.listed += 1,
+        }
+
+        self.items.push(row.name.clone());
+        self.rows.push(row);
+        self
+    }
+
+    pub fn push_listed(&mut self, item: &Item) -> &mut Self {
+        self.listed += 1;
+        self.items.push(item.full_name());
+        self.rows.push(DigestRow {
+            change: DigestChange::Listed,
+            name: item.full_name(),
+            price: item.price,
+            delta: None,
+        });
+        self
+    }
+
+    // Whether the digest only lists the current items, and has no changes.
+    pub fn is_listing(&self) -> bool {
+        self.listed > 0 && self.listed == self.total()
+    }
+
+    // The changes listed, which leaves out the minor price changes.
+    pub fn total(&self) -> usize {
+        self.items.len()
+    }
+
+    // The `TOP_ITEMS` biggest drops, the biggest first.
+    pub fn top_drops(&self) -> Vec<&(String, i64)> {
+        let mut drops = self.drops.iter().collect::<Vec<_>>();
+        drops.sort_by_key(|(_, delta)| std::cmp::Reverse(*delta));
+        drops.truncate(TOP_ITEMS);
+        drops
+    }
+}
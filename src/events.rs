@@ -0,0 +1,72 @@
+use std::io::{self, Write};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::config::TrendDirection;
+use super::models::Item;
+
+// A change found while comparing the site with the database, with the item as it is now
+// and, for a price change, the price before it. `diff` only returns these, and the sinks,
+// the digest and `--emit-events` only consume them, so detecting a change never needs FCM.
+// Serialized as one JSON object per line for `--emit-events`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ItemEvent {
+    Added {
+        item: Item,
+    },
+    PriceChanged {
+        item: Item,
+        old_price: i64,
+        new_price: i64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        image_url: Option<String>,
+    },
+    Sold {
+        item: Item,
+    },
+    Deleted {
+        item: Item,
+    },
+    // An item which was sold is available again.
+    Relisted {
+        item: Item,
+    },
+    // The price kept moving in one direction over the last `TREND_WINDOW` changes.
+    Trend {
+        item: Item,
+        direction: TrendDirection,
+        // Oldest first, ending with the current price.
+        prices: Vec<i64>,
+    },
+    // The name, kind or exterior of the item differs from the row stored for its order id,
+    // since the order was reused or the parser now reads the section differently.
+    DetailsChanged {
+        item: Item,
+        // The row as it was stored before this run.
+        previous: Item,
+    },
+}
+
+impl ItemEvent {
+    pub fn item(&self) -> &Item {
+        match self {
+            ItemEvent::Added { item }
+            | ItemEvent::PriceChanged { item, .. }
+            | ItemEvent::Sold { item }
+            | ItemEvent::Deleted { item }
+            | ItemEvent::Relisted { item }
+            | ItemEvent::Trend { item, .. }
+            | ItemEvent::DetailsChanged { item, .. } => item,
+        }
+    }
+}
+
+// Writes each event as a single line of JSON, so the output can be piped into `jq` and friends.
+pub fn write_ndjson(mut out: impl Write, events: &[ItemEvent]) -> io::Result<()> {
+    for event in events {
+        serde_json::to_writer(&mut out, event)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
@@ -0,0 +1,39 @@
+use serde_with_macros::skip_serializing_none;
+
+use serde_derive::Serialize;
+
+use crate::fcm::message::Priority;
+
+/// Android specific options of a message, sent as the `android` block of the HTTP v1 API.
+/// With the legacy API its values fill in the top-level fields of the message that are not set.
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Default, Clone)]
+pub struct AndroidConfig {
+    pub(crate) collapse_key: Option<String>,
+    pub(crate) priority: Option<Priority>,
+    pub(crate) ttl: Option<i32>,
+}
+
+impl AndroidConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Identify a group of messages that can be collapsed on Android.
+    pub fn collapse_key(&mut self, collapse_key: impl Into<String>) -> &mut Self {
+        self.collapse_key = Some(collapse_key.into());
+        self
+    }
+
+    /// The delivery priority on Android.
+    pub fn priority(&mut self, priority: Priority) -> &mut Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// How long (in seconds) to keep the message if the device is offline.
+    pub fn ttl(&mut self, ttl: i32) -> &mut Self {
+        self.ttl = Some(ttl);
+        self
+    }
+}
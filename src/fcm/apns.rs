@@ -0,0 +1,91 @@
+use serde_with_macros::skip_serializing_none;
+
+use serde_derive::Serialize;
+
+/// iOS specific options of a notification, sent as the `apns` block of the message.
+/// With the HTTP v1 API the flat `badge` of the notification and the `content_available`
+/// and `mutable_content` of the message fill in what is not set here.
+#[derive(Serialize, Debug, PartialEq, Default, Clone)]
+pub struct ApnsConfig {
+    pub(crate) payload: ApnsPayload,
+}
+
+#[derive(Serialize, Debug, PartialEq, Default, Clone)]
+pub struct ApnsPayload {
+    pub(crate) aps: Aps,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Default, Clone)]
+pub struct Aps {
+    pub(crate) alert: Option<ApnsAlert>,
+    pub(crate) badge: Option<u32>,
+    pub(crate) sound: Option<ApnsSound>,
+    #[serde(rename = "content-available")]
+    pub(crate) content_available: Option<i32>,
+    #[serde(rename = "mutable-content")]
+    pub(crate) mutable_content: Option<i32>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct ApnsAlert {
+    pub(crate) title: Option<String>,
+    pub(crate) body: Option<String>,
+}
+
+/// A sound file in the app bundle, `default` for the system sound, or a critical alert,
+/// which plays even when the device is muted and needs an entitlement from Apple.
+#[derive(Serialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum ApnsSound {
+    Named(String),
+    Critical {
+        critical: i32,
+        name: String,
+        volume: f32,
+    },
+}
+
+impl ApnsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The title and body shown on iOS, instead of those of the notification.
+    pub fn alert(&mut self, title: impl Into<String>, body: impl Into<String>) -> &mut Self {
+        self.payload.aps.alert = Some(ApnsAlert {
+            title: Some(title.into()),
+            body: Some(body.into()),
+        });
+        self
+    }
+
+    /// The number shown on the app icon, 0 to remove it.
+    pub fn badge(&mut self, badge: u32) -> &mut Self {
+        self.payload.aps.badge = Some(badge);
+        self
+    }
+
+    /// Play a sound file from the app bundle, or `default`.
+    pub fn sound(&mut self, sound: impl Into<String>) -> &mut Self {
+        self.payload.aps.sound = Some(ApnsSound::Named(sound.into()));
+        self
+    }
+
+    /// Play the sound as a critical alert at `volume`, from 0.0 to 1.0.
+    pub fn critical_sound(&mut self, sound: impl Into<String>, volume: f32) -> &mut Self {
+        self.payload.aps.sound = Some(ApnsSound::Critical {
+            critical: 1,
+            name: sound.into(),
+            volume,
+        });
+        self
+    }
+
+    /// Wake the app in the background to handle the notification.
+    pub fn content_available(&mut self, content_available: bool) -> &mut Self {
+        self.payload.aps.content_available = Some(content_available as i32);
+        self
+    }
+}
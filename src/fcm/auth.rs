@@ -0,0 +1,173 @@
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use async_std::sync::Mutex;
+use isahc::http::Request;
+use isahc::prelude::{HttpClient, ResponseExt};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde_derive::{Deserialize, Serialize};
+
+const SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+// Google accepts assertions valid for at most an hour.
+const ASSERTION_LIFETIME_SECS: u64 = 3600;
+// Tokens are refreshed this long before they actually expire.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// The fields of a service-account JSON key that are needed to mint access tokens.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ServiceAccountKey {
+    pub project_id: String,
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    DEFAULT_TOKEN_URI.to_owned()
+}
+
+impl ServiceAccountKey {
+    /// Parse a service-account key from its JSON content.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| anyhow!("invalid service account key: {}", e))
+    }
+
+    /// Read a service-account key from a JSON file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read {}: {}", path.display(), e))?;
+
+        Self::from_json(&json)
+    }
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+/// Mints OAuth2 bearer tokens for a service account and caches them until they expire.
+pub struct ServiceAccount {
+    pub(crate) key: ServiceAccountKey,
+    token: Mutex<Option<CachedToken>>,
+}
+
+impl ServiceAccount {
+    pub fn new(key: ServiceAccountKey) -> Self {
+        ServiceAccount {
+            key,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Returns a cached access token, or mints a new one with `http` if it is missing or about
+    /// to expire. `http` is the client the messages are sent with, so both go through its proxy.
+    pub async fn access_token(&self, http: &HttpClient) -> Result<String> {
+        // Held across the request so concurrent sends wait for a single token instead of minting their own.
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if Instant::now() + EXPIRY_MARGIN < token.expires_at {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let requested_at = Instant::now();
+        let request = Request::post(&self.key.token_uri)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(form_encode(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &self.assertion()?),
+            ]))?;
+        let mut response = http
+            .send_async(request)
+            .await
+            .map_err(|e| anyhow!("failed to fetch access token: {}", e))?;
+        let body = response.text_async().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "failed to fetch access token: {} {}",
+                response.status(),
+                body.trim()
+            ));
+        }
+        let response: TokenResponse = serde_json::from_str(&body)
+            .map_err(|e| anyhow!("failed to fetch access token: {}", e))?;
+
+        let access_token = response.access_token.clone();
+        *cached = Some(CachedToken {
+            access_token: response.access_token,
+            expires_at: requested_at + Duration::from_secs(response.expires_in),
+        });
+
+        Ok(access_token)
+    }
+
+    // A JWT signed with the service account's key using RS256.
+    fn assertion(&self) -> Result<String> {
+        let iat = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let claims = Claims {
+            iss: &self.key.client_email,
+            scope: SCOPE,
+            aud: &self.key.token_uri,
+            iat,
+            exp: iat + ASSERTION_LIFETIME_SECS,
+        };
+
+        let header = encode(br#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = encode(&serde_json::to_vec(&claims)?);
+        let signing_input = format!("{}.{}", header, payload);
+
+        let pkey = PKey::private_key_from_pem(self.key.private_key.as_bytes())?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &pkey)?;
+        signer.update(signing_input.as_bytes())?;
+        let signature = signer.sign_to_vec()?;
+
+        Ok(format!("{}.{}", signing_input, encode(&signature)))
+    }
+}
+
+// `application/x-www-form-urlencoded`, escaping everything but the unreserved characters.
+fn form_encode(pairs: &[(&str, &str)]) -> String {
+    let escape = |value: &str| {
+        value
+            .bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (byte as char).to_string()
+                }
+                _ => format!("%{:02X}", byte),
+            })
+            .collect::<String>()
+    };
+
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}={}", escape(name), escape(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
@@ -1,11 +1,15 @@
+use std::collections::HashSet;
+
 use serde_with_macros::skip_serializing_none;
 
 use serde_derive::Serialize;
 use serde_json::{self, Value};
 
+use crate::fcm::android::AndroidConfig;
+use crate::fcm::apns::ApnsConfig;
 use crate::fcm::notification::Notification;
 
-#[derive(Serialize, PartialEq, Debug)]
+#[derive(Serialize, PartialEq, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum Priority {
     Normal,
@@ -13,53 +17,78 @@ pub enum Priority {
 }
 
 #[skip_serializing_none]
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct MessageBody {
-    collapse_key: Option<String>,
-    content_available: Option<bool>,
-    data: Option<Value>,
-    delay_while_idle: Option<bool>,
-    dry_run: Option<bool>,
-    notification: Option<Notification>,
-    priority: Option<Priority>,
-    registration_ids: Option<Vec<String>>,
-    restricted_package_name: Option<String>,
-    time_to_live: Option<i32>,
-    to: Option<String>,
+    // Only sent as is by the HTTP v1 API.
+    #[serde(skip)]
+    pub(crate) android: Option<AndroidConfig>,
+    pub(crate) apns: Option<ApnsConfig>,
+    pub(crate) collapse_key: Option<String>,
+    pub(crate) condition: Option<String>,
+    pub(crate) content_available: Option<bool>,
+    pub(crate) data: Option<Value>,
+    pub(crate) delay_while_idle: Option<bool>,
+    pub(crate) dry_run: Option<bool>,
+    pub(crate) mutable_content: Option<bool>,
+    pub(crate) notification: Option<Notification>,
+    pub(crate) priority: Option<Priority>,
+    pub(crate) registration_ids: Option<Vec<String>>,
+    pub(crate) restricted_package_name: Option<String>,
+    pub(crate) time_to_live: Option<i32>,
+    pub(crate) to: Option<String>,
+}
+
+/// Why `MessageBuilder::build` failed.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MessageError {
+    #[error("exactly one of to, registration_ids or condition must be set, but {0} were")]
+    InvalidTarget(usize),
 }
 
 /// Represents a FCM message. Construct the FCM message
 /// using various utility methods and finally send it.
 /// # Examples:
-/// ```rust
-/// use fcm::MessageBuilder;
+/// ```
+/// use takya_notifier::fcm::MessageBuilder;
 ///
 /// let mut builder = MessageBuilder::new("<FCM API Key>", "<registration id>");
 /// builder.dry_run(true);
-/// let message = builder.finalize();
+/// let message = builder.build().unwrap();
+/// assert_eq!(message.api_key, "<FCM API Key>");
 /// ```
-#[derive(Debug)]
+// Not `Serialize`, so the api key never ends up in a logged payload; serialize the body instead.
+#[derive(Debug, Clone)]
 pub struct Message {
     pub api_key: String,
     pub body: MessageBody,
 }
 
+impl Message {
+    /// The JSON body which `Client::send` posts to the legacy API, without the api key,
+    /// e.g. to log it or to compare it in a test.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&self.body)
+    }
+}
+
 ///
 /// A builder to get a `Message` instance.
 ///
 /// # Examples
 ///
-/// ```rust
-/// use fcm::MessageBuilder;
+/// ```
+/// use takya_notifier::fcm::MessageBuilder;
 ///
 /// let mut builder = MessageBuilder::new("<FCM API Key>", "<registration id>");
 /// builder.dry_run(true);
-/// let message = builder.finalize();
+/// let message = builder.build().unwrap();
 /// ```
 #[derive(Debug)]
 pub struct MessageBuilder {
     api_key: String,
+    android: Option<AndroidConfig>,
     collapse_key: Option<String>,
+    condition: Option<String>,
     content_available: Option<bool>,
     data: Option<Value>,
     delay_while_idle: Option<bool>,
@@ -73,13 +102,15 @@ pub struct MessageBuilder {
 }
 
 impl MessageBuilder {
-    /// Get a new instance of Message. You need to supply to.
-    pub fn new(api_key: impl Into<String>, to: impl Into<String>) -> Self {
+    // A builder with nothing set, to which each constructor adds its one target.
+    fn untargeted(api_key: String) -> Self {
         MessageBuilder {
-            api_key: api_key.into(),
-            to: Some(to.into()),
+            api_key,
+            android: None,
+            to: None,
             registration_ids: None,
             collapse_key: None,
+            condition: None,
             priority: None,
             content_available: None,
             delay_while_idle: None,
@@ -91,37 +122,59 @@ impl MessageBuilder {
         }
     }
 
+    /// Get a new instance of Message. You need to supply to.
+    pub fn new(api_key: impl Into<String>, to: impl Into<String>) -> Self {
+        MessageBuilder {
+            to: Some(to.into()),
+            ..Self::untargeted(api_key.into())
+        }
+    }
+
+    /// Get a new instance of Message sent to everyone subscribed to the topic.
+    /// The `/topics/` prefix is added if it is missing.
+    pub fn new_topic(api_key: impl Into<String>, topic: impl AsRef<str>) -> Self {
+        let topic = topic.as_ref();
+        if topic.starts_with("/topics/") {
+            Self::new(api_key, topic)
+        } else {
+            Self::new(api_key, format!("/topics/{}", topic))
+        }
+    }
+
+    /// Get a new instance of Message sent to the topics matching the condition,
+    /// like `'TopicA' in topics && 'TopicB' in topics`.
+    pub fn new_condition(api_key: impl Into<String>, condition: impl Into<String>) -> Self {
+        MessageBuilder {
+            condition: Some(condition.into()),
+            ..Self::untargeted(api_key.into())
+        }
+    }
+
     /// Get a new instance of Message. You need to supply registration ids.
+    /// Duplicated ids are dropped, keeping the first occurrence.
     pub fn new_multi<S>(api_key: S, ids: &[S]) -> Self
     where
         S: Into<String> + AsRef<str>,
     {
-        let converted = ids.iter().map(|a| a.as_ref().into()).collect();
-
         MessageBuilder {
-            api_key: api_key.into(),
-            to: None,
-            registration_ids: Some(converted),
-            collapse_key: None,
-            priority: None,
-            content_available: None,
-            delay_while_idle: None,
-            time_to_live: None,
-            restricted_package_name: None,
-            dry_run: None,
-            data: None,
-            notification: None,
+            registration_ids: Some(dedup_ids(ids)),
+            ..Self::untargeted(api_key.into())
         }
     }
 
-    /// String value to replace format specifiers in the body string.
+    /// Set the registration ids to send the message to. Duplicated ids are dropped,
+    /// keeping the first occurrence.
     pub fn registration_ids<S>(&mut self, ids: &[S]) -> &mut Self
     where
         S: Into<String> + AsRef<str>,
     {
-        let converted = ids.iter().map(|a| a.as_ref().into()).collect();
+        self.registration_ids = Some(dedup_ids(ids));
+        self
+    }
 
-        self.registration_ids = Some(converted);
+    /// Send to the topics matching the condition. The message must not have any other target.
+    pub fn condition(&mut self, condition: impl Into<String>) -> &mut Self {
+        self.condition = Some(condition.into());
         self
     }
 
@@ -133,12 +186,12 @@ impl MessageBuilder {
 
     /// Set the priority of the message. You can set Normal or High priorities.
     /// # Examples:
-    /// ```rust
-    /// use fcm::{MessageBuilder, Priority};
+    /// ```
+    /// use takya_notifier::fcm::{MessageBuilder, Priority};
     ///
     /// let mut builder = MessageBuilder::new("<FCM API Key>", "<registration id>");
     /// builder.priority(Priority::High);
-    /// let message = builder.finalize();
+    /// let message = builder.build().unwrap();
     /// ```
     pub fn priority(&mut self, priority: Priority) -> &mut Self {
         self.priority = Some(priority);
@@ -184,16 +237,16 @@ impl MessageBuilder {
     /// anything that Serde can serialize to JSON.
     ///
     /// # Examples:
-    /// ```rust
-    /// use fcm::MessageBuilder;
+    /// ```
+    /// use takya_notifier::fcm::MessageBuilder;
     /// use std::collections::HashMap;
     ///
     /// let mut map = HashMap::new();
     /// map.insert("message", "Howdy!");
     ///
     /// let mut builder = MessageBuilder::new("<FCM API Key>", "<registration id>");
-    /// builder.data(&map);
-    /// let message = builder.finalize();
+    /// builder.data(&map).unwrap();
+    /// let message = builder.build().unwrap();
     /// ```
     pub fn data(&mut self, data: &impl serde::Serialize) -> Result<&mut Self, serde_json::Error> {
         self.data = Some(serde_json::to_value(data)?);
@@ -202,40 +255,82 @@ impl MessageBuilder {
 
     /// Use this to set a `Notification` for the message.
     /// # Examples:
-    /// ```rust
-    /// use fcm::{MessageBuilder, NotificationBuilder};
+    /// ```
+    /// use takya_notifier::fcm::{MessageBuilder, NotificationBuilder};
     ///
     /// let mut builder = NotificationBuilder::new();
     /// builder.title("Hey!");
     /// builder.body("Do you want to catch up later?");
-    /// let notification = builder.finalize();
+    /// let notification = builder.build();
     ///
     /// let mut builder = MessageBuilder::new("<FCM API Key>", "<registration id>");
     /// builder.notification(notification);
-    /// let message = builder.finalize();
+    /// let message = builder.build().unwrap();
     /// ```
     pub fn notification(&mut self, notification: Notification) -> &mut Self {
         self.notification = Some(notification);
         self
     }
 
-    /// Complete the build and get a `Message` instance
-    pub fn build(self) -> Message {
-        Message {
+    /// Set the Android specific options of the message.
+    pub fn android(&mut self, android: AndroidConfig) -> &mut Self {
+        self.android = Some(android);
+        self
+    }
+
+    /// Complete the build and get a `Message` instance. Fails unless exactly one of
+    /// `to`, `registration_ids` or `condition` is set.
+    pub fn build(self) -> Result<Message, MessageError> {
+        let targets = [
+            self.to.is_some(),
+            self.registration_ids.is_some(),
+            self.condition.is_some(),
+        ]
+        .iter()
+        .filter(|&&set| set)
+        .count();
+        if targets != 1 {
+            return Err(MessageError::InvalidTarget(targets));
+        }
+
+        let android = self.android.clone().unwrap_or_default();
+
+        Ok(Message {
             api_key: self.api_key,
             body: MessageBody {
+                android: self.android,
                 to: self.to,
                 registration_ids: self.registration_ids,
-                collapse_key: self.collapse_key,
-                priority: self.priority,
+                collapse_key: self.collapse_key.or(android.collapse_key),
+                condition: self.condition,
+                priority: self.priority.or(android.priority),
                 content_available: self.content_available,
                 delay_while_idle: self.delay_while_idle,
-                time_to_live: self.time_to_live,
+                time_to_live: self.time_to_live.or(android.ttl),
                 restricted_package_name: self.restricted_package_name,
                 dry_run: self.dry_run,
                 data: self.data.clone(),
+                mutable_content: self.notification.as_ref().and_then(|n| n.mutable_content),
+                apns: self.notification.as_ref().and_then(|n| n.apns.clone()),
                 notification: self.notification,
             },
-        }
+        })
     }
+
+    /// The former name of `build`.
+    #[deprecated(note = "use `build`, which fails instead of sending a message without a target")]
+    pub fn finalize(self) -> Result<Message, MessageError> {
+        self.build()
+    }
+}
+
+// Drops duplicated ids while keeping the order of first appearance,
+// so a device never receives the same message twice.
+fn dedup_ids<S: AsRef<str>>(ids: &[S]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    ids.iter()
+        .map(AsRef::as_ref)
+        .filter(|id| seen.insert(*id))
+        .map(Into::into)
+        .collect()
 }
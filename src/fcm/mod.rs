@@ -1,18 +1,47 @@
 #![allow(dead_code)]
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use isahc::http::{Request, Uri};
+use isahc::prelude::{HttpClient, ResponseExt};
 
+mod android;
+pub use crate::fcm::android::*;
+mod apns;
+pub use crate::fcm::apns::*;
+mod auth;
+pub use crate::fcm::auth::*;
 mod message;
 pub use crate::fcm::message::*;
 mod notification;
 pub use crate::fcm::notification::*;
+mod response;
+pub use crate::fcm::response::*;
+mod v1;
+pub use crate::fcm::v1::*;
 
+use crate::http_client;
+
+const LEGACY_ENDPOINT: &str = "https://fcm.googleapis.com/fcm/send";
+
+// Builds a `Notification` from `attr = value;` pairs, panicking if it would be empty.
+// Starting with `try;` returns the `Result` of `NotificationBuilder::try_build` instead.
 #[macro_export]
 macro_rules! build_notification {
     (
+        try;
         $($attr_name:ident = $attr_value:expr;)*
     ) => {{
-        let mut noti = crate::fcm::NotificationBuilder::new();
+        let mut noti = $crate::fcm::NotificationBuilder::new();
+        $(
+            noti.$attr_name($attr_value);
+        )*
+
+        noti.try_build()
+    }};
+    (
+        $($attr_name:ident = $attr_value:expr;)*
+    ) => {{
+        let mut noti = $crate::fcm::NotificationBuilder::new();
         $(
             noti.$attr_name($attr_value);
         )*
@@ -22,30 +51,183 @@ macro_rules! build_notification {
 }
 
 pub struct Client {
-    api_key: String,
-    to: String,
+    auth: Auth,
+    // A single target is sent to with `to`, which also takes a topic, and several
+    // with `registration_ids`.
+    to: Vec<String>,
+    // `surf::post` sets up a new curl handle, and so a new TLS session, for every request.
+    // One client keeps its connections to FCM alive between the sends of a run instead.
+    http: HttpClient,
+}
+
+// Which API a `Client` talks to is decided by the constructor that built it.
+enum Auth {
+    // The legacy `fcm/send` endpoint with a server key.
+    ServerKey(String),
+    // The HTTP v1 API with OAuth2 tokens minted for a service account.
+    ServiceAccount(ServiceAccount),
 }
 
 impl Client {
-    pub fn new(api_key: impl Into<String>, to: impl Into<String>) -> Self {
-        Client {
-            api_key: api_key.into(),
-            to: to.into(),
+    /// A client for the legacy HTTP API, authenticated with a server key.
+    pub fn new(api_key: impl Into<String>, to: impl Into<String>) -> Result<Self> {
+        Self::new_multi(api_key, vec![to.into()])
+    }
+
+    /// A client for the legacy HTTP API which sends every notification to each of `ids`.
+    pub fn new_multi(api_key: impl Into<String>, ids: Vec<String>) -> Result<Self> {
+        Ok(Client {
+            auth: Auth::ServerKey(api_key.into()),
+            to: ids,
+            http: http_client::build(None)?,
+        })
+    }
+
+    /// A client for the HTTP v1 API, authenticated with a service-account key.
+    pub fn from_service_account(key: ServiceAccountKey, to: impl Into<String>) -> Result<Self> {
+        Self::from_service_account_multi(key, vec![to.into()])
+    }
+
+    /// A client for the HTTP v1 API which sends every notification to each of `ids`.
+    pub fn from_service_account_multi(key: ServiceAccountKey, ids: Vec<String>) -> Result<Self> {
+        Ok(Client {
+            auth: Auth::ServiceAccount(ServiceAccount::new(key)),
+            to: ids,
+            http: http_client::build(None)?,
+        })
+    }
+
+    /// Send every request through `proxy`, an `http://` or `https://` proxy.
+    pub fn with_proxy(mut self, proxy: &Uri) -> Result<Self> {
+        self.http = http_client::build(Some(proxy))?;
+        Ok(self)
+    }
+
+    /// Send the notification to the client's target with `priority`.
+    pub async fn send_notification(
+        &self,
+        notification: Notification,
+        priority: Priority,
+    ) -> Result<FcmResponse> {
+        let mut message_builder = self.message_builder();
+        message_builder
+            .notification(notification)
+            .priority(priority);
+
+        self.send(message_builder.build()?).await
+    }
+
+    /// Send a data-only message to the client's target, which lets the app sync in the
+    /// background instead of showing a banner.
+    pub async fn send_data(&self, data: &impl serde::Serialize) -> Result<FcmResponse> {
+        self.send(self.data_message(data)?).await
+    }
+
+    /// A data-only message carrying `data`, with `content_available` set so iOS wakes the app.
+    pub fn data_message(&self, data: &impl serde::Serialize) -> Result<Message> {
+        let mut message_builder = self.message_builder();
+        message_builder.data(data)?.content_available(true);
+
+        Ok(message_builder.build()?)
+    }
+
+    /// Send the notification to each of `registration_ids` instead of the client's target.
+    pub async fn send_notification_to(
+        &self,
+        notification: Notification,
+        priority: Priority,
+        registration_ids: &[String],
+    ) -> Result<FcmResponse> {
+        let mut message_builder = self.message_builder_to(registration_ids);
+        message_builder
+            .notification(notification)
+            .priority(priority);
+
+        self.send(message_builder.build()?).await
+    }
+
+    /// A builder of a message to the client's target, for the options `send_notification`
+    /// doesn't set. The api key is filled in for the legacy API.
+    pub fn message_builder(&self) -> MessageBuilder {
+        match self.to.as_slice() {
+            [to] => MessageBuilder::new(self.api_key(), to),
+            ids => MessageBuilder::new_multi(self.api_key(), ids),
         }
     }
 
-    pub async fn send_notification(&self, notification: Notification) -> Result<surf::Response> {
-        let mut message_builder = MessageBuilder::new(&self.api_key, &self.to);
-        message_builder.notification(notification);
+    /// A builder of a message to each of `registration_ids` instead of the client's target.
+    pub fn message_builder_to(&self, registration_ids: &[String]) -> MessageBuilder {
+        MessageBuilder::new_multi(self.api_key(), registration_ids)
+    }
 
-        self.send(message_builder.build()).await
+    fn api_key(&self) -> String {
+        match &self.auth {
+            Auth::ServerKey(api_key) => api_key.clone(),
+            Auth::ServiceAccount(_) => String::new(),
+        }
     }
 
-    pub async fn send(&self, message: Message) -> Result<surf::Response> {
-        surf::post("https://fcm.googleapis.com/fcm/send")
-            .set_header("Authorization", &format!("key={}", message.api_key))
-            .body_json(&message.body)?
-            .await
-            .map_err(|e| anyhow!(e))
+    /// Send the message and return the per-target results. With the v1 API the message's
+    /// `api_key` is ignored, and a message with several registration ids is sent as one
+    /// request per id, with the results collected in the same order.
+    pub async fn send(&self, message: Message) -> Result<FcmResponse> {
+        match &self.auth {
+            Auth::ServerKey(_) => {
+                let request = Request::post(LEGACY_ENDPOINT)
+                    .header("Authorization", format!("key={}", message.api_key))
+                    .header("Content-Type", "application/json")
+                    .body(message.to_json()?)?;
+                let mut response = self.http.send_async(request).await?;
+
+                if !response.status().is_success() {
+                    let body = response.text_async().await.unwrap_or_default();
+                    return Err(FcmError::from_status(response.status().as_u16(), body).into());
+                }
+
+                Ok(serde_json::from_str(&response.text_async().await?)?)
+            }
+            Auth::ServiceAccount(account) => {
+                let url = format!(
+                    "https://fcm.googleapis.com/v1/projects/{}/messages:send",
+                    account.key.project_id
+                );
+
+                let mut fcm_response = FcmResponse::default();
+                for request in message.body.to_v1() {
+                    let token = account.access_token(&self.http).await?;
+                    let request = Request::post(&url)
+                        .header("Authorization", format!("Bearer {}", token))
+                        .header("Content-Type", "application/json")
+                        .body(serde_json::to_vec(&request)?)?;
+                    let mut response = self.http.send_async(request).await?;
+
+                    let body = response.text_async().await?;
+                    let status = response.status().as_u16();
+                    // Bad credentials fail every request alike, so they aren't a result of the target.
+                    if status == 401 || status == 403 {
+                        return Err(FcmError::from_status(status, body).into());
+                    }
+                    let result = if response.status().is_success() {
+                        let sent: V1SendResponse = serde_json::from_str(&body)?;
+                        SendResult {
+                            message_id: Some(sent.name),
+                            ..SendResult::default()
+                        }
+                    } else {
+                        let reason = match ErrorReason::from_v1_body(&body) {
+                            Some(reason) => reason,
+                            None => return Err(FcmError::from_status(status, body).into()),
+                        };
+                        SendResult {
+                            error: Some(reason),
+                            ..SendResult::default()
+                        }
+                    };
+                    fcm_response.push(result);
+                }
+
+                Ok(fcm_response)
+            }
+        }
     }
 }
@@ -1,60 +1,184 @@
+use std::time::Duration;
+
 use serde_with_macros::skip_serializing_none;
 
 use serde_derive::Serialize;
 
+use crate::fcm::apns::ApnsConfig;
+
 /// This struct represents a FCM notification. Use the
 /// corresponding `NotificationBuilder` to get an instance. You can then use
 /// this notification instance when sending a FCM message.
 #[skip_serializing_none]
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct Notification {
-    badge: Option<String>,
-    body: Option<String>,
-    body_loc_args: Option<Vec<String>>,
-    body_loc_key: Option<String>,
-    click_action: Option<String>,
-    color: Option<String>,
-    icon: Option<String>,
-    sound: Option<String>,
-    tag: Option<String>,
-    title: Option<String>,
-    title_loc_args: Option<Vec<String>>,
-    title_loc_key: Option<String>,
+    pub(crate) android_channel_id: Option<String>,
+    // Sent at the top level of the message, where FCM expects it.
+    #[serde(skip)]
+    pub(crate) apns: Option<ApnsConfig>,
+    pub(crate) badge: Option<String>,
+    pub(crate) body: Option<String>,
+    pub(crate) body_loc_args: Option<Vec<String>>,
+    pub(crate) body_loc_key: Option<String>,
+    pub(crate) click_action: Option<String>,
+    pub(crate) color: Option<String>,
+    pub(crate) icon: Option<String>,
+    pub(crate) image: Option<String>,
+    // Sent at the top level of the message, where FCM expects it.
+    #[serde(skip)]
+    pub(crate) mutable_content: Option<bool>,
+    pub(crate) notification_priority: Option<NotificationPriority>,
+    pub(crate) sound: Option<String>,
+    pub(crate) tag: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) title_loc_args: Option<Vec<String>>,
+    pub(crate) title_loc_key: Option<String>,
+    // The Android only options below have no legacy counterpart, and are only sent by the
+    // HTTP v1 API, in the Android notification.
+    #[serde(skip)]
+    pub(crate) ticker: Option<String>,
+    #[serde(skip)]
+    pub(crate) vibrate_timings: Option<Vec<String>>,
+    #[serde(skip)]
+    pub(crate) light_settings: Option<LightSettings>,
+    #[serde(skip)]
+    pub(crate) default_sound: Option<bool>,
+    #[serde(skip)]
+    pub(crate) default_vibrate_timings: Option<bool>,
+    #[serde(skip)]
+    pub(crate) default_light_settings: Option<bool>,
+}
+
+/// How the notification LED of an Android device blinks, in a #rrggbb color.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LightSettings {
+    pub(crate) color: String,
+    pub(crate) on: Duration,
+    pub(crate) off: Duration,
 }
+
+impl LightSettings {
+    /// The LED is on for `on` and then off for `off`, over and over. A color which is not
+    /// in #rrggbb format fails `try_build` with `BuildError::InvalidColor`.
+    pub fn new(color: impl Into<String>, on: Duration, off: Duration) -> Self {
+        LightSettings {
+            color: color.into(),
+            on,
+            off,
+        }
+    }
+}
+
+// A duration as FCM takes it, in seconds with up to nine fractional digits, e.g. `0.5s`.
+pub(crate) fn duration_string(duration: Duration) -> String {
+    format!("{}s", duration.as_secs_f64())
+}
+
+/// How prominently Android shows the notification.
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub enum NotificationPriority {
+    #[serde(rename = "PRIORITY_MIN")]
+    Min,
+    #[serde(rename = "PRIORITY_LOW")]
+    Low,
+    #[serde(rename = "PRIORITY_DEFAULT")]
+    Default,
+    #[serde(rename = "PRIORITY_HIGH")]
+    High,
+    #[serde(rename = "PRIORITY_MAX")]
+    Max,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum BuildError {
+    #[error("a notification needs a title or a body, or their localization keys")]
+    Empty,
+    #[error("invalid color `{0}`, expected #rrggbb")]
+    InvalidColor(String),
+}
+
+// `^#[0-9a-fA-F]{6}$`, the only format FCM takes. Anything else is dropped without an error.
+fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
 pub struct NotificationBuilder {
+    apns: Option<ApnsConfig>,
     title: Option<String>,
     body: Option<String>,
     icon: Option<String>,
+    image: Option<String>,
+    mutable_content: Option<bool>,
+    notification_priority: Option<NotificationPriority>,
     sound: Option<String>,
     badge: Option<String>,
     tag: Option<String>,
     color: Option<String>,
     click_action: Option<String>,
+    android_channel_id: Option<String>,
     body_loc_key: Option<String>,
     body_loc_args: Option<Vec<String>>,
     title_loc_key: Option<String>,
     title_loc_args: Option<Vec<String>>,
+    ticker: Option<String>,
+    vibrate_timings: Option<Vec<String>>,
+    light_settings: Option<LightSettings>,
+    default_sound: Option<bool>,
+    default_vibrate_timings: Option<bool>,
+    default_light_settings: Option<bool>,
+}
+
+impl Notification {
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    /// The JSON of the notification as it is sent within a message, without the fields which are unset.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+impl Default for NotificationBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NotificationBuilder {
     pub fn new() -> NotificationBuilder {
         NotificationBuilder {
+            apns: None,
             title: None,
             body: None,
             icon: None,
+            image: None,
+            mutable_content: None,
+            notification_priority: None,
             sound: None,
             badge: None,
             tag: None,
             color: None,
             click_action: None,
+            android_channel_id: None,
             body_loc_key: None,
             body_loc_args: None,
             title_loc_key: None,
             title_loc_args: None,
+            ticker: None,
+            vibrate_timings: None,
+            light_settings: None,
+            default_sound: None,
+            default_vibrate_timings: None,
+            default_light_settings: None,
         }
     }
 
-    // Set the title of the notification
+    /// Set the title of the notification
     pub fn title(&mut self, title: impl Into<String>) -> &mut Self {
         self.title = Some(title.into());
         self
@@ -72,6 +196,25 @@ impl NotificationBuilder {
         self
     }
 
+    /// Set the URL of an image shown as a big picture on Android and iOS.
+    pub fn image(&mut self, image: impl Into<String>) -> &mut Self {
+        self.image = Some(image.into());
+        self
+    }
+
+    /// Let an iOS notification service extension modify the notification before it is shown,
+    /// which is needed to download and attach the image.
+    pub fn mutable_content(&mut self, mutable_content: bool) -> &mut Self {
+        self.mutable_content = Some(mutable_content);
+        self
+    }
+
+    /// Set how prominently Android shows the notification.
+    pub fn notification_priority(&mut self, priority: NotificationPriority) -> &mut Self {
+        self.notification_priority = Some(priority);
+        self
+    }
+
     /// Set the sound to be played
     pub fn sound(&mut self, sound: impl Into<String>) -> &mut Self {
         self.sound = Some(sound.into());
@@ -91,7 +234,8 @@ impl NotificationBuilder {
         self
     }
 
-    /// The color of the icon, in #rrggbb format
+    /// The color of the icon, in #rrggbb format such as `#ff0000`. Any other value fails
+    /// `try_build` with `BuildError::InvalidColor`.
     pub fn color(&mut self, color: impl Into<String>) -> &mut Self {
         self.color = Some(color.into());
         self
@@ -105,6 +249,56 @@ impl NotificationBuilder {
         self
     }
 
+    /// The notification channel on Android 8.0 and later. The channel must be created
+    /// by the app, otherwise the default channel is used.
+    pub fn android_channel_id(&mut self, android_channel_id: impl Into<String>) -> &mut Self {
+        self.android_channel_id = Some(android_channel_id.into());
+        self
+    }
+
+    /// The text read out by accessibility services on Android, such as TalkBack.
+    pub fn ticker(&mut self, ticker: impl Into<String>) -> &mut Self {
+        self.ticker = Some(ticker.into());
+        self
+    }
+
+    /// The vibration pattern on Android: how long to wait, vibrate, wait and so on, starting
+    /// with a wait. Overrides the default pattern of the channel.
+    pub fn vibrate_timings(&mut self, timings: &[Duration]) -> &mut Self {
+        self.vibrate_timings = Some(timings.iter().copied().map(duration_string).collect());
+        self
+    }
+
+    /// How the LED of an Android device blinks for the notification.
+    pub fn light_settings(&mut self, light_settings: LightSettings) -> &mut Self {
+        self.light_settings = Some(light_settings);
+        self
+    }
+
+    /// Play the default sound of Android, instead of `sound`.
+    pub fn default_sound(&mut self, default_sound: bool) -> &mut Self {
+        self.default_sound = Some(default_sound);
+        self
+    }
+
+    /// Vibrate in the default pattern of Android, instead of `vibrate_timings`.
+    pub fn default_vibrate_timings(&mut self, default_vibrate_timings: bool) -> &mut Self {
+        self.default_vibrate_timings = Some(default_vibrate_timings);
+        self
+    }
+
+    /// Blink the LED as Android does by default, instead of `light_settings`.
+    pub fn default_light_settings(&mut self, default_light_settings: bool) -> &mut Self {
+        self.default_light_settings = Some(default_light_settings);
+        self
+    }
+
+    /// Set the iOS specific options, for sounds and badges beyond the flat `sound` and `badge`.
+    pub fn apns(&mut self, apns: ApnsConfig) -> &mut Self {
+        self.apns = Some(apns);
+        self
+    }
+
     /// Set the body key string for localization
     pub fn body_loc_key(&mut self, body_loc_key: impl Into<String>) -> &mut Self {
         self.body_loc_key = Some(body_loc_key.into());
@@ -139,21 +333,70 @@ impl NotificationBuilder {
         self
     }
 
-    /// Complete the build and get a `Notification` instance
+    /// Complete the build and get a `Notification` instance.
+    ///
+    /// # Panics
+    ///
+    /// If neither a title nor a body was set, or the color is invalid, see `try_build`.
     pub fn build(self) -> Notification {
-        Notification {
+        match self.try_build() {
+            Ok(notification) => notification,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// The former name of `build`.
+    #[deprecated(note = "use `build`, or `try_build` to handle an empty notification")]
+    pub fn finalize(self) -> Notification {
+        self.build()
+    }
+
+    /// Complete the build, failing if neither a title nor a body, nor a localization key
+    /// of either, was set, since FCM rejects such a notification, or if the color is not
+    /// in #rrggbb format, since FCM ignores it. The same goes for the color of `light_settings`.
+    pub fn try_build(self) -> Result<Notification, BuildError> {
+        if self.title.is_none()
+            && self.body.is_none()
+            && self.title_loc_key.is_none()
+            && self.body_loc_key.is_none()
+        {
+            return Err(BuildError::Empty);
+        }
+        let light_color = self.light_settings.as_ref().map(|light| &light.color);
+        if let Some(color) = self
+            .color
+            .as_ref()
+            .into_iter()
+            .chain(light_color)
+            .find(|color| !is_hex_color(color))
+        {
+            return Err(BuildError::InvalidColor(color.clone()));
+        }
+
+        Ok(Notification {
+            apns: self.apns,
             title: self.title,
             body: self.body,
             icon: self.icon,
+            image: self.image,
+            mutable_content: self.mutable_content,
+            notification_priority: self.notification_priority,
             sound: self.sound,
             badge: self.badge,
             tag: self.tag,
             color: self.color,
             click_action: self.click_action,
+            android_channel_id: self.android_channel_id,
             body_loc_key: self.body_loc_key,
             body_loc_args: self.body_loc_args,
             title_loc_key: self.title_loc_key,
             title_loc_args: self.title_loc_args,
-        }
+            ticker: self.ticker,
+            vibrate_timings: self.vibrate_timings,
+            light_settings: self.light_settings,
+            default_sound: self.default_sound,
+            default_vibrate_timings: self.default_vibrate_timings,
+            default_light_settings: self.default_light_settings,
+        })
     }
 }
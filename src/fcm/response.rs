@@ -0,0 +1,201 @@
+use serde_derive::Deserialize;
+use strum_macros::EnumString;
+
+/// The body FCM answers a send with. `results` has one entry per target,
+/// in the same order as the registration ids of the message.
+#[derive(Deserialize, Debug, PartialEq, Default)]
+pub struct FcmResponse {
+    pub multicast_id: Option<i64>,
+    #[serde(default)]
+    pub success: u32,
+    #[serde(default)]
+    pub failure: u32,
+    #[serde(default)]
+    pub canonical_ids: u32,
+    #[serde(default)]
+    pub results: Vec<SendResult>,
+}
+
+/// A send which FCM refused as a whole, rather than for one of its targets.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum FcmError {
+    /// 401: the server key or the access token was not accepted.
+    #[error("FCM rejected the credentials, check the server key or service account: {0}")]
+    Unauthorized(String),
+    /// 403: the credentials are valid, but not allowed to send to this project.
+    #[error("FCM denied sending with these credentials: {0}")]
+    Forbidden(String),
+    /// 400: the message itself is malformed.
+    #[error("FCM rejected the message as malformed: {0}")]
+    BadRequest(String),
+    #[error("FCM returned {status}: {body}")]
+    Status { status: u16, body: String },
+}
+
+impl FcmError {
+    /// The error of a response with a status other than success, with its body.
+    pub fn from_status(status: u16, body: String) -> Self {
+        match status {
+            400 => FcmError::BadRequest(body),
+            401 => FcmError::Unauthorized(body),
+            403 => FcmError::Forbidden(body),
+            status => FcmError::Status { status, body },
+        }
+    }
+}
+
+/// The outcome of sending to a single target.
+#[derive(Deserialize, Debug, PartialEq, Default)]
+pub struct SendResult {
+    pub message_id: Option<String>,
+    /// The canonical token to use from now on, if FCM knows the device by another one.
+    pub registration_id: Option<String>,
+    pub error: Option<ErrorReason>,
+}
+
+/// The error strings FCM reports for a target.
+#[derive(Deserialize, EnumString, Debug, PartialEq, Clone)]
+#[serde(from = "String")]
+pub enum ErrorReason {
+    MissingRegistration,
+    InvalidRegistration,
+    NotRegistered,
+    InvalidPackageName,
+    MismatchSenderId,
+    InvalidParameters,
+    MessageTooBig,
+    InvalidDataKey,
+    InvalidTtl,
+    Unavailable,
+    InternalServerError,
+    DeviceMessageRateExceeded,
+    TopicsMessageRateExceeded,
+    InvalidApnsCredential,
+    #[strum(disabled)]
+    Other(String),
+}
+
+impl From<String> for ErrorReason {
+    fn from(s: String) -> Self {
+        s.parse().unwrap_or(ErrorReason::Other(s))
+    }
+}
+
+impl ErrorReason {
+    /// Whether the token will never work again and should not be used anymore.
+    pub fn is_dead_token(&self) -> bool {
+        matches!(
+            self,
+            ErrorReason::NotRegistered | ErrorReason::InvalidRegistration
+        )
+    }
+
+    // Maps an HTTP v1 error code onto the legacy error strings.
+    pub(crate) fn from_v1(code: &str) -> Self {
+        match code {
+            "UNREGISTERED" => ErrorReason::NotRegistered,
+            "SENDER_ID_MISMATCH" => ErrorReason::MismatchSenderId,
+            "QUOTA_EXCEEDED" => ErrorReason::DeviceMessageRateExceeded,
+            "UNAVAILABLE" => ErrorReason::Unavailable,
+            "INTERNAL" => ErrorReason::InternalServerError,
+            "THIRD_PARTY_AUTH_ERROR" => ErrorReason::InvalidApnsCredential,
+            other => ErrorReason::Other(other.to_owned()),
+        }
+    }
+
+    /// The reason of a failed HTTP v1 send from its body, or None if it isn't an FCM error.
+    pub fn from_v1_body(body: &str) -> Option<Self> {
+        let failed: V1ErrorResponse = serde_json::from_str(body).ok()?;
+        Some(failed.error.reason())
+    }
+}
+
+impl FcmResponse {
+    /// Whether the message reached some target and every failure was a dead token, so
+    /// there is nothing worth sending again. A topic send has no per-target results.
+    pub fn is_delivered(&self) -> bool {
+        self.results.is_empty()
+            || self.results.iter().any(|result| result.error.is_none())
+                && self
+                    .results
+                    .iter()
+                    .filter_map(|result| result.error.as_ref())
+                    .all(ErrorReason::is_dead_token)
+    }
+
+    pub(crate) fn push(&mut self, result: SendResult) {
+        if result.error.is_some() {
+            self.failure += 1;
+        } else {
+            self.success += 1;
+        }
+        if result.registration_id.is_some() {
+            self.canonical_ids += 1;
+        }
+        self.results.push(result);
+    }
+}
+
+// The body of a successful HTTP v1 send.
+#[derive(Deserialize)]
+pub(crate) struct V1SendResponse {
+    pub name: String,
+}
+
+// The body of a failed HTTP v1 send.
+#[derive(Deserialize)]
+pub(crate) struct V1ErrorResponse {
+    pub error: V1Error,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct V1Error {
+    pub status: Option<String>,
+    pub message: Option<String>,
+    #[serde(default)]
+    pub details: Vec<V1ErrorDetail>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct V1ErrorDetail {
+    pub error_code: Option<String>,
+    #[serde(default)]
+    pub field_violations: Vec<V1FieldViolation>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct V1FieldViolation {
+    pub field: Option<String>,
+}
+
+impl V1Error {
+    // The FCM specific code in the details is more precise than the generic status.
+    pub fn code(&self) -> &str {
+        self.details
+            .iter()
+            .find_map(|detail| detail.error_code.as_deref())
+            .or(self.status.as_deref())
+            .unwrap_or("UNKNOWN")
+    }
+
+    pub fn reason(&self) -> ErrorReason {
+        match self.code() {
+            // Also the code of a malformed message, which must not get every token deleted,
+            // so it only counts as a dead token when the error is about the token.
+            "INVALID_ARGUMENT" if self.is_about_token() => ErrorReason::InvalidRegistration,
+            code => ErrorReason::from_v1(code),
+        }
+    }
+
+    fn is_about_token(&self) -> bool {
+        self.details
+            .iter()
+            .flat_map(|detail| &detail.field_violations)
+            .any(|violation| violation.field.as_deref() == Some("message.token"))
+            || self
+                .message
+                .as_deref()
+                .is_some_and(|message| message.contains("registration token"))
+    }
+}
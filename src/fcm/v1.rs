@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+
+use serde_with_macros::skip_serializing_none;
+
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use crate::fcm::apns::ApnsConfig;
+use crate::fcm::message::{MessageBody, Priority};
+use crate::fcm::notification::{duration_string, LightSettings, NotificationPriority};
+
+/// The body of a request to the HTTP v1 `messages:send` endpoint.
+/// The v1 API takes a single target per request, so a legacy body with several
+/// registration ids becomes several of these.
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SendRequest<'a> {
+    validate_only: Option<bool>,
+    message: V1Message<'a>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq)]
+struct V1Message<'a> {
+    token: Option<&'a str>,
+    topic: Option<&'a str>,
+    condition: Option<&'a str>,
+    notification: Option<V1Notification<'a>>,
+    android: Option<V1AndroidConfig<'a>>,
+    apns: Option<ApnsConfig>,
+    data: Option<BTreeMap<String, String>>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq)]
+struct V1Notification<'a> {
+    title: Option<&'a str>,
+    body: Option<&'a str>,
+    image: Option<&'a str>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Default)]
+struct V1AndroidConfig<'a> {
+    collapse_key: Option<&'a str>,
+    priority: Option<&'static str>,
+    ttl: Option<String>,
+    restricted_package_name: Option<&'a str>,
+    notification: Option<V1AndroidNotification<'a>>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq)]
+struct V1AndroidNotification<'a> {
+    channel_id: Option<&'a str>,
+    notification_priority: Option<NotificationPriority>,
+    icon: Option<&'a str>,
+    color: Option<&'a str>,
+    sound: Option<&'a str>,
+    tag: Option<&'a str>,
+    click_action: Option<&'a str>,
+    body_loc_key: Option<&'a str>,
+    body_loc_args: Option<&'a [String]>,
+    title_loc_key: Option<&'a str>,
+    title_loc_args: Option<&'a [String]>,
+    ticker: Option<&'a str>,
+    vibrate_timings: Option<&'a [String]>,
+    light_settings: Option<V1LightSettings>,
+    default_sound: Option<bool>,
+    default_vibrate_timings: Option<bool>,
+    default_light_settings: Option<bool>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct V1LightSettings {
+    color: V1Color,
+    light_on_duration: String,
+    light_off_duration: String,
+}
+
+// Each channel from 0 to 1. Without an alpha the color is opaque.
+#[derive(Serialize, Debug, PartialEq)]
+struct V1Color {
+    red: f32,
+    green: f32,
+    blue: f32,
+}
+
+impl From<&LightSettings> for V1LightSettings {
+    // The color was checked to be #rrggbb when the notification was built.
+    fn from(light: &LightSettings) -> Self {
+        let channel = |i: usize| {
+            let value = u8::from_str_radix(&light.color[i..i + 2], 16).unwrap_or_default();
+            f32::from(value) / 255.0
+        };
+
+        V1LightSettings {
+            color: V1Color {
+                red: channel(1),
+                green: channel(3),
+                blue: channel(5),
+            },
+            light_on_duration: duration_string(light.on),
+            light_off_duration: duration_string(light.off),
+        }
+    }
+}
+
+enum Target<'a> {
+    Token(&'a str),
+    Topic(&'a str),
+    Condition(&'a str),
+}
+
+impl MessageBody {
+    /// Convert the legacy body into HTTP v1 requests, one per target.
+    /// `delay_while_idle` has no v1 counterpart and is dropped.
+    pub fn to_v1(&self) -> Vec<SendRequest<'_>> {
+        let targets = match (&self.to, &self.registration_ids, &self.condition) {
+            (Some(to), _, _) => match to.strip_prefix("/topics/") {
+                Some(topic) => vec![Target::Topic(topic)],
+                None => vec![Target::Token(to)],
+            },
+            (None, Some(ids), _) => ids.iter().map(|id| Target::Token(id)).collect(),
+            (None, None, Some(condition)) => vec![Target::Condition(condition)],
+            (None, None, None) => vec![],
+        };
+
+        targets
+            .into_iter()
+            .map(|target| SendRequest {
+                validate_only: self.dry_run,
+                message: self.v1_message(target),
+            })
+            .collect()
+    }
+
+    fn v1_message<'a>(&'a self, target: Target<'a>) -> V1Message<'a> {
+        let (token, topic, condition) = match target {
+            Target::Token(token) => (Some(token), None, None),
+            Target::Topic(topic) => (None, Some(topic), None),
+            Target::Condition(condition) => (None, None, Some(condition)),
+        };
+
+        let android_notification = self.notification.as_ref().map(|n| V1AndroidNotification {
+            channel_id: n.android_channel_id.as_deref(),
+            notification_priority: n.notification_priority,
+            icon: n.icon.as_deref(),
+            color: n.color.as_deref(),
+            sound: n.sound.as_deref(),
+            tag: n.tag.as_deref(),
+            click_action: n.click_action.as_deref(),
+            body_loc_key: n.body_loc_key.as_deref(),
+            body_loc_args: n.body_loc_args.as_deref(),
+            title_loc_key: n.title_loc_key.as_deref(),
+            title_loc_args: n.title_loc_args.as_deref(),
+            ticker: n.ticker.as_deref(),
+            vibrate_timings: n.vibrate_timings.as_deref(),
+            light_settings: n.light_settings.as_ref().map(V1LightSettings::from),
+            default_sound: n.default_sound,
+            default_vibrate_timings: n.default_vibrate_timings,
+            default_light_settings: n.default_light_settings,
+        });
+
+        // The options set with `apns` take precedence over the flat fields.
+        let mut apns = self.apns.clone().unwrap_or_default();
+        let aps = &mut apns.payload.aps;
+        aps.badge = aps.badge.or_else(|| {
+            self.notification
+                .as_ref()
+                .and_then(|n| n.badge.as_ref())
+                .and_then(|badge| badge.parse().ok())
+        });
+        aps.content_available = aps
+            .content_available
+            .or_else(|| self.content_available.filter(|&c| c).map(|_| 1));
+        aps.mutable_content = aps
+            .mutable_content
+            .or_else(|| self.mutable_content.filter(|&m| m).map(|_| 1));
+
+        // The Android block takes precedence over the top-level fields.
+        let overrides = self.android.as_ref();
+        let android = V1AndroidConfig {
+            collapse_key: overrides
+                .and_then(|a| a.collapse_key.as_deref())
+                .or(self.collapse_key.as_deref()),
+            priority: overrides
+                .and_then(|a| a.priority.as_ref())
+                .or(self.priority.as_ref())
+                .map(|priority| match priority {
+                    Priority::Normal => "normal",
+                    Priority::High => "high",
+                }),
+            ttl: overrides
+                .and_then(|a| a.ttl)
+                .or(self.time_to_live)
+                .map(|ttl| format!("{}s", ttl)),
+            restricted_package_name: self.restricted_package_name.as_deref(),
+            notification: android_notification,
+        };
+
+        V1Message {
+            token,
+            topic,
+            condition,
+            notification: self.notification.as_ref().map(|n| V1Notification {
+                title: n.title.as_deref(),
+                body: n.body.as_deref(),
+                image: n.image.as_deref(),
+            }),
+            android: Some(android).filter(|android| *android != V1AndroidConfig::default()),
+            apns: Some(apns).filter(|apns| *apns != ApnsConfig::default()),
+            data: self.data.as_ref().map(string_map),
+        }
+    }
+}
+
+// The v1 API only accepts string values in `data`, so anything else is sent as its JSON text.
+fn string_map(data: &Value) -> BTreeMap<String, String> {
+    match data {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                (key.clone(), value)
+            })
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
@@ -0,0 +1,88 @@
+use super::config::TrendDirection;
+use super::events::ItemEvent;
+
+// Decides which events are worth a notification. The database is updated regardless.
+pub struct NotifyFilter {
+    // `PRICE_DROP_THRESHOLD_PERCENT`, drops smaller than this are not notified.
+    pub price_drop_threshold_percent: f64,
+    // `NOTIFY_ON_INCREASE`
+    pub notify_on_increase: bool,
+    // `MIN_PRICE_DELTA_YEN`, price changes smaller than this many yen are minor.
+    // The digest only counts them, and `NOTIFY_MODE=per_event` notifies them anyway
+    // unless `min_price_delta_per_event` is set.
+    pub min_price_delta_yen: i64,
+    // `MIN_PRICE_DELTA_PER_EVENT`
+    pub min_price_delta_per_event: bool,
+    // `MIN_ITEM_PRICE_YEN`, items cheaper than this are not notified when they are added,
+    // relisted or change, e.g. to leave out stickers and graffiti.
+    pub min_item_price_yen: i64,
+    // `MIN_ITEM_PRICE_ON_REMOVAL`, to leave them out when they are sold or deleted as well
+    pub min_item_price_on_removal: bool,
+}
+
+impl Default for NotifyFilter {
+    fn default() -> Self {
+        NotifyFilter {
+            price_drop_threshold_percent: 0.0,
+            notify_on_increase: false,
+            min_price_delta_yen: 0,
+            min_price_delta_per_event: false,
+            min_item_price_yen: 0,
+            min_item_price_on_removal: false,
+        }
+    }
+}
+
+impl NotifyFilter {
+    // Whether the event is a price change smaller than `min_price_delta_yen`.
+    pub fn is_minor(&self, event: &ItemEvent) -> bool {
+        match event {
+            ItemEvent::PriceChanged {
+                old_price,
+                new_price,
+                ..
+            } => (new_price - old_price).abs() < self.min_price_delta_yen,
+            _ => false,
+        }
+    }
+
+    // Whether the item of the event is priced below `min_item_price_yen`, going by the price
+    // it has now, and the event is one that the floor applies to.
+    pub fn is_below_floor(&self, event: &ItemEvent) -> bool {
+        let applies = match event {
+            ItemEvent::Sold { .. } | ItemEvent::Deleted { .. } => self.min_item_price_on_removal,
+            _ => true,
+        };
+
+        applies && event.item().price < self.min_item_price_yen
+    }
+
+    pub fn should_notify(&self, event: &ItemEvent) -> bool {
+        if self.is_below_floor(event) {
+            return false;
+        }
+
+        match event {
+            ItemEvent::PriceChanged {
+                old_price,
+                new_price,
+                ..
+            } => {
+                if new_price >= old_price {
+                    return self.notify_on_increase;
+                }
+                if *old_price <= 0 {
+                    return true;
+                }
+
+                let drop = (old_price - new_price) as f64 / *old_price as f64 * 100.0;
+                drop >= self.price_drop_threshold_percent
+            }
+            ItemEvent::Trend {
+                direction: TrendDirection::Up,
+                ..
+            } => self.notify_on_increase,
+            _ => true,
+        }
+    }
+}
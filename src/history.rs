@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+
+use super::models::{NewPriceHistory, PriceHistory};
+use super::schema::price_history::dsl as table;
+
+// Window of the rolling average stored in `Item::avg_price_7d`.
+pub const AVERAGE_WINDOW_DAYS: i64 = 7;
+
+// The start of the window of the rolling average.
+pub fn average_since() -> NaiveDateTime {
+    Utc::now().naive_utc() - Duration::days(AVERAGE_WINDOW_DAYS)
+}
+
+pub fn record_prices(conn: &MysqlConnection, prices: &[(i32, i64)]) -> QueryResult<usize> {
+    let recorded_at = Utc::now().naive_utc();
+    let rows = prices
+        .iter()
+        .map(|&(order_id, price)| NewPriceHistory {
+            order_id,
+            price,
+            recorded_at,
+        })
+        .collect::<Vec<_>>();
+
+    diesel::insert_into(table::price_history)
+        .values(rows)
+        .execute(conn)
+}
+
+// Every recorded price of the item, oldest first.
+pub fn price_history_for(conn: &MysqlConnection, order_id: i32) -> QueryResult<Vec<PriceHistory>> {
+    table::price_history
+        .filter(table::order_id.eq(order_id))
+        .order((table::recorded_at.asc(), table::id.asc()))
+        .load(conn)
+}
+
+// The prices of the given items recorded at or after `since`, and at least the last `keep_last`
+// of each for the trend, oldest first, keyed by order id.
+pub fn load_for_items(
+    conn: &MysqlConnection,
+    order_ids: &[i32],
+    since: NaiveDateTime,
+    keep_last: usize,
+) -> QueryResult<HashMap<i32, Vec<PriceHistory>>> {
+    let rows = table::price_history
+        .filter(table::order_id.eq_any(order_ids))
+        .filter(table::recorded_at.ge(since))
+        .order((table::recorded_at.asc(), table::id.asc()))
+        .load::<PriceHistory>(conn)?;
+
+    let mut history = HashMap::new();
+    for row in rows {
+        history
+            .entry(row.order_id)
+            .or_insert_with(Vec::new)
+            .push(row);
+    }
+
+    for &order_id in order_ids {
+        let recent = history.entry(order_id).or_insert_with(Vec::new);
+        if recent.len() >= keep_last {
+            continue;
+        }
+        let mut older = table::price_history
+            .filter(table::order_id.eq(order_id))
+            .filter(table::recorded_at.lt(since))
+            .order((table::recorded_at.desc(), table::id.desc()))
+            .limit((keep_last - recent.len()) as i64)
+            .load::<PriceHistory>(conn)?;
+        older.reverse();
+        recent.splice(0..0, older);
+    }
+
+    Ok(history)
+}
+
+// Deletes the prices of the given items recorded before `before`, except their last `keep_last`,
+// which is what `load_for_items` would load of them.
+pub fn prune(
+    conn: &MysqlConnection,
+    order_ids: &[i32],
+    before: NaiveDateTime,
+    keep_last: usize,
+) -> QueryResult<usize> {
+    let mut pruned = 0;
+    for &order_id in order_ids {
+        let cutoff = match keep_last {
+            0 => before,
+            keep_last => {
+                // The oldest of the last `keep_last` prices, or None if there are no more than them.
+                let kept_from = table::price_history
+                    .filter(table::order_id.eq(order_id))
+                    .order((table::recorded_at.desc(), table::id.desc()))
+                    .select(table::recorded_at)
+                    .offset(keep_last as i64 - 1)
+                    .first::<NaiveDateTime>(conn)
+                    .optional()?;
+                match kept_from {
+                    Some(kept_from) => kept_from.min(before),
+                    None => continue,
+                }
+            }
+        };
+        pruned += diesel::delete(
+            table::price_history
+                .filter(table::order_id.eq(order_id))
+                .filter(table::recorded_at.lt(cutoff)),
+        )
+        .execute(conn)?;
+    }
+
+    Ok(pruned)
+}
+
+// Prices recorded at or after `since`.
+pub fn prices_since(history: &[PriceHistory], since: NaiveDateTime) -> Vec<i64> {
+    history
+        .iter()
+        .filter(|h| h.recorded_at >= since)
+        .map(|h| h.price)
+        .collect()
+}
+
+// Returns None for an empty slice.
+pub fn average(prices: &[i64]) -> Option<i64> {
+    if prices.is_empty() {
+        return None;
+    }
+
+    let sum: i128 = prices.iter().map(|&p| i128::from(p)).sum();
+    Some((sum / prices.len() as i128) as i64)
+}
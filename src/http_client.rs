@@ -0,0 +1,34 @@
+use isahc::http::Uri;
+use isahc::HttpClient;
+
+// The proxies of `HTTP_PROXY` and `HTTPS_PROXY`, which every outbound request goes through when set.
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConfig {
+    // `HTTP_PROXY` or `http_proxy`, for `http://` URLs
+    pub http: Option<Uri>,
+    // `HTTPS_PROXY` or `https_proxy`, for `https://` URLs
+    pub https: Option<Uri>,
+}
+
+impl ProxyConfig {
+    // The proxy to reach `url` through, picked by its scheme.
+    pub fn for_url(&self, url: &str) -> Option<&Uri> {
+        if url.starts_with("https://") {
+            self.https.as_ref()
+        } else {
+            self.http.as_ref()
+        }
+    }
+}
+
+// The one place the HTTP clients of the scraper and of FCM are built, so both go through the
+// same proxy. These are isahc clients, the curl-based backend surf 1.x uses by default, since
+// surf 1.x itself has no way to configure one.
+pub fn build(proxy: Option<&Uri>) -> Result<HttpClient, isahc::Error> {
+    let builder = HttpClient::builder();
+
+    match proxy {
+        Some(proxy) => builder.proxy(proxy.clone()).build(),
+        None => builder.build(),
+    }
+}
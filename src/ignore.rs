@@ -0,0 +1,85 @@
+use regex::Regex;
+
+use super::models::Item;
+
+// Items which are never notified, from `IGNORE_PATTERNS`. They are still written to the
+// database like any other. Each entry is one of
+// - `#1234`, the item of that order id,
+// - `/pattern/`, a regex, and
+// - anything else, a substring, ignoring the case,
+// where a pattern or a substring is matched against the full name with the kind, the exterior
+// and the StatTrak™ or Souvenir prefix, the same as in the notifications.
+#[derive(Default, Debug)]
+pub struct IgnoreList {
+    order_ids: Vec<i32>,
+    substrings: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl IgnoreList {
+    // Parses the comma-separated entries of `IGNORE_PATTERNS`. A comma in an entry, e.g. in the
+    // regex `/x{1\,3}/`, is written `\,`.
+    pub fn from_list(list: &str) -> Result<Self, String> {
+        let mut entries = vec![];
+        let mut entry = String::new();
+        let mut chars = list.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&',') => entry.push(chars.next().unwrap()),
+                ',' => entries.push(std::mem::take(&mut entry)),
+                c => entry.push(c),
+            }
+        }
+        entries.push(entry);
+
+        Self::parse(
+            &entries
+                .iter()
+                .map(|entry| entry.trim())
+                .filter(|entry| !entry.is_empty())
+                .map(str::to_owned)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    // Fails with the entry which is not a valid regex.
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let mut list = IgnoreList::default();
+        for entry in entries {
+            if let Some(order_id) = entry.strip_prefix('#').and_then(|id| id.parse().ok()) {
+                list.order_ids.push(order_id);
+            } else if let Some(pattern) = entry
+                .strip_prefix('/')
+                .and_then(|rest| rest.strip_suffix('/'))
+            {
+                list.patterns
+                    .push(Regex::new(pattern).map_err(|_| entry.clone())?);
+            } else {
+                list.substrings.push(entry.to_lowercase());
+            }
+        }
+
+        Ok(list)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order_ids.is_empty() && self.substrings.is_empty() && self.patterns.is_empty()
+    }
+
+    pub fn matches_order_id(&self, order_id: i32) -> bool {
+        self.order_ids.contains(&order_id)
+    }
+
+    pub fn matches(&self, item: &Item) -> bool {
+        if self.matches_order_id(item.order_id) {
+            return true;
+        }
+
+        let name = item.full_name();
+        let lowercase = name.to_lowercase();
+        self.substrings
+            .iter()
+            .any(|substring| lowercase.contains(substring.as_str()))
+            || self.patterns.iter().any(|pattern| pattern.is_match(&name))
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+
+use chrono::Utc;
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+use scraper::ElementRef;
+
+use super::models::ScrapeState;
+use super::schema::scrape_state::dsl as table;
+
+const STATE_ID: i32 = 1;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// A hash of the markup of the section, without its text: the distinct paths of tag names and
+// classes from the section down to every element. Each item repeats the same paths, so the hash
+// stays the same however many items are listed, and changes with the markup around them.
+pub fn fingerprint(section: ElementRef) -> String {
+    let mut paths = BTreeSet::new();
+    collect_paths(section, "", &mut paths);
+
+    hash(paths.into_iter())
+}
+
+// One hash of the pages of a listing split across several, in the order of `TARGET_URL`.
+pub fn combine(hashes: &[String]) -> String {
+    match hashes {
+        [hash] => hash.clone(),
+        hashes => hash(hashes.iter().cloned()),
+    }
+}
+
+fn collect_paths(element: ElementRef, parent: &str, paths: &mut BTreeSet<String>) {
+    let mut classes = element.value().classes().collect::<Vec<_>>();
+    classes.sort_unstable();
+    let mut path = format!("{}/{}", parent, element.value().name());
+    for class in classes {
+        path.push('.');
+        path.push_str(class);
+    }
+
+    for child in element.children().filter_map(ElementRef::wrap) {
+        collect_paths(child, &path, paths);
+    }
+    paths.insert(path);
+}
+
+// FNV-1a, which unlike the hasher of std is the same on every build, since the hash is stored.
+fn hash(lines: impl Iterator<Item = String>) -> String {
+    let mut hash = FNV_OFFSET;
+    for line in lines {
+        for byte in line.bytes().chain(std::iter::once(b'\n')) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    format!("{:016x}", hash)
+}
+
+pub fn load(conn: &MysqlConnection) -> QueryResult<Option<ScrapeState>> {
+    table::scrape_state.find(STATE_ID).first(conn).optional()
+}
+
+pub fn save(conn: &MysqlConnection, layout_hash: &str, item_count: usize) -> QueryResult<usize> {
+    diesel::replace_into(table::scrape_state)
+        .values(ScrapeState {
+            id: STATE_ID,
+            layout_hash: layout_hash.to_owned(),
+            item_count: i32::try_from(item_count).unwrap_or(i32::MAX),
+            updated_at: Utc::now().naive_utc(),
+        })
+        .execute(conn)
+}
+
+// Whether the site was most likely redesigned: the layout is not the one of the last run, and
+// fewer than `min_found_ratio` of the items of the last run were found in it. Either alone is
+// normal, since the markup changes a little now and then, and items sell out in bulk.
+pub fn looks_redesigned(
+    previous: Option<&ScrapeState>,
+    layout_hash: &str,
+    item_count: usize,
+    min_found_ratio: f64,
+) -> bool {
+    previous.is_some_and(|previous| {
+        previous.layout_hash != layout_hash
+            && (item_count as f64) < f64::from(previous.item_count) * min_found_ratio
+    })
+}
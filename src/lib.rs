@@ -0,0 +1,78 @@
+#[cfg(feature = "app")]
+#[macro_use]
+extern crate diesel;
+
+// Puts the items behind the `app` feature, which is everything but the standalone `fcm` client.
+macro_rules! cfg_app {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "app")]
+            $item
+        )*
+    };
+}
+
+pub mod fcm;
+pub mod http_client;
+
+cfg_app! {
+    use std::time::Duration;
+
+    pub mod alerts;
+    pub mod catalog;
+    pub mod config;
+    pub mod db;
+    pub mod diff;
+    pub mod digest;
+    pub mod events;
+    pub mod filter;
+    pub mod history;
+    pub mod ignore;
+    pub mod layout;
+    pub mod messages;
+    #[cfg(feature = "metrics")]
+    pub mod metrics;
+    pub mod models;
+    pub mod notifier;
+    pub mod parsers;
+    pub mod pending;
+    mod run;
+    pub mod runs;
+    pub mod schema;
+    pub mod scrape;
+    pub mod smtp;
+    pub mod summary;
+    pub mod tokens;
+    pub mod watch;
+
+    pub use self::db::{apply, load_db_items};
+    pub use self::diff::diff;
+    pub use self::run::{deliver, plan_notifications, run, Sent};
+    pub use self::scrape::fetch_and_parse;
+
+    #[derive(thiserror::Error, Debug)]
+    pub enum Error {
+        #[error("failed to fetch site correctly: {0}")]
+        FetchFailed(String),
+        #[error("site returned {0}")]
+        BadStatus(u16),
+        #[error("failed to resolve the host of {0}")]
+        DnsFailed(String),
+        #[error("timed out fetching {url} after {timeout:?}")]
+        FetchTimedOut { url: String, timeout: Duration },
+        #[error("failed to parse and select the html")]
+        ParseFailed,
+        #[error("no section of the page matched the selector `{0}`, the site layout may have changed")]
+        SelectorNoMatch(String),
+        #[error("{corrupted} item sections were corrupted against {parsed} parsed, the site layout may have changed")]
+        TooManyCorruptedSections { corrupted: usize, parsed: usize },
+        #[error("site returned a bot challenge page instead of the listing (found `{0}`)")]
+        ChallengeDetected(String),
+        #[error("failed to read response body after {read} bytes: {source}")]
+        BodyReadFailed {
+            read: usize,
+            #[source]
+            source: std::io::Error,
+        },
+    }
+}
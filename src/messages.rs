@@ -0,0 +1,329 @@
+use std::time::Duration;
+
+use crate::build_notification;
+
+use super::catalog::{self, Messages};
+use super::config::{Lang, TrendDirection};
+use super::digest::DigestBuilder;
+use super::events::ItemEvent;
+use super::fcm::Notification;
+use super::models::Item;
+use super::notifier::NotificationEvent;
+use super::runs;
+
+// Builds the notification of every event. The texts are looked up in the catalog of `lang`,
+// or with `use_loc_keys`, only the localization keys and their arguments are sent, and the
+// client app renders the message in the user's language.
+
+pub fn notification(event: &NotificationEvent, lang: Lang, use_loc_keys: bool) -> Notification {
+    match event {
+        NotificationEvent::Item(event) => item_event(event, lang, use_loc_keys),
+        NotificationEvent::Digest(builder) => digest(builder, lang, use_loc_keys),
+        NotificationEvent::UnknownSold { order_id, price } => {
+            unknown_sold(*order_id, *price, lang, use_loc_keys)
+        }
+        NotificationEvent::SlowRun { elapsed, baseline } => {
+            slow_run(*elapsed, *baseline, lang, use_loc_keys)
+        }
+        NotificationEvent::ScrapeFailed { error } => scrape_failed(error, lang, use_loc_keys),
+        NotificationEvent::LayoutChanged { found, previous } => {
+            layout_changed(*found, *previous, lang, use_loc_keys)
+        }
+        NotificationEvent::Listing { item } => listing(item, lang, use_loc_keys),
+        NotificationEvent::TargetReached { item, target_price } => {
+            target_reached(item, *target_price, lang, use_loc_keys)
+        }
+    }
+}
+
+pub fn item_event(event: &ItemEvent, lang: Lang, use_loc_keys: bool) -> Notification {
+    let messages = Messages::for_lang(lang);
+    let mut notification = match event {
+        ItemEvent::Added { item } if use_loc_keys => build_notification! {
+            title_loc_key = "item_added";
+            title_loc_args = &[item.full_name()];
+        },
+        ItemEvent::Added { item } => build_notification! {
+            title = messages.added(item);
+        },
+        ItemEvent::PriceChanged {
+            item,
+            old_price,
+            new_price,
+            ..
+        } if use_loc_keys => match item.avg_price_7d {
+            Some(avg) => build_notification! {
+                title_loc_key = "price_changed";
+                title_loc_args = &[item.full_name()];
+                body_loc_key = "price_changed_body_with_average";
+                body_loc_args = &[
+                    old_price.to_string(),
+                    new_price.to_string(),
+                    avg.to_string(),
+                    format!("{:+}", new_price - old_price),
+                    catalog::format_percent_change(*old_price, *new_price),
+                ];
+            },
+            None => build_notification! {
+                title_loc_key = "price_changed";
+                title_loc_args = &[item.full_name()];
+                body_loc_key = "price_changed_body";
+                body_loc_args = &[
+                    old_price.to_string(),
+                    new_price.to_string(),
+                    format!("{:+}", new_price - old_price),
+                    catalog::format_percent_change(*old_price, *new_price),
+                ];
+            },
+        },
+        ItemEvent::PriceChanged {
+            item,
+            old_price,
+            new_price,
+            ..
+        } => {
+            let (title, body) = messages.price_changed(item, *old_price, *new_price);
+            build_notification! {
+                title = title;
+                body = body;
+            }
+        }
+        ItemEvent::Sold { item } if use_loc_keys => build_notification! {
+            title_loc_key = "item_sold";
+            title_loc_args = &[item.full_name()];
+        },
+        ItemEvent::Sold { item } => build_notification! {
+            title = messages.sold(item);
+        },
+        ItemEvent::Deleted { item } if use_loc_keys => build_notification! {
+            title_loc_key = "item_deleted";
+            title_loc_args = &[item.full_name()];
+        },
+        ItemEvent::Deleted { item } => build_notification! {
+            title = messages.deleted(item);
+        },
+        ItemEvent::Relisted { item } if use_loc_keys => build_notification! {
+            title_loc_key = "item_relisted";
+            title_loc_args = &[item.full_name()];
+            body_loc_key = "item_relisted_body";
+            body_loc_args = &[item.price.to_string()];
+        },
+        ItemEvent::Relisted { item } => {
+            let (title, body) = messages.relisted(item);
+            build_notification! {
+                title = title;
+                body = body;
+            }
+        }
+        ItemEvent::Trend {
+            item,
+            direction,
+            prices,
+        } if use_loc_keys => build_notification! {
+            title_loc_key = match direction {
+                TrendDirection::Up => "price_trend_up",
+                _ => "price_trend_down",
+            };
+            title_loc_args = &[item.full_name(), (prices.len() - 1).to_string()];
+            body_loc_key = "price_trend_body";
+            body_loc_args = &[catalog::join_prices(prices)];
+        },
+        ItemEvent::Trend {
+            item,
+            direction,
+            prices,
+        } => {
+            let (title, body) = messages.trend(item, *direction, prices);
+            build_notification! {
+                title = title;
+                body = body;
+            }
+        }
+        ItemEvent::DetailsChanged { item, previous } if use_loc_keys => build_notification! {
+            title_loc_key = "item_details_changed";
+            body_loc_key = "item_details_changed_body";
+            body_loc_args = &[
+                item.order_id.to_string(),
+                previous.full_name(),
+                item.full_name(),
+            ];
+        },
+        ItemEvent::DetailsChanged { item, previous } => {
+            let (title, body) = messages.details_changed(item, previous);
+            build_notification! {
+                title = title;
+                body = body;
+            }
+        }
+    };
+
+    // The picture is only attached to price changes, where it is worth the extra data.
+    if let ItemEvent::PriceChanged {
+        image_url: Some(url),
+        ..
+    } = event
+    {
+        notification.image = Some(url.clone());
+        // iOS only downloads the picture in a notification service extension.
+        notification.mutable_content = Some(true);
+    }
+
+    notification
+}
+
+pub fn digest(digest: &DigestBuilder, lang: Lang, use_loc_keys: bool) -> Notification {
+    if use_loc_keys {
+        return build_notification! {
+            title_loc_key = if digest.is_listing() {
+                "listing_digest"
+            } else {
+                "digest"
+            };
+            title_loc_args = &[digest.total().to_string()];
+            body_loc_key = "digest_body";
+            body_loc_args = &[
+                digest.added.to_string(),
+                digest.price_drops.to_string(),
+                digest.price_rises.to_string(),
+                digest.sold.to_string(),
+                digest.deleted.to_string(),
+                digest.relisted.to_string(),
+                digest.listed.to_string(),
+                digest.minor_price_changes.to_string(),
+                digest.details_changed.to_string(),
+            ];
+        };
+    }
+
+    let (title, body) = Messages::for_lang(lang).digest(digest);
+    build_notification! {
+        title = title;
+        body = body;
+    }
+}
+
+pub fn listing(item: &Item, lang: Lang, use_loc_keys: bool) -> Notification {
+    if use_loc_keys {
+        build_notification! {
+            title_loc_key = "item_listing";
+            title_loc_args = &[item.full_name()];
+            body_loc_key = "item_listing_body";
+            body_loc_args = &[item.price.to_string()];
+        }
+    } else {
+        let (title, body) = Messages::for_lang(lang).listing(item);
+        build_notification! {
+            title = title;
+            body = body;
+        }
+    }
+}
+
+pub fn unknown_sold(order_id: i32, price: i64, lang: Lang, use_loc_keys: bool) -> Notification {
+    if use_loc_keys {
+        build_notification! {
+            title_loc_key = "unknown_sold";
+            title_loc_args = &[order_id.to_string()];
+            body_loc_key = "unknown_sold_body";
+            body_loc_args = &[price.to_string()];
+        }
+    } else {
+        let (title, body) = Messages::for_lang(lang).unknown_sold(order_id, price);
+        build_notification! {
+            title = title;
+            body = body;
+        }
+    }
+}
+
+pub fn slow_run(
+    elapsed: Duration,
+    baseline: Option<Duration>,
+    lang: Lang,
+    use_loc_keys: bool,
+) -> Notification {
+    if !use_loc_keys {
+        let (title, body) = Messages::for_lang(lang).slow_run(elapsed, baseline);
+        return build_notification! {
+            title = title;
+            body = body;
+        };
+    }
+
+    let elapsed = catalog::format_secs(elapsed);
+    match baseline {
+        Some(baseline) => build_notification! {
+            title_loc_key = "slow_run";
+            body_loc_key = "slow_run_body_with_baseline";
+            body_loc_args = &[
+                elapsed,
+                runs::BASELINE_RUNS.to_string(),
+                catalog::format_secs(baseline),
+            ];
+        },
+        None => build_notification! {
+            title_loc_key = "slow_run";
+            body_loc_key = "slow_run_body";
+            body_loc_args = &[elapsed];
+        },
+    }
+}
+
+pub fn scrape_failed(error: &str, lang: Lang, use_loc_keys: bool) -> Notification {
+    if use_loc_keys {
+        build_notification! {
+            title_loc_key = "scrape_failed";
+            body_loc_key = "scrape_failed_body";
+            body_loc_args = &[error];
+        }
+    } else {
+        let (title, body) = Messages::for_lang(lang).scrape_failed(error);
+        build_notification! {
+            title = title;
+            body = body;
+        }
+    }
+}
+
+pub fn layout_changed(
+    found: usize,
+    previous: usize,
+    lang: Lang,
+    use_loc_keys: bool,
+) -> Notification {
+    if use_loc_keys {
+        build_notification! {
+            title_loc_key = "layout_changed";
+            body_loc_key = "layout_changed_body";
+            body_loc_args = &[found.to_string(), previous.to_string()];
+        }
+    } else {
+        let (title, body) = Messages::for_lang(lang).layout_changed(found, previous);
+        build_notification! {
+            title = title;
+            body = body;
+        }
+    }
+}
+
+pub fn target_reached(
+    item: &Item,
+    target_price: i64,
+    lang: Lang,
+    use_loc_keys: bool,
+) -> Notification {
+    if use_loc_keys {
+        build_notification! {
+            title_loc_key = "target_reached";
+            title_loc_args = &[item.full_name()];
+            body_loc_key = "target_reached_body";
+            body_loc_args = &[item.price.to_string(), target_price.to_string()];
+        }
+    } else {
+        let (title, body) = Messages::for_lang(lang).target_reached(item, target_price);
+        build_notification! {
+            title = title;
+            body = body;
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use prometheus::{
+    register_int_counter, register_int_counter_vec, Encoder, IntCounter, IntCounterVec, TextEncoder,
+};
+
+const JOB: &str = "takya_notifier";
+
+lazy_static! {
+    pub static ref ITEMS_PARSED: IntCounter = register_int_counter!(
+        "takya_notifier_items_parsed_total",
+        "Item sections parsed from the site"
+    )
+    .unwrap();
+    pub static ref CORRUPTED_SECTIONS: IntCounter = register_int_counter!(
+        "takya_notifier_corrupted_sections_total",
+        "Item sections skipped because they could not be parsed"
+    )
+    .unwrap();
+    pub static ref NOTIFICATIONS_SENT: IntCounterVec = register_int_counter_vec!(
+        "takya_notifier_notifications_sent_total",
+        "Notifications delivered to a sink, by kind",
+        &["kind"]
+    )
+    .unwrap();
+    pub static ref FAILURES: IntCounterVec = register_int_counter_vec!(
+        "takya_notifier_failures_total",
+        "Runs which failed to fetch or parse the site, by stage",
+        &["stage"]
+    )
+    .unwrap();
+}
+
+// The process is short-lived, so the metrics are pushed to a Pushgateway instead of being scraped.
+pub async fn push(pushgateway_url: &str) -> anyhow::Result<()> {
+    let mut body = vec![];
+    let encoder = TextEncoder::new();
+    encoder.encode(&prometheus::gather(), &mut body)?;
+
+    let url = format!(
+        "{}/metrics/job/{}",
+        pushgateway_url.trim_end_matches('/'),
+        JOB
+    );
+    let response = surf::put(url)
+        .set_header("Content-Type", encoder.format_type())
+        .body_bytes(body)
+        .await
+        .map_err(|e| anyhow!(e))?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!("Pushgateway returned {}", response.status()));
+    }
+
+    Ok(())
+}
@@ -1,11 +1,17 @@
+use chrono::NaiveDateTime;
 use diesel::{Identifiable, Insertable, Queryable};
 use diesel_derive_enum::DbEnum;
+use serde_derive::{Deserialize, Serialize};
 use strum_macros::{AsRefStr, Display, EnumString};
 
-use super::schema::item;
+use super::schema::{
+    item, pending_notifications, price_history, run_history, scrape_state, tokens, watch,
+};
 
 // Kind and exterior will be None if it is vanilla.
-#[derive(Queryable, Insertable, Identifiable, AsChangeset)]
+#[derive(
+    Queryable, Insertable, Identifiable, AsChangeset, Serialize, Deserialize, PartialEq, Clone, Debug,
+)]
 #[changeset_options(treat_none_as_null = "true")]
 #[table_name = "item"]
 #[primary_key(order_id)]
@@ -14,18 +20,86 @@ pub struct Item {
     pub name: String,
     pub kind: Option<String>,
     pub exterior: Option<Exterior>,
-    pub price: i32,
+    pub price: i64,
     pub has_sold: bool,
     pub is_stattrak: bool,
+    // Average of the prices observed in the last 7 days, updated when the price changes.
+    pub avg_price_7d: Option<i64>,
+    // Phase of a Doppler or Gamma Doppler skin, e.g. `Phase 2` or `Ruby`.
+    pub phase: Option<String>,
+    pub is_souvenir: bool,
+    // The wear value, when the site lists it next to the exterior.
+    pub float_value: Option<f32>,
+    // The name tag the owner put on the item, without the quotes.
+    pub nametag: Option<String>,
+}
+
+impl Item {
+    // The name as a buyer would read it, e.g. `StatTrak™ AK-47 | Redline (Field-Tested) "My Rifle"`.
+    // Used for notifications.
+    pub fn full_name(&self) -> String {
+        let stattrak = if self.is_stattrak {
+            "StatTrak™ "
+        } else if self.is_souvenir {
+            "Souvenir "
+        } else {
+            ""
+        };
+        let name = match (&self.kind, &self.exterior) {
+            (Some(kind), Some(exterior)) => format!(
+                "{}{} | {} ({}{}){}",
+                stattrak,
+                self.name,
+                kind,
+                exterior,
+                self.float_suffix(),
+                self.phase_suffix()
+            ),
+            (Some(kind), None) => format!(
+                "{}{} | {}{}",
+                stattrak,
+                self.name,
+                kind,
+                self.phase_suffix()
+            ),
+            (None, _) => format!("{}{} | Vanilla", stattrak, self.name),
+        };
+        name + &self.nametag_suffix()
+    }
+
+    // ` "My Rifle"` to go at the end, or nothing.
+    fn nametag_suffix(&self) -> String {
+        match &self.nametag {
+            Some(nametag) => format!(" \"{}\"", nametag),
+            None => String::new(),
+        }
+    }
+
+    // `, 0.2341` to go after the exterior, or nothing.
+    fn float_suffix(&self) -> String {
+        match self.float_value {
+            Some(float) => format!(", {}", float),
+            None => String::new(),
+        }
+    }
+
+    // ` - Phase 2`, as the site appends it, or nothing.
+    fn phase_suffix(&self) -> String {
+        match &self.phase {
+            Some(phase) => format!(" - {}", phase),
+            None => String::new(),
+        }
+    }
 }
 
 // For without exterior.
 impl std::fmt::Display for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match &self.kind {
-            Some(kind) => write!(f, "{} | {}", self.name, kind),
-            None => write!(f, "{} | Vanilla", self.name),
+            Some(kind) => write!(f, "{} | {}{}", self.name, kind, self.phase_suffix())?,
+            None => write!(f, "{} | Vanilla", self.name)?,
         }
+        write!(f, "{}", self.nametag_suffix())
     }
 }
 
@@ -35,27 +109,104 @@ impl std::fmt::LowerExp for Item {
         match &self.kind {
             Some(kind) => write!(
                 f,
-                "{} | {} ({})",
+                "{} | {} ({}{}){}",
                 self.name,
                 kind,
-                self.exterior.as_ref().unwrap()
-            ),
-            None => write!(f, "{} | Vanilla", self.name),
+                self.exterior.as_ref().unwrap(),
+                self.float_suffix(),
+                self.phase_suffix()
+            )?,
+            None => write!(f, "{} | Vanilla", self.name)?,
         }
+        write!(f, "{}", self.nametag_suffix())
     }
 }
 
-#[derive(Display, PartialEq, EnumString, Debug, AsRefStr, Clone, DbEnum)]
+// Parsed from either the full name or the abbreviation, and always displayed as the full name.
+#[derive(Display, PartialEq, EnumString, Debug, AsRefStr, Clone, DbEnum, Serialize, Deserialize)]
 #[DbValueStyle = "SCREAMING_SNAKE_CASE"]
 pub enum Exterior {
-    #[strum(serialize = "Factory New")]
+    #[strum(to_string = "Factory New", serialize = "FN")]
     FN,
-    #[strum(serialize = "Minimal Wear")]
+    #[strum(to_string = "Minimal Wear", serialize = "MW")]
     MW,
-    #[strum(serialize = "Field-Tested")]
+    #[strum(to_string = "Field-Tested", serialize = "FT")]
     FT,
-    #[strum(serialize = "Well-Worn")]
+    #[strum(to_string = "Well-Worn", serialize = "WW")]
     WW,
-    #[strum(serialize = "Battle-Scarred")]
+    #[strum(to_string = "Battle-Scarred", serialize = "BS")]
     BS,
 }
+
+#[derive(Queryable, Debug)]
+pub struct PriceHistory {
+    pub id: i32,
+    pub order_id: i32,
+    pub price: i64,
+    pub recorded_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "price_history"]
+pub struct NewPriceHistory {
+    pub order_id: i32,
+    pub price: i64,
+    pub recorded_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "run_history"]
+pub struct NewRunHistory {
+    pub duration_ms: i32,
+    pub finished_at: NaiveDateTime,
+}
+
+// The single row of what the last normal run scraped, see `layout`.
+#[derive(Queryable, Insertable, Debug)]
+#[table_name = "scrape_state"]
+pub struct ScrapeState {
+    pub id: i32,
+    pub layout_hash: String,
+    pub item_count: i32,
+    pub updated_at: NaiveDateTime,
+}
+
+// A notification waiting to be sent, see `pending`.
+#[derive(Queryable, Debug)]
+pub struct PendingNotification {
+    pub id: i32,
+    // The `NotificationEvent` as JSON.
+    pub event: String,
+    // How many runs failed to send it through every sink.
+    pub attempts: i32,
+    // The bits of the sinks which sent it already, see `notifier::SinkKind`.
+    pub sent_to: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "pending_notifications"]
+pub struct NewPendingNotification {
+    pub event: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Identifiable, PartialEq, Clone, Debug)]
+#[table_name = "tokens"]
+pub struct Token {
+    pub id: i32,
+    pub registration_id: String,
+}
+
+// A price alert, notified when a matching item is listed at or below `target_price`.
+// `name` is written with its StatTrak™ or Souvenir prefix, which only matches such items,
+// `kind` is None for vanilla and `exterior` None for any exterior.
+#[derive(Queryable, Identifiable, PartialEq, Clone, Debug)]
+#[table_name = "watch"]
+pub struct Watch {
+    pub id: i32,
+    pub name: String,
+    pub kind: Option<String>,
+    pub exterior: Option<Exterior>,
+    pub target_price: i64,
+}
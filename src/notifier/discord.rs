@@ -0,0 +1,75 @@
+use anyhow::anyhow;
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde_json::json;
+
+use super::{NotificationEvent, Sink};
+use crate::config::Lang;
+use crate::events::ItemEvent;
+use crate::messages;
+
+const COLOR_ADDED: u32 = 0x2ecc71;
+const COLOR_PRICE_DROP: u32 = 0x3498db;
+const COLOR_PRICE_RISE: u32 = 0xe67e22;
+const COLOR_SOLD: u32 = 0xe74c3c;
+const COLOR_DELETED: u32 = 0x95a5a6;
+const COLOR_OTHER: u32 = 0xf1c40f;
+
+// Posts each notification as an embed to a Discord channel webhook.
+pub struct DiscordWebhook {
+    url: String,
+    lang: Lang,
+}
+
+impl DiscordWebhook {
+    pub fn new(url: impl Into<String>, lang: Lang) -> Self {
+        DiscordWebhook {
+            url: url.into(),
+            lang,
+        }
+    }
+}
+
+fn color(event: &NotificationEvent) -> u32 {
+    match event {
+        NotificationEvent::Item(ItemEvent::Added { .. })
+        | NotificationEvent::Item(ItemEvent::Relisted { .. }) => COLOR_ADDED,
+        NotificationEvent::Item(ItemEvent::PriceChanged {
+            old_price,
+            new_price,
+            ..
+        }) if new_price < old_price => COLOR_PRICE_DROP,
+        NotificationEvent::Item(ItemEvent::PriceChanged { .. }) => COLOR_PRICE_RISE,
+        NotificationEvent::TargetReached { .. } => COLOR_PRICE_DROP,
+        NotificationEvent::Item(ItemEvent::Sold { .. }) => COLOR_SOLD,
+        NotificationEvent::Item(ItemEvent::Deleted { .. }) => COLOR_DELETED,
+        _ => COLOR_OTHER,
+    }
+}
+
+impl Sink for DiscordWebhook {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let notification = messages::notification(event, self.lang, false);
+            let body = json!({
+                "embeds": [{
+                    "title": notification.title(),
+                    "description": notification.body(),
+                    "color": color(event),
+                }]
+            });
+
+            let mut response = surf::post(&self.url)
+                .body_json(&body)?
+                .await
+                .map_err(|e| anyhow!(e))?;
+
+            if !response.status().is_success() {
+                let text = response.body_string().await.unwrap_or_default();
+                return Err(anyhow!("Discord returned {}: {}", response.status(), text));
+            }
+
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
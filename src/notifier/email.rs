@@ -0,0 +1,112 @@
+use futures::future::{FutureExt, LocalBoxFuture};
+
+use super::{escape_html, NotificationEvent, Sink};
+use crate::catalog::Messages;
+use crate::config::Lang;
+use crate::digest::{DigestChange, DigestRow};
+use crate::messages;
+use crate::smtp::{self, Credentials, Mail, Server};
+
+// Sends each notification as an email, with a table of the changed items. Meant for
+// `NOTIFY_MODE=digest`, where a run comes down to a single email with every change in it.
+pub struct EmailSink {
+    server: Server,
+    credentials: Option<Credentials>,
+    from: String,
+    to: Vec<String>,
+    lang: Lang,
+}
+
+impl EmailSink {
+    pub fn new(
+        server: Server,
+        credentials: Option<Credentials>,
+        from: impl Into<String>,
+        to: Vec<String>,
+        lang: Lang,
+    ) -> Self {
+        EmailSink {
+            server,
+            credentials,
+            from: from.into(),
+            to,
+            lang,
+        }
+    }
+}
+
+// The items of the notification, as many as a digest has or at most one otherwise.
+fn rows(event: &NotificationEvent) -> Vec<DigestRow> {
+    match event {
+        NotificationEvent::Digest(digest) => digest.rows.clone(),
+        NotificationEvent::Item(event) => DigestRow::from_event(event).into_iter().collect(),
+        NotificationEvent::Listing { item } => vec![DigestRow {
+            change: DigestChange::Listed,
+            name: item.full_name(),
+            price: item.price,
+            delta: None,
+        }],
+        _ => vec![],
+    }
+}
+
+// The title as the heading and the body as a paragraph, then the table of the items.
+fn format_html(title: &str, body: &str, rows: &[DigestRow], lang: Lang) -> String {
+    let messages = Messages::for_lang(lang);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<body>\n<h2>{}</h2>\n",
+        escape_html(title)
+    );
+    if !body.is_empty() {
+        html.push_str(&format!(
+            "<p>{}</p>\n",
+            escape_html(body).replace('\n', "<br>\n")
+        ));
+    }
+
+    if !rows.is_empty() {
+        html.push_str(
+            "<table border=\"1\" cellpadding=\"4\" cellspacing=\"0\" style=\"border-collapse: collapse\">\n",
+        );
+        html.push_str(&format!(
+            "<tr><th>{}</th><th>{}</th><th>{}</th><th>{}</th></tr>\n",
+            messages.table_change, messages.table_item, messages.table_price, messages.table_delta
+        ));
+        for row in rows {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td align=\"right\">{}</td><td align=\"right\">{}</td></tr>\n",
+                messages.change(row.change),
+                escape_html(&row.name),
+                messages.price(row.price),
+                row.delta
+                    .map(|delta| messages.price_delta(delta))
+                    .unwrap_or_default()
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+impl Sink for EmailSink {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let notification = messages::notification(event, self.lang, false);
+            let title = notification.title().unwrap_or_default();
+            let body = notification.body().unwrap_or_default();
+            let mail = Mail {
+                from: self.from.clone(),
+                to: self.to.clone(),
+                subject: title.to_owned(),
+                text: format!("{}\n\n{}\n", title, body),
+                html: format_html(title, body, &rows(event), self.lang),
+            };
+
+            smtp::send(&self.server, self.credentials.as_ref(), &mail).await?;
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
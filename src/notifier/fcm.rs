@@ -0,0 +1,203 @@
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use futures::future::{FutureExt, LocalBoxFuture};
+use tracing::warn;
+
+use super::{NotificationEvent, Sink, WebhookPayload};
+use crate::config::{FcmAuth, FcmConfig, Lang, Sounds};
+use crate::events::ItemEvent;
+use crate::fcm::{Client, FcmResponse, MessageBuilder, Priority, ServiceAccountKey};
+use crate::messages;
+
+// Built once by `main` and shared by every run, so a service account's access token is reused.
+pub fn build_client(config: &FcmConfig) -> anyhow::Result<Client> {
+    let client = match &config.auth {
+        FcmAuth::ServerKey(key) => Client::new_multi(key, config.registration_ids.clone())?,
+        FcmAuth::ServiceAccountFile(path) => Client::from_service_account_multi(
+            ServiceAccountKey::from_file(path)?,
+            config.registration_ids.clone(),
+        )?,
+        FcmAuth::ServiceAccountJson(json) => Client::from_service_account_multi(
+            ServiceAccountKey::from_json(json)?,
+            config.registration_ids.clone(),
+        )?,
+    };
+
+    match &config.proxy {
+        Some(proxy) => client.with_proxy(proxy),
+        None => Ok(client),
+    }
+}
+
+// The kinds of `NotificationEvent::kind`, with `price_changed` told apart by its direction.
+pub const PRIORITY_KINDS: &[&str] = &[
+    "added",
+    "price_drop",
+    "price_rise",
+    "sold",
+    "deleted",
+    "relisted",
+    "trend",
+    "details_changed",
+    "digest",
+    "unknown_sold",
+    "slow_run",
+    "scrape_failed",
+    "layout_changed",
+    "listing",
+    "target_reached",
+];
+
+// Sent with high priority unless `FCM_HIGH_PRIORITY` says otherwise: the deals someone may
+// snap up before the next run.
+pub const DEFAULT_HIGH_PRIORITY: &[&str] = &["added", "relisted", "price_drop", "target_reached"];
+
+// The kind of the event among `PRIORITY_KINDS`.
+fn priority_kind(event: &NotificationEvent) -> &'static str {
+    match event {
+        NotificationEvent::Item(ItemEvent::PriceChanged {
+            old_price,
+            new_price,
+            ..
+        }) if new_price < old_price => "price_drop",
+        NotificationEvent::Item(ItemEvent::PriceChanged { .. }) => "price_rise",
+        event => event.kind(),
+    }
+}
+
+// High for the events whose kind, one of `PRIORITY_KINDS`, is in `high_priority`, so the
+// device is woken for them right away, and normal priority, which may be delayed, otherwise.
+pub fn priority(event: &NotificationEvent, high_priority: &[String]) -> Priority {
+    let kind = priority_kind(event);
+    if high_priority.iter().any(|high| high == kind) {
+        Priority::High
+    } else {
+        Priority::Normal
+    }
+}
+
+// The sound of the event's kind, or the default sound, or None to send no sound at all.
+pub fn sound<'s>(event: &NotificationEvent, sounds: &'s Sounds) -> Option<&'s str> {
+    match sounds.by_kind.get(priority_kind(event)) {
+        Some(sound) => sound.as_deref(),
+        None => sounds.default.as_deref(),
+    }
+}
+
+pub struct FcmSink<'c> {
+    client: &'c Client,
+    // `FCM_REGISTRATION_ID(S)` and the registered tokens, see `tokens::recipients`. The client's
+    // own target is used while it's empty.
+    registration_ids: Vec<String>,
+    lang: Lang,
+    use_loc_keys: bool,
+    // Sets the tag and collapse key of item notifications, so the latest one replaces the others.
+    collapse: bool,
+    // Also sends the change as a data-only message, for the app to sync in the background.
+    send_data: bool,
+    // The kinds sent with high priority, see `priority`.
+    high_priority: &'c [String],
+    // The sound of each kind, see `sound`. Without them no sound is set.
+    sounds: Option<&'c Sounds>,
+    // Kept so the caller can prune the tokens FCM reported as dead.
+    responses: Mutex<Vec<FcmResponse>>,
+}
+
+impl<'c> FcmSink<'c> {
+    pub fn new(
+        client: &'c Client,
+        registration_ids: Vec<String>,
+        lang: Lang,
+        use_loc_keys: bool,
+        collapse: bool,
+        send_data: bool,
+        high_priority: &'c [String],
+    ) -> Self {
+        FcmSink {
+            client,
+            registration_ids,
+            lang,
+            use_loc_keys,
+            collapse,
+            send_data,
+            high_priority,
+            sounds: None,
+            responses: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn with_sounds(mut self, sounds: &'c Sounds) -> Self {
+        self.sounds = Some(sounds);
+        self
+    }
+
+    fn message_builder(&self) -> MessageBuilder {
+        if self.registration_ids.is_empty() {
+            self.client.message_builder()
+        } else {
+            self.client.message_builder_to(&self.registration_ids)
+        }
+    }
+
+    async fn send_message(&self, builder: MessageBuilder) -> anyhow::Result<()> {
+        let response = self.client.send(builder.build()?).await?;
+
+        for error in response.results.iter().filter_map(|r| r.error.as_ref()) {
+            warn!(?error, "FCM rejected a token");
+        }
+        // Kept even when failing, so the dead tokens are pruned before the retry.
+        let delivered = response.is_delivered();
+        self.responses.lock().unwrap().push(response);
+
+        if !delivered {
+            return Err(anyhow!("FCM could not deliver the message to every live token"));
+        }
+        Ok(())
+    }
+
+    // The same fields as the generic webhook, so one parser on the app handles both.
+    async fn send_data_message(&self, event: &NotificationEvent) -> anyhow::Result<()> {
+        let mut builder = self.message_builder();
+        builder
+            .data(&WebhookPayload::new(event, self.lang))?
+            .content_available(true);
+        self.send_message(builder).await
+    }
+
+    // The responses of every send so far, in no particular order.
+    pub fn take_responses(&self) -> Vec<FcmResponse> {
+        std::mem::take(&mut *self.responses.lock().unwrap())
+    }
+}
+
+impl Sink for FcmSink<'_> {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let mut notification = messages::notification(event, self.lang, self.use_loc_keys);
+            let mut builder = self.message_builder();
+            if let Some(key) = event.collapse_key().filter(|_| self.collapse) {
+                notification.tag = Some(key.clone());
+                builder.collapse_key(key);
+            }
+            if let Some(sound) = self.sounds.and_then(|sounds| sound(event, sounds)) {
+                notification.sound = Some(sound.to_owned());
+            }
+            builder
+                .notification(notification)
+                .priority(priority(event, self.high_priority));
+            self.send_message(builder).await?;
+
+            // The notification went out already, so a failed data message is only logged, instead
+            // of queueing the notification to be shown again.
+            if self.send_data {
+                if let Err(error) = self.send_data_message(event).await {
+                    warn!(?error, kind = event.kind(), "failed to send the FCM data message");
+                }
+            }
+
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
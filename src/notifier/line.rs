@@ -0,0 +1,95 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use futures::future::{FutureExt, LocalBoxFuture};
+
+use super::{send_with_retry, NotificationEvent, Sink};
+use crate::config::Lang;
+use crate::messages;
+
+const ENDPOINT: &str = "https://notify-api.line.me/api/notify";
+// LINE Notify rejects longer messages.
+const MAX_MESSAGE_CHARS: usize = 1000;
+
+// Posts each notification as a message to LINE Notify.
+pub struct LineNotify {
+    token: String,
+    lang: Lang,
+}
+
+impl LineNotify {
+    pub fn new(token: impl Into<String>, lang: Lang) -> Self {
+        LineNotify {
+            token: token.into(),
+            lang,
+        }
+    }
+
+    async fn post(&self, message: &str) -> anyhow::Result<surf::Response> {
+        surf::post(ENDPOINT)
+            .set_header("Authorization", format!("Bearer {}", self.token))
+            .body_form(&[("message", message)])?
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+}
+
+// LINE prefixes the message with the token's name, so the text starts on a new line.
+fn format_message(event: &NotificationEvent, lang: Lang) -> String {
+    let notification = messages::notification(event, lang, false);
+    let mut message = String::from("\n");
+    if let Some(title) = notification.title() {
+        message.push_str(title);
+    }
+    if let Some(body) = notification.body() {
+        message.push('\n');
+        message.push_str(body);
+    }
+
+    truncate(message)
+}
+
+fn truncate(message: String) -> String {
+    if message.chars().count() <= MAX_MESSAGE_CHARS {
+        return message;
+    }
+
+    let mut truncated = message
+        .chars()
+        .take(MAX_MESSAGE_CHARS - 1)
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+// How long to wait before the rate limit resets, from `X-RateLimit-Reset` (epoch seconds).
+fn rate_limit_wait(response: &mut surf::Response) -> LocalBoxFuture<'_, Option<Duration>> {
+    async move {
+        let reset = response.header("X-RateLimit-Reset")?.parse::<u64>().ok()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        Some(Duration::from_secs(reset.saturating_sub(now)))
+    }
+    .boxed_local()
+}
+
+impl Sink for LineNotify {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let message = format_message(event, self.lang);
+            let mut response =
+                send_with_retry("LINE Notify", || self.post(&message), rate_limit_wait).await?;
+
+            if !response.status().is_success() {
+                let text = response.body_string().await.unwrap_or_default();
+                return Err(anyhow!(
+                    "LINE Notify returned {}: {}",
+                    response.status(),
+                    text
+                ));
+            }
+
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
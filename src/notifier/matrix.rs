@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde_derive::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use super::{escape_html, send_with_retry, NotificationEvent, Sink};
+use crate::catalog::{self, Messages};
+use crate::config::Lang;
+use crate::events::ItemEvent;
+use crate::messages;
+
+// Sends each notification as a message to a Matrix room, with the access token of a user in it.
+pub struct MatrixSink {
+    homeserver: String,
+    room_id: String,
+    access_token: String,
+    lang: Lang,
+}
+
+// The body of a failed request, which tells how long to back off after a 429.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    errcode: Option<String>,
+    error: Option<String>,
+    retry_after_ms: Option<u64>,
+}
+
+impl MatrixSink {
+    pub fn new(
+        homeserver: impl Into<String>,
+        room_id: impl Into<String>,
+        access_token: impl Into<String>,
+        lang: Lang,
+    ) -> Self {
+        MatrixSink {
+            homeserver: homeserver.into(),
+            room_id: room_id.into(),
+            access_token: access_token.into(),
+            lang,
+        }
+    }
+
+    // The room id and the transaction id are pushed as path segments, which escapes a `/` or `#` in them.
+    fn send_url(&self, txn_id: &str) -> anyhow::Result<Url> {
+        let mut url = Url::parse(&self.homeserver)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("invalid Matrix homeserver {}", self.homeserver))?
+            .pop_if_empty()
+            .extend(&[
+                "_matrix",
+                "client",
+                "r0",
+                "rooms",
+                &self.room_id,
+                "send",
+                "m.room.message",
+                txn_id,
+            ]);
+        Ok(url)
+    }
+
+    async fn put(
+        &self,
+        url: &Url,
+        (text, html): &(String, String),
+    ) -> anyhow::Result<surf::Response> {
+        surf::put(url.as_str())
+            .set_header("Authorization", format!("Bearer {}", self.access_token))
+            .body_json(&json!({
+                "msgtype": "m.text",
+                "body": text,
+                "format": "org.matrix.custom.html",
+                "formatted_body": html,
+            }))?
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+}
+
+// The message as plain text, for the clients without HTML, and as HTML with the item name in bold
+// and the price changes, colored green for a drop and red for a rise.
+fn format_message(event: &NotificationEvent, lang: Lang) -> (String, String) {
+    let notification = messages::notification(event, lang, false);
+    let messages = Messages::for_lang(lang);
+    let title = notification.title().unwrap_or_default();
+    let mut text = title.to_owned();
+    let mut html = escape_html(title);
+    if let Some(item) = event.item() {
+        let name = escape_html(&item.full_name());
+        html = html.replacen(&name, &format!("<strong>{}</strong>", name), 1);
+    }
+
+    match event {
+        NotificationEvent::Item(ItemEvent::PriceChanged {
+            old_price,
+            new_price,
+            ..
+        }) => {
+            let prices = format!(
+                "{} → {}",
+                messages.price(*old_price),
+                messages.price(*new_price)
+            );
+            let delta = format!(
+                "{}, {}%",
+                messages.price_delta(new_price - old_price),
+                catalog::format_percent_change(*old_price, *new_price)
+            );
+            text.push('\n');
+            text.push_str(&messages.price_change(*old_price, *new_price));
+            html.push_str(&format!(
+                "<br>{} ({})",
+                escape_html(&prices),
+                colored_delta(&delta, new_price - old_price)
+            ));
+        }
+        NotificationEvent::Digest(digest) if !digest.rows.is_empty() => {
+            if let Some(body) = notification.body() {
+                text.push('\n');
+                text.push_str(body);
+            }
+            html.push_str("<ul>");
+            for row in &digest.rows {
+                html.push_str(&format!(
+                    "<li>{}: {} {}",
+                    messages.change(row.change),
+                    escape_html(&row.name),
+                    escape_html(&messages.price(row.price))
+                ));
+                if let Some(delta) = row.delta {
+                    html.push_str(&format!(
+                        " ({})",
+                        colored_delta(&messages.price_delta(delta), delta)
+                    ));
+                }
+                html.push_str("</li>");
+            }
+            html.push_str("</ul>");
+        }
+        _ => {
+            if let Some(body) = notification.body() {
+                text.push('\n');
+                text.push_str(body);
+                html.push_str("<br>");
+                html.push_str(&escape_html(body).replace('\n', "<br>"));
+            }
+        }
+    }
+
+    (text, html)
+}
+
+fn colored_delta(delta: &str, amount: i64) -> String {
+    let color = if amount < 0 { "#2e7d32" } else { "#c62828" };
+    format!("<font color=\"{}\">{}</font>", color, escape_html(delta))
+}
+
+// How long the homeserver asks to back off after a 429.
+fn rate_limit_wait(response: &mut surf::Response) -> LocalBoxFuture<'_, Option<Duration>> {
+    async move {
+        let failed: ErrorResponse = response.body_json().await.ok()?;
+        failed.retry_after_ms.map(Duration::from_millis)
+    }
+    .boxed_local()
+}
+
+impl MatrixSink {
+    // The homeserver drops a message whose transaction id it has seen from this token before, so
+    // a retry with the same id can't post the message twice.
+    async fn send_message(&self, event: &NotificationEvent, txn_id: &str) -> anyhow::Result<()> {
+        let message = format_message(event, self.lang);
+        let url = self.send_url(txn_id)?;
+        let mut response =
+            send_with_retry("Matrix", || self.put(&url, &message), rate_limit_wait).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let failed: Option<ErrorResponse> = response.body_json().await.ok();
+            let (errcode, error) = failed
+                .map(|failed| (failed.errcode, failed.error))
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "Matrix returned {}: {} {}",
+                status,
+                errcode.unwrap_or_default(),
+                error.unwrap_or_default()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Sink for MatrixSink {
+    // A notification which isn't queued is never sent again, so any id new to this token will do.
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let txn_id = format!("takya-{:016x}", rand::random::<u64>());
+            self.send_message(event, &txn_id).await
+        }
+        .boxed_local()
+    }
+
+    // The id comes from the row, so a later run retrying it reuses it, while another notification
+    // with the same content gets an id of its own.
+    fn send_queued<'a>(
+        &'a self,
+        event: &'a NotificationEvent,
+        key: &'a str,
+    ) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move { self.send_message(event, &format!("takya-{}", key)).await }.boxed_local()
+    }
+}
@@ -0,0 +1,261 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures::future::LocalBoxFuture;
+use futures::StreamExt;
+use serde_derive::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::digest::DigestBuilder;
+use super::events::ItemEvent;
+use super::models::Item;
+
+mod discord;
+pub use self::discord::DiscordWebhook;
+mod email;
+pub use self::email::EmailSink;
+mod fcm;
+pub use self::fcm::{
+    build_client, priority, sound, FcmSink, DEFAULT_HIGH_PRIORITY, PRIORITY_KINDS,
+};
+mod line;
+pub use self::line::LineNotify;
+#[cfg(feature = "testing")]
+mod recording;
+#[cfg(feature = "testing")]
+pub use self::recording::RecordingSink;
+mod matrix;
+pub use self::matrix::MatrixSink;
+mod ntfy;
+mod rate_limit;
+pub use self::ntfy::{NtfySink, DEFAULT_SERVER as NTFY_DEFAULT_SERVER};
+pub use self::rate_limit::RateLimited;
+mod slack;
+pub use self::slack::SlackWebhook;
+mod telegram;
+pub use self::telegram::TelegramBot;
+mod webhook;
+pub use self::webhook::{GenericWebhook, WebhookPayload};
+
+// After a 429, a send is only retried if the service asks to wait at most this long.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+// Everything a run may notify about. Each sink formats it in its own way.
+// Serialized into `pending_notifications` until every sink has sent it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum NotificationEvent {
+    Item(ItemEvent),
+    Digest(DigestBuilder),
+    UnknownSold {
+        order_id: i32,
+        price: i64,
+    },
+    SlowRun {
+        elapsed: Duration,
+        baseline: Option<Duration>,
+    },
+    // The site could not be fetched or parsed, with the error.
+    ScrapeFailed {
+        error: String,
+    },
+    // An item which is currently listed, sent for each one by `--announce-current`.
+    Listing {
+        item: Item,
+    },
+    // Far fewer items than in the last run were found, in a layout other than the last one.
+    LayoutChanged {
+        found: usize,
+        previous: usize,
+    },
+    // A watched item is listed at or below the target price of the watch.
+    TargetReached {
+        item: Item,
+        target_price: i64,
+    },
+}
+
+impl NotificationEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NotificationEvent::Item(ItemEvent::Added { .. }) => "added",
+            NotificationEvent::Item(ItemEvent::PriceChanged { .. }) => "price_changed",
+            NotificationEvent::Item(ItemEvent::Sold { .. }) => "sold",
+            NotificationEvent::Item(ItemEvent::Deleted { .. }) => "deleted",
+            NotificationEvent::Item(ItemEvent::Relisted { .. }) => "relisted",
+            NotificationEvent::Item(ItemEvent::Trend { .. }) => "trend",
+            NotificationEvent::Item(ItemEvent::DetailsChanged { .. }) => "details_changed",
+            NotificationEvent::Digest(_) => "digest",
+            NotificationEvent::UnknownSold { .. } => "unknown_sold",
+            NotificationEvent::SlowRun { .. } => "slow_run",
+            NotificationEvent::ScrapeFailed { .. } => "scrape_failed",
+            NotificationEvent::LayoutChanged { .. } => "layout_changed",
+            NotificationEvent::Listing { .. } => "listing",
+            NotificationEvent::TargetReached { .. } => "target_reached",
+        }
+    }
+
+    // The item the notification is about, if it is about a single one.
+    pub fn item(&self) -> Option<&Item> {
+        match self {
+            NotificationEvent::Item(event) => Some(event.item()),
+            NotificationEvent::Listing { item } | NotificationEvent::TargetReached { item, .. } => {
+                Some(item)
+            }
+            _ => None,
+        }
+    }
+
+    // Notifications about the same item share this key, and the others are never collapsed.
+    pub fn collapse_key(&self) -> Option<String> {
+        match self {
+            NotificationEvent::Item(event) => Some(format!("item-{}", event.item().order_id)),
+            NotificationEvent::Listing { item } => Some(format!("item-{}", item.order_id)),
+            _ => None,
+        }
+    }
+}
+
+// A destination notifications are delivered to. Only the FCM clients can resolve localization
+// keys, so every other sink renders the texts itself, whatever `USE_LOC_KEYS` is.
+pub trait Sink {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>>;
+
+    // Sends a notification queued in `pending_notifications`, with its `Outgoing::key`. A sink
+    // which can tell the service to drop a repeated send overrides this to pass it on.
+    fn send_queued<'a>(
+        &'a self,
+        event: &'a NotificationEvent,
+        _key: &'a str,
+    ) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        self.send(event)
+    }
+}
+
+// A notification to send, with what a retry of it needs to know.
+#[derive(Debug)]
+pub struct Outgoing {
+    pub event: NotificationEvent,
+    // The bits of the sinks which sent it already, see `SinkKind`.
+    pub sent_to: i32,
+    // Unique to a notification queued in `pending_notifications`, and the same on every retry of
+    // it. None for one which isn't queued.
+    pub key: Option<String>,
+}
+
+impl Outgoing {
+    // A notification which isn't queued, for every sink to send.
+    pub fn new(event: NotificationEvent) -> Self {
+        Outgoing {
+            event,
+            sent_to: 0,
+            key: None,
+        }
+    }
+}
+
+// The kinds of sink, each with a fixed bit in `pending_notifications.sent_to`, so the rows keep
+// their meaning when a sink is added to or removed from the config.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SinkKind {
+    Fcm,
+    Discord,
+    Line,
+    Slack,
+    Telegram,
+    Ntfy,
+    Matrix,
+    Email,
+    Webhook,
+}
+
+impl SinkKind {
+    pub fn bit(self) -> i32 {
+        1 << self as i32
+    }
+}
+
+// Sends a request with `send`, and if `service` answers it with a 429, waits as long as `wait`
+// reads from the response and sends it once more. A longer wait, or none at all, fails the send.
+async fn send_with_retry<F, Fut>(
+    service: &str,
+    send: F,
+    wait: for<'r> fn(&'r mut surf::Response) -> LocalBoxFuture<'r, Option<Duration>>,
+) -> anyhow::Result<surf::Response>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<surf::Response>>,
+{
+    let mut response = send().await?;
+    if response.status() == 429 {
+        match wait(&mut response).await {
+            Some(wait) if wait <= MAX_RATE_LIMIT_WAIT => {
+                warn!(?wait, "{} rate limit hit, retrying", service);
+                async_std::task::sleep(wait).await;
+                response = send().await?;
+            }
+            Some(wait) => {
+                return Err(anyhow!(
+                    "{} rate limit exceeded, not retried since it asks to wait {:?}",
+                    service,
+                    wait
+                ))
+            }
+            None => {
+                return Err(anyhow!(
+                    "{} rate limit exceeded, not retried since it doesn't say how long to wait",
+                    service
+                ))
+            }
+        }
+    }
+
+    Ok(response)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Sends every notification through every sink, with at most `concurrency` sends to each sink in
+// flight. Each sink goes at its own pace, so one waiting on its rate limit doesn't hold back the
+// others. The results come sink by sink, each with every notification in order.
+pub async fn dispatch(
+    sinks: &[&dyn Sink],
+    notifications: &[NotificationEvent],
+    concurrency: usize,
+) -> Vec<anyhow::Result<()>> {
+    let sends = sinks
+        .iter()
+        .map(|&sink| (sink, notifications.iter().map(|event| (event, None)).collect()))
+        .collect::<Vec<_>>();
+    dispatch_each(&sends, concurrency)
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+// A notification for `dispatch_each`, with its `Outgoing::key` if it is queued.
+pub type Keyed<'a> = (&'a NotificationEvent, Option<&'a str>);
+
+// Like `dispatch`, with the notifications to send through each sink given along with it. The
+// results come for each sink, in the order of its notifications.
+pub async fn dispatch_each(
+    sends: &[(&dyn Sink, Vec<Keyed<'_>>)],
+    concurrency: usize,
+) -> Vec<Vec<anyhow::Result<()>>> {
+    let per_sink = sends.iter().map(|(sink, notifications)| {
+        let sends = notifications.iter().map(move |&(event, key)| match key {
+            Some(key) => sink.send_queued(event, key),
+            None => sink.send(event),
+        });
+        futures::stream::iter(sends)
+            .buffered(concurrency)
+            .collect::<Vec<_>>()
+    });
+    futures::future::join_all(per_sink).await
+}
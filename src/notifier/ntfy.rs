@@ -0,0 +1,103 @@
+use anyhow::anyhow;
+use futures::future::{FutureExt, LocalBoxFuture};
+
+use super::{NotificationEvent, Sink};
+use crate::config::Lang;
+use crate::events::ItemEvent;
+use crate::messages;
+
+pub const DEFAULT_SERVER: &str = "https://ntfy.sh";
+
+// Publishes each notification to a topic of an ntfy server, ntfy.sh or a self-hosted one.
+pub struct NtfySink {
+    url: String,
+    token: Option<String>,
+    lang: Lang,
+}
+
+impl NtfySink {
+    pub fn new(server: &str, topic: &str, token: Option<String>, lang: Lang) -> Self {
+        NtfySink {
+            url: format!("{}/{}", server.trim_end_matches('/'), topic),
+            token,
+            lang,
+        }
+    }
+}
+
+// The priority (1 to 5, 3 being the default) and the tags, shown as emojis, of each kind of event.
+fn priority_and_tags(event: &NotificationEvent) -> (u8, &'static str) {
+    match event {
+        NotificationEvent::Item(ItemEvent::PriceChanged {
+            old_price,
+            new_price,
+            ..
+        }) if new_price < old_price => (4, "green_circle,chart_with_downwards_trend"),
+        NotificationEvent::Item(ItemEvent::PriceChanged { .. }) => (3, "chart_with_upwards_trend"),
+        NotificationEvent::Item(ItemEvent::Added { .. })
+        | NotificationEvent::Item(ItemEvent::Relisted { .. }) => (3, "new"),
+        NotificationEvent::Item(ItemEvent::Sold { .. }) | NotificationEvent::UnknownSold { .. } => {
+            (2, "moneybag")
+        }
+        NotificationEvent::Item(ItemEvent::Deleted { .. }) => (2, "wastebasket"),
+        NotificationEvent::Item(ItemEvent::Trend { .. }) => (3, "chart"),
+        NotificationEvent::Item(ItemEvent::DetailsChanged { .. }) => (3, "pencil2"),
+        NotificationEvent::Listing { .. } => (3, "shopping_cart"),
+        NotificationEvent::Digest(_) => (3, "clipboard"),
+        NotificationEvent::SlowRun { .. } => (2, "hourglass"),
+        NotificationEvent::ScrapeFailed { .. } => (4, "warning"),
+        NotificationEvent::LayoutChanged { .. } => (4, "warning,construction"),
+        NotificationEvent::TargetReached { .. } => (5, "green_circle,dart"),
+    }
+}
+
+// Headers can only carry ASCII, so any other title is sent RFC 2047 encoded, which ntfy decodes.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_owned()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64::encode(value))
+    }
+}
+
+impl Sink for NtfySink {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let notification = messages::notification(event, self.lang, false);
+            let title = notification.title().unwrap_or_default();
+            // The message is required, so a notification without a body is sent as its title alone.
+            let (title, message) = match notification.body() {
+                Some(body) => (Some(title), body),
+                None => (None, title),
+            };
+            let (priority, tags) = priority_and_tags(event);
+
+            let mut request = surf::post(&self.url)
+                .set_header("Priority", priority.to_string())
+                .set_header("Tags", tags);
+            if let Some(title) = title {
+                request = request.set_header("Title", encode_header(title));
+            }
+            if let Some(token) = &self.token {
+                request = request.set_header("Authorization", format!("Bearer {}", token));
+            }
+
+            let mut response = request
+                .body_string(message.to_owned())
+                .await
+                .map_err(|e| anyhow!(e))?;
+            if !response.status().is_success() {
+                // Errors come back as JSON from ntfy itself, but as plain text from a proxy in front of it.
+                let text = response.body_string().await.unwrap_or_default();
+                return Err(anyhow!(
+                    "ntfy returned {}: {}",
+                    response.status(),
+                    text.trim()
+                ));
+            }
+
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
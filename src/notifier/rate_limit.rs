@@ -0,0 +1,69 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures::future::{FutureExt, LocalBoxFuture};
+
+use super::{NotificationEvent, Sink};
+
+// Spaces the sends to a sink at least `1 / rate` seconds apart, as a leaky bucket: each send
+// takes the next free slot, and waits for it asleep, so the other sinks keep sending meanwhile.
+pub struct RateLimited<'a> {
+    sink: &'a dyn Sink,
+    interval: Option<Duration>,
+    next_slot: Mutex<Option<Instant>>,
+}
+
+impl<'a> RateLimited<'a> {
+    // `rate` is in sends per second, and None sends as fast as the sink takes them.
+    pub fn new(sink: &'a dyn Sink, rate: Option<f64>) -> Self {
+        RateLimited {
+            sink,
+            interval: rate.map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            next_slot: Mutex::new(None),
+        }
+    }
+
+    // How long to wait for the next free slot, which is taken at once, so the sends which are
+    // already waiting line up behind it in the order they asked.
+    fn reserve(&self, interval: Duration) -> Duration {
+        let now = Instant::now();
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let slot = next_slot.map_or(now, |next| next.max(now));
+        *next_slot = Some(slot + interval);
+
+        slot - now
+    }
+}
+
+impl<'s> RateLimited<'s> {
+    async fn wait_for_slot(&self) {
+        if let Some(interval) = self.interval {
+            let wait = self.reserve(interval);
+            if wait > Duration::from_secs(0) {
+                async_std::task::sleep(wait).await;
+            }
+        }
+    }
+}
+
+impl Sink for RateLimited<'_> {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            self.wait_for_slot().await;
+            self.sink.send(event).await
+        }
+        .boxed_local()
+    }
+
+    fn send_queued<'a>(
+        &'a self,
+        event: &'a NotificationEvent,
+        key: &'a str,
+    ) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            self.wait_for_slot().await;
+            self.sink.send_queued(event, key).await
+        }
+        .boxed_local()
+    }
+}
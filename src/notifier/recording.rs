@@ -0,0 +1,45 @@
+use std::sync::{Arc, Mutex};
+
+use futures::future::{FutureExt, LocalBoxFuture};
+
+use super::{NotificationEvent, Sink};
+
+// Keeps every event it is sent instead of delivering it, for tests of the pipeline.
+// Clones share the same list, so a test can keep one and hand the other to the pipeline.
+#[derive(Clone, Default)]
+pub struct RecordingSink {
+    events: Arc<Mutex<Vec<NotificationEvent>>>,
+    keys: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The events received so far, in the order they were sent.
+    pub fn events(&self) -> Vec<NotificationEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    // The keys of the queued events received so far, in the order they were sent.
+    pub fn keys(&self) -> Vec<String> {
+        self.keys.lock().unwrap().clone()
+    }
+}
+
+impl Sink for RecordingSink {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        self.events.lock().unwrap().push(event.clone());
+        async { Ok(()) }.boxed_local()
+    }
+
+    fn send_queued<'a>(
+        &'a self,
+        event: &'a NotificationEvent,
+        key: &'a str,
+    ) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        self.keys.lock().unwrap().push(key.to_owned());
+        self.send(event)
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::anyhow;
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde_json::{json, Value};
+
+use super::{NotificationEvent, Sink};
+use crate::catalog::Messages;
+use crate::config::Lang;
+use crate::events::ItemEvent;
+use crate::messages;
+
+const COLOR_PRICE_DROP: &str = "#2eb886";
+const COLOR_PRICE_RISE: &str = "#e01e5a";
+const COLOR_GONE: &str = "#9e9e9e";
+const COLOR_OTHER: &str = "#1d9bd1";
+
+// Posts each notification to a Slack incoming webhook, as Block Kit blocks in a colored attachment.
+pub struct SlackWebhook {
+    url: String,
+    lang: Lang,
+}
+
+impl SlackWebhook {
+    pub fn new(url: impl Into<String>, lang: Lang) -> Self {
+        SlackWebhook {
+            url: url.into(),
+            lang,
+        }
+    }
+}
+
+fn color(event: &NotificationEvent) -> &'static str {
+    match event {
+        NotificationEvent::Item(ItemEvent::PriceChanged {
+            old_price,
+            new_price,
+            ..
+        }) if new_price < old_price => COLOR_PRICE_DROP,
+        NotificationEvent::Item(ItemEvent::PriceChanged { .. }) => COLOR_PRICE_RISE,
+        NotificationEvent::TargetReached { .. } => COLOR_PRICE_DROP,
+        NotificationEvent::Item(ItemEvent::Sold { .. })
+        | NotificationEvent::Item(ItemEvent::Deleted { .. }) => COLOR_GONE,
+        _ => COLOR_OTHER,
+    }
+}
+
+// A header with the item name, and the price change or the message body as context below it.
+fn blocks(event: &NotificationEvent, lang: Lang) -> Vec<Value> {
+    let notification = messages::notification(event, lang, false);
+    let header = match event {
+        NotificationEvent::Item(ItemEvent::PriceChanged { item, .. }) => item.full_name(),
+        _ => notification.title().unwrap_or_default().to_owned(),
+    };
+    let context = match event {
+        NotificationEvent::Item(ItemEvent::PriceChanged {
+            old_price,
+            new_price,
+            ..
+        }) => Some(Messages::for_lang(lang).price_change(*old_price, *new_price)),
+        _ => notification.body().map(str::to_owned),
+    };
+
+    let mut blocks = vec![json!({
+        "type": "header",
+        "text": { "type": "plain_text", "text": header },
+    })];
+    if let Some(context) = context {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": context }],
+        }));
+    }
+
+    blocks
+}
+
+impl Sink for SlackWebhook {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let body = json!({
+                "attachments": [{
+                    "color": color(event),
+                    "blocks": blocks(event, self.lang),
+                }]
+            });
+
+            let mut response = surf::post(&self.url)
+                .body_json(&body)?
+                .await
+                .map_err(|e| anyhow!(e))?;
+
+            // Slack answers `ok` on success, and an error code like `invalid_payload` otherwise.
+            let status = response.status();
+            let text = response.body_string().await.unwrap_or_default();
+            if !status.is_success() || text.trim() != "ok" {
+                return Err(anyhow!("Slack returned {}: {}", status, text));
+            }
+
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
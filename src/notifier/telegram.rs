@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use super::{escape_html, send_with_retry, NotificationEvent, Sink};
+use crate::catalog::Messages;
+use crate::config::Lang;
+use crate::events::ItemEvent;
+use crate::messages;
+
+const API_URL: &str = "https://api.telegram.org";
+
+// Sends each notification as a message from a Telegram bot to a chat.
+pub struct TelegramBot {
+    api_url: String,
+    token: String,
+    chat_id: String,
+    lang: Lang,
+}
+
+// The body of a failed request, which tells how long to back off after a 429.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Deserialize)]
+struct ResponseParameters {
+    retry_after: Option<u64>,
+}
+
+impl TelegramBot {
+    pub fn new(token: impl Into<String>, chat_id: impl Into<String>, lang: Lang) -> Self {
+        TelegramBot {
+            api_url: API_URL.to_owned(),
+            token: token.into(),
+            chat_id: chat_id.into(),
+            lang,
+        }
+    }
+
+    // Sends to another server than `api.telegram.org`, like a local Bot API server.
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    async fn post(&self, text: &str) -> anyhow::Result<surf::Response> {
+        let url = format!("{}/bot{}/sendMessage", self.api_url, self.token);
+        surf::post(url)
+            .body_json(&json!({
+                "chat_id": self.chat_id,
+                "text": text,
+                "parse_mode": "HTML",
+            }))?
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+}
+
+// The title with the item name in bold, followed by the price change or the body.
+fn format_message(event: &NotificationEvent, lang: Lang) -> String {
+    let notification = messages::notification(event, lang, false);
+    let mut message = escape_html(notification.title().unwrap_or_default());
+    if let Some(item) = event.item() {
+        let name = escape_html(&item.full_name());
+        message = message.replacen(&name, &format!("<b>{}</b>", name), 1);
+    }
+
+    let body = match event {
+        NotificationEvent::Item(ItemEvent::PriceChanged {
+            old_price,
+            new_price,
+            ..
+        }) => Some(escape_html(
+            &Messages::for_lang(lang).price_change(*old_price, *new_price),
+        )),
+        _ => notification.body().map(escape_html),
+    };
+    if let Some(body) = body {
+        message.push('\n');
+        message.push_str(&body);
+    }
+
+    message
+}
+
+// How long Telegram asks to back off after a 429.
+fn rate_limit_wait(response: &mut surf::Response) -> LocalBoxFuture<'_, Option<Duration>> {
+    async move {
+        let failed: ErrorResponse = response.body_json().await.ok()?;
+        failed
+            .parameters
+            .and_then(|parameters| parameters.retry_after)
+            .map(Duration::from_secs)
+    }
+    .boxed_local()
+}
+
+impl Sink for TelegramBot {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let text = format_message(event, self.lang);
+            let mut response =
+                send_with_retry("Telegram", || self.post(&text), rate_limit_wait).await?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let failed: Option<ErrorResponse> = response.body_json().await.ok();
+                let description = failed.and_then(|failed| failed.description);
+                return Err(anyhow!(
+                    "Telegram returned {}: {}",
+                    status,
+                    description.unwrap_or_default()
+                ));
+            }
+
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
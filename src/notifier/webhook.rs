@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde_derive::Serialize;
+use tracing::warn;
+
+use super::{NotificationEvent, Sink};
+use crate::config::Lang;
+use crate::events::ItemEvent;
+use crate::messages;
+use crate::models::{Exterior, Item};
+
+// A 5xx is retried this many times, waiting a little longer before each.
+const MAX_RETRIES: u32 = 2;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+// Posts each notification as JSON to any endpoint, for integrations without a sink of their own.
+pub struct GenericWebhook {
+    url: String,
+    headers: Vec<(&'static str, String)>,
+    lang: Lang,
+}
+
+// Every field is always present, null when the event has no such value.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct WebhookPayload<'a> {
+    pub event_type: &'static str,
+    pub order_id: Option<i32>,
+    pub name: Option<&'a str>,
+    pub kind: Option<&'a str>,
+    pub exterior: Option<&'a Exterior>,
+    pub old_price: Option<i64>,
+    pub new_price: Option<i64>,
+    pub title: Option<String>,
+    pub body: Option<String>,
+}
+
+impl<'a> WebhookPayload<'a> {
+    pub fn new(event: &'a NotificationEvent, lang: Lang) -> Self {
+        let (item, old_price, new_price): (Option<&Item>, _, _) = match event {
+            NotificationEvent::Item(ItemEvent::Added { item })
+            | NotificationEvent::Item(ItemEvent::Sold { item })
+            | NotificationEvent::Item(ItemEvent::Deleted { item })
+            | NotificationEvent::Item(ItemEvent::Relisted { item })
+            | NotificationEvent::Item(ItemEvent::DetailsChanged { item, .. })
+            | NotificationEvent::Listing { item }
+            | NotificationEvent::TargetReached { item, .. } => (Some(item), None, Some(item.price)),
+            NotificationEvent::Item(ItemEvent::PriceChanged {
+                item,
+                old_price,
+                new_price,
+                ..
+            }) => (Some(item), Some(*old_price), Some(*new_price)),
+            NotificationEvent::Item(ItemEvent::Trend { item, prices, .. }) => {
+                (Some(item), prices.first().copied(), prices.last().copied())
+            }
+            NotificationEvent::UnknownSold { price, .. } => (None, None, Some(*price)),
+            NotificationEvent::Digest(_)
+            | NotificationEvent::SlowRun { .. }
+            | NotificationEvent::ScrapeFailed { .. }
+            | NotificationEvent::LayoutChanged { .. } => (None, None, None),
+        };
+        let order_id = match event {
+            NotificationEvent::UnknownSold { order_id, .. } => Some(*order_id),
+            _ => item.map(|item| item.order_id),
+        };
+        let notification = messages::notification(event, lang, false);
+
+        WebhookPayload {
+            event_type: event.kind(),
+            order_id,
+            name: item.map(|item| item.name.as_str()),
+            kind: item.and_then(|item| item.kind.as_deref()),
+            exterior: item.and_then(|item| item.exterior.as_ref()),
+            old_price,
+            new_price,
+            title: notification.title().map(str::to_owned),
+            body: notification.body().map(str::to_owned),
+        }
+    }
+}
+
+impl GenericWebhook {
+    pub fn new(url: impl Into<String>, headers: Vec<(&'static str, String)>, lang: Lang) -> Self {
+        GenericWebhook {
+            url: url.into(),
+            headers,
+            lang,
+        }
+    }
+
+    async fn post(&self, payload: &WebhookPayload<'_>) -> anyhow::Result<surf::Response> {
+        let mut request = surf::post(&self.url);
+        for (name, value) in &self.headers {
+            request = request.set_header(name, value);
+        }
+
+        request.body_json(payload)?.await.map_err(|e| anyhow!(e))
+    }
+}
+
+impl Sink for GenericWebhook {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let payload = WebhookPayload::new(event, self.lang);
+            let mut response = self.post(&payload).await?;
+
+            for retry in 1..=MAX_RETRIES {
+                if !response.status().is_server_error() {
+                    break;
+                }
+                warn!(status = %response.status(), retry, "webhook failed, retrying");
+                async_std::task::sleep(RETRY_DELAY * retry).await;
+                response = self.post(&payload).await?;
+            }
+
+            if !response.status().is_success() {
+                let text = response.body_string().await.unwrap_or_default();
+                return Err(anyhow!("webhook returned {}: {}", response.status(), text));
+            }
+
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
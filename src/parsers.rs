@@ -1,16 +1,36 @@
 use anyhow::Result;
 use regex::Regex;
-use std::iter::Iterator;
+use std::iter::{Enumerate, Fuse, Iterator, Peekable};
+use tracing::warn;
 
 use super::models::{Exterior, Item};
 
-const STATTRAK: &str = "StatTrak ";
+// The site writes StatTrak both with and without the trademark sign.
+const STATTRAK_PREFIXES: &[&str] = &["StatTrak™ ", "StatTrak "];
+const SOUVENIR: &str = "Souvenir ";
+// Matched against the price line after `normalize_width`.
+const PRICE_PATTERN: &str = r"販売価格: *([0-9,]+)円 *";
 
+// Phases of Doppler and Gamma Doppler skins.
+const PHASES: &[&str] = &[
+    "Phase 1",
+    "Phase 2",
+    "Phase 3",
+    "Phase 4",
+    "Ruby",
+    "Sapphire",
+    "Black Pearl",
+    "Emerald",
+];
+
+#[derive(Debug)]
 pub struct ItemSection {
     // If item has already sold, then item may be None.
     pub item: Option<Item>,
     pub order_id: i32,
-    pub price: i32,
+    pub price: i64,
+    // Picture of the skin, filled in by the scraper when it can tell which section it belongs to.
+    pub image_url: Option<String>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -19,74 +39,190 @@ pub enum ParseError {
     InvalidItemFormat(String),
     #[error(transparent)]
     InvalidNumber(#[from] std::num::ParseIntError),
+    #[error(transparent)]
+    InvalidFloat(#[from] std::num::ParseFloatError),
     #[error("invalid exterior (expected `FN`, `MW`, `FT`, `WW` or `BS` found `{0}`)")]
     InvalidExterior(String),
+    #[error("invalid price format (expected `販売価格: 1,234円`, found `{0}`)")]
+    InvalidPriceFormat(String),
+    #[error("item name is too long (expected at most {max} characters, found {len})")]
+    NameTooLong { len: usize, max: usize },
+    #[error("incomplete item section ({0})")]
+    IncompleteSection(&'static str),
+}
+
+// A section which could not be parsed, and roughly where it was.
+#[derive(thiserror::Error, Debug)]
+#[error("line {line}: {source}")]
+pub struct SectionError {
+    // Index of the text line the section starts at, counted from the start of the listing.
+    pub line: usize,
+    #[source]
+    pub source: ParseError,
+}
+
+pub struct ParserConfig {
+    // Names longer than this are rejected, or truncated if `truncate_long_names` is set.
+    // Defaults to the length of the `name` column.
+    pub max_name_len: usize,
+    pub truncate_long_names: bool,
+    // The line each section starts with, `★` on the site.
+    pub section_delimiter: String,
+    // Whether a blank line separates the name line from the price line, which is then skipped.
+    pub blank_line_before_price: bool,
+    // Instead of expecting the price at a fixed line, takes the first line after the name
+    // which looks like a price, as long as no other section starts before it.
+    pub scan_for_price: bool,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig {
+            max_name_len: 36,
+            truncate_long_names: false,
+            section_delimiter: "★".to_owned(),
+            blank_line_before_price: true,
+            scan_for_price: false,
+        }
+    }
 }
 
 #[inline]
-fn warn_corrupted_section<I: std::fmt::Display>(why: I) {
-    eprintln!("Warn: found corrupted item section, {}", why);
+fn warn_corrupted_section<I: std::fmt::Display>(why: I, line: &str) {
+    warn!(%why, line, "found corrupted item section");
+
+    #[cfg(feature = "metrics")]
+    super::metrics::CORRUPTED_SECTIONS.inc();
 }
 
-// Consumes lines iterator
-pub fn parse_items<'a>(mut lines: scraper::element_ref::Text) -> Vec<ItemSection> {
-    let mut items = vec![];
+// Walks the text lines of the listing and yields each item section as soon as it has been
+// read, so only the section at hand is ever held. A corrupted section is logged and yielded
+// as an error, with the line it starts at, and the walk goes on with the next one.
+pub struct ItemSectionIter<'a, 'c, I: Iterator<Item = &'a str>> {
+    // `Text` starts over once exhausted, which a section cut off at the end would loop on.
+    lines: Peekable<Enumerate<Fuse<I>>>,
+    config: &'c ParserConfig,
+}
 
-    while let Some(t) = lines.next() {
-        if t.trim() == "★" {
-            // Found item section
-            // then parse it!
-
-            // find item name line
-            let item_name_line = match lines.next() {
-                Some(line) => line,
-                None => {
-                    warn_corrupted_section("no item name line found.");
-                    continue;
-                }
-            };
+impl<'a, 'c, I: Iterator<Item = &'a str>> ItemSectionIter<'a, 'c, I> {
+    pub fn new(lines: impl IntoIterator<IntoIter = I>, config: &'c ParserConfig) -> Self {
+        ItemSectionIter {
+            lines: lines.into_iter().fuse().enumerate().peekable(),
+            config,
+        }
+    }
 
-            // Discard blank line.
-            if lines.next().is_none() {
-                warn_corrupted_section("no blank line found.");
-                continue;
+    // The section after the delimiter, whose name line is the next one.
+    fn section(&mut self, delimiter_line: &'a str) -> Result<ItemSection, ParseError> {
+        let config = self.config;
+        let lines = &mut self.lines;
+        let delimiter = config.section_delimiter.trim();
+        let corrupted = |source: ParseError, text: &str| {
+            warn_corrupted_section(&source, text);
+            source
+        };
+
+        // find item name line
+        let item_name_line = if config.scan_for_price {
+            // Extra blank lines are skipped, but not a line starting the next section.
+            while lines.next_if(|(_, l)| l.trim().is_empty()).is_some() {}
+            lines.next_if(|(_, l)| l.trim() != delimiter)
+        } else {
+            lines.next()
+        };
+        let item_name_line = match item_name_line {
+            Some((_, line)) => line,
+            None => {
+                return Err(corrupted(
+                    ParseError::IncompleteSection("no item name line found"),
+                    delimiter_line,
+                ))
             }
+        };
 
-            // find price line
-            let price_line = match lines.next() {
-                Some(line) => line,
-                None => {
-                    warn_corrupted_section("no price line found.");
-                    continue;
+        // find price line
+        let price_line = if config.scan_for_price {
+            loop {
+                match lines.next_if(|(_, l)| l.trim() != delimiter) {
+                    Some((_, l)) if is_price_line(l) => break Some(l),
+                    Some(_) => continue,
+                    None => break None,
                 }
-            };
+            }
+        } else {
+            // Discard blank line.
+            if config.blank_line_before_price && lines.next().is_none() {
+                return Err(corrupted(
+                    ParseError::IncompleteSection("no blank line found"),
+                    item_name_line,
+                ));
+            }
+            lines.next().map(|(_, line)| line)
+        };
+        let price_line = match price_line {
+            Some(line) => line,
+            None => {
+                return Err(corrupted(
+                    ParseError::IncompleteSection("no price line found"),
+                    item_name_line,
+                ))
+            }
+        };
 
-            let item = match parse_item_section(item_name_line, price_line) {
-                Ok(item) => item,
-                Err(e) => {
-                    warn_corrupted_section(e);
-                    continue;
-                }
-            };
+        parse_item_section(item_name_line, price_line, config)
+            .map_err(|e| corrupted(e, item_name_line))
+    }
+}
+
+impl<'a, 'c, I: Iterator<Item = &'a str>> Iterator for ItemSectionIter<'a, 'c, I> {
+    type Item = Result<ItemSection, SectionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let delimiter = self.config.section_delimiter.trim();
+        let (line, t) = self.lines.find(|(_, t)| t.trim() == delimiter)?;
 
-            items.push(item);
+        Some(
+            self.section(t)
+                .map_err(|source| SectionError { line, source }),
+        )
+    }
+}
+
+// Consumes lines iterator, collecting what `ItemSectionIter` yields.
+// Corrupted sections are logged and skipped, and returned alongside the parsed ones.
+pub fn parse_items(
+    lines: scraper::element_ref::Text,
+    config: &ParserConfig,
+) -> (Vec<ItemSection>, Vec<SectionError>) {
+    let mut items = vec![];
+    let mut errors = vec![];
+    for section in ItemSectionIter::new(lines, config) {
+        match section {
+            Ok(item) => items.push(item),
+            Err(e) => errors.push(e),
         }
     }
 
-    items
+    (items, errors)
 }
 
 pub fn parse_item_section<'a>(
     item_name_line: &'a str,
     price_line: &'a str,
+    config: &ParserConfig,
 ) -> Result<ItemSection, ParseError> {
     let mut name: Option<String> = None;
     let mut kind: Option<String> = None;
     let mut exterior: Option<Exterior> = None;
+    let mut phase: Option<String> = None;
+    let mut float_value: Option<f32> = None;
     let order_id: i32;
 
+    // The nametag is taken out first, so the rest of the line reads like any other item.
+    let (line, nametag) = split_nametag(&normalize_name(item_name_line));
+
     // Parse for item name, skin, order number.
-    let v = item_name_line.split(" | ").collect::<Vec<&str>>();
+    let v = line.split(" | ").collect::<Vec<&str>>();
     match v.len() {
         1 => {
             // Vanilla Item or Sold
@@ -94,7 +230,7 @@ pub fn parse_item_section<'a>(
             if let Some(sold_caps) = sold_matcher.captures(v[0]) {
                 order_id = sold_caps[1].parse()?;
             } else {
-                let vanilla_matcher = Regex::new(r"([A-Za-z ]+) \(Vanilla\) #(\d+)").unwrap();
+                let vanilla_matcher = Regex::new(r"([^()#|]+?) \(Vanilla\) #(\d+)").unwrap();
                 let caps = vanilla_matcher
                     .captures(v[0])
                     .ok_or_else(|| ParseError::InvalidItemFormat(item_name_line.to_owned()))?;
@@ -103,13 +239,34 @@ pub fn parse_item_section<'a>(
             }
         }
         2 => {
-            // Normal item
-            let item_matcher = Regex::new(r"([A-Za-z ]+) \(([-A-Za-z ]+)\) #(\d+)").unwrap();
+            // Normal item, optionally with a float like `Redline (Field-Tested, 0.2341) #id`,
+            // and optionally followed by a phase like `Doppler (Factory New) - Phase 2 #id`.
+            // The kind may have parentheses of its own, as in `龍王 (Dragon King) (Field-Tested)`.
+            let item_matcher = Regex::new(
+                r"((?:[^()#]|\([^()]*\))+?) \(([-A-Za-z ]+)(?:, *([0-9.]+))?\)(?: - ([A-Za-z0-9 ]+))? #(\d+)",
+            )
+            .unwrap();
             let caps = item_matcher
                 .captures(v[1])
                 .ok_or_else(|| ParseError::InvalidItemFormat(item_name_line.to_owned()))?;
             name = Some(v[0].to_owned());
-            kind = Some(caps[1].to_owned());
+
+            let (k, p) = split_phase(caps[1].trim());
+            kind = Some(k.to_owned());
+            float_value = match caps.get(3) {
+                Some(float) => Some(float.as_str().parse()?),
+                None => None,
+            };
+            phase = match (caps.get(4), p) {
+                (Some(suffix), _) => {
+                    let suffix = suffix.as_str().trim();
+                    if !PHASES.contains(&suffix) {
+                        return Err(ParseError::InvalidItemFormat(item_name_line.to_owned()));
+                    }
+                    Some(suffix.to_owned())
+                }
+                (None, p) => p.map(str::to_owned),
+            };
 
             use std::str::FromStr;
             let exterior_str = &caps[2];
@@ -119,7 +276,7 @@ pub fn parse_item_section<'a>(
                     return Err(ParseError::InvalidExterior(exterior_str.to_owned()));
                 }
             };
-            order_id = caps[3].parse()?;
+            order_id = caps[5].parse()?;
         }
         _ => {
             return Err(ParseError::InvalidItemFormat(item_name_line.to_owned()));
@@ -130,24 +287,32 @@ pub fn parse_item_section<'a>(
     name = name.map(trim_and_own);
     kind = kind.map(trim_and_own);
 
-    // Check if item is stattrak, and remove StatTrak from name.
-    let is_stattrak = {
-        if let Some(n) = &mut name {
-            if n.starts_with(STATTRAK) {
-                n.drain(..STATTRAK.len());
-                true
-            } else {
-                false
+    // Check if item is stattrak or souvenir, and remove the prefix from name.
+    let (is_stattrak, is_souvenir) = match &mut name {
+        Some(n) => strip_prefixes(n),
+        None => (false, false),
+    };
+
+    if let Some(n) = &mut name {
+        let len = n.chars().count();
+        if len > config.max_name_len {
+            if !config.truncate_long_names {
+                return Err(ParseError::NameTooLong {
+                    len,
+                    max: config.max_name_len,
+                });
             }
-        } else {
-            false
+            *n = n.chars().take(config.max_name_len).collect();
         }
-    };
+    }
 
     // Parse price
     let price = {
-        let price_matcher = Regex::new(r"販売価格: ([0-9,]+)円 *").unwrap();
-        let caps = price_matcher.captures(price_line).unwrap();
+        let price_matcher = Regex::new(PRICE_PATTERN).unwrap();
+        let normalized = normalize_width(price_line);
+        let caps = price_matcher
+            .captures(&normalized)
+            .ok_or_else(|| ParseError::InvalidPriceFormat(price_line.to_owned()))?;
 
         (caps[1]).replace(',', "").parse()?
     };
@@ -162,15 +327,105 @@ pub fn parse_item_section<'a>(
                 price,
                 has_sold: false,
                 is_stattrak,
+                avg_price_7d: None,
+                phase,
+                is_souvenir,
+                float_value,
+                nametag,
             }),
             order_id,
             price,
+            image_url: None,
         })
     } else {
         Ok(ItemSection {
             item: None,
             order_id,
             price,
+            image_url: None,
+        })
+    }
+}
+
+fn is_price_line(line: &str) -> bool {
+    Regex::new(PRICE_PATTERN)
+        .unwrap()
+        .is_match(&normalize_width(line))
+}
+
+// Turns full-width digits, commas, colons and spaces into their ASCII forms, e.g. `１，２３４円`
+// into `1,234円`, leaving every other character as is.
+pub fn normalize_width(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '０'..='９' => char::from(b'0' + (c as u32 - '０' as u32) as u8),
+            '，' => ',',
+            '：' => ':',
+            '\u{3000}' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+// Collapses every run of whitespace, including non-breaking and full-width spaces, into a single
+// space, and turns the other dashes into `-`, so a name written a little differently by the site
+// is still stored the same, e.g. `AK-47  |  Redline (Field–Tested)` as `AK-47 | Redline (Field-Tested)`.
+pub fn normalize_name(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .map(|c| match c {
+            '\u{2010}'..='\u{2015}' | '\u{2212}' | '\u{ff0d}' => '-',
+            c => c,
         })
+        .collect()
+}
+
+// Splits a nametag given by the owner, e.g. `"My Rifle"` in `AK-47 | Redline "My Rifle" (Field-Tested) #1`,
+// off the name line. Both ASCII and curly quotes are accepted.
+fn split_nametag(line: &str) -> (String, Option<String>) {
+    let nametag_matcher = Regex::new(r#" *["“]([^"”]+)["”]"#).unwrap();
+    match nametag_matcher.captures(line) {
+        Some(caps) => (
+            nametag_matcher.replace(line, "").into_owned(),
+            Some(caps[1].trim().to_owned()),
+        ),
+        None => (line.to_owned(), None),
+    }
+}
+
+// Removes the StatTrak and Souvenir prefixes from the name, returning which of them it had.
+pub fn strip_prefixes(name: &mut String) -> (bool, bool) {
+    let mut is_stattrak = false;
+    if let Some(prefix) = STATTRAK_PREFIXES.iter().find(|p| name.starts_with(*p)) {
+        name.drain(..prefix.len());
+        is_stattrak = true;
+    }
+
+    let is_souvenir = name.starts_with(SOUVENIR);
+    if is_souvenir {
+        name.drain(..SOUVENIR.len());
     }
+
+    (is_stattrak, is_souvenir)
+}
+
+// Splits a phase written into the kind, e.g. `Doppler Phase 2`, off a Doppler skin.
+// Other skins are returned unchanged, so a kind which only happens to end with `Ruby` is kept whole.
+fn split_phase(kind: &str) -> (&str, Option<&str>) {
+    if !kind.contains("Doppler") {
+        return (kind, None);
+    }
+
+    for phase in PHASES {
+        if let Some(rest) = kind.strip_suffix(phase) {
+            let rest = rest.trim_end().trim_end_matches('-').trim_end();
+            if !rest.is_empty() {
+                return (rest, Some(phase));
+            }
+        }
+    }
+
+    (kind, None)
 }
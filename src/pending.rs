@@ -0,0 +1,134 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use tracing::warn;
+
+use super::models::{NewPendingNotification, PendingNotification};
+use super::notifier::NotificationEvent;
+use super::schema::pending_notifications::dsl as table;
+
+// The notifications of a run are written here in the transaction which commits the changes they
+// are about, and deleted once every sink has sent them. Each row keeps which sinks sent it, so a
+// later run only sends it through the others. A run killed in between leaves them to the next one,
+// so each sink sends each notification at least once, and may send it twice if the run was killed
+// before its send was recorded.
+
+// A notification which failed this many times is given up on, so one a sink keeps rejecting
+// doesn't stay queued forever.
+pub const MAX_ATTEMPTS: i32 = 5;
+
+// A notification read back from `pending_notifications`.
+#[derive(Debug)]
+pub struct Pending {
+    pub id: i32,
+    // The bits of the sinks which sent it already, see `notifier::SinkKind`.
+    pub sent_to: i32,
+    pub created_at: NaiveDateTime,
+    pub event: NotificationEvent,
+}
+
+impl Pending {
+    // The `Outgoing::key` of the row. The id alone could come back after the table is recreated,
+    // so the time the row was written goes with it.
+    pub fn key(&self) -> String {
+        format!("{}-{}", self.id, self.created_at.format("%Y%m%d%H%M%S"))
+    }
+}
+
+pub fn encode(event: &NotificationEvent) -> serde_json::Result<String> {
+    serde_json::to_string(event)
+}
+
+pub fn decode(json: &str) -> serde_json::Result<NotificationEvent> {
+    serde_json::from_str(json)
+}
+
+pub fn enqueue(conn: &MysqlConnection, events: &[NotificationEvent]) -> QueryResult<()> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now().naive_utc();
+    let rows = events
+        .iter()
+        .map(|event| {
+            Ok(NewPendingNotification {
+                event: encode(event).map_err(|e| DieselError::SerializationError(Box::new(e)))?,
+                created_at: now,
+            })
+        })
+        .collect::<QueryResult<Vec<_>>>()?;
+    diesel::insert_into(table::pending_notifications)
+        .values(rows)
+        .execute(conn)?;
+    Ok(())
+}
+
+// Every pending notification, oldest first. A row which no longer decodes, e.g. one written by an
+// older version, is dropped with a warning.
+pub fn load(conn: &MysqlConnection) -> QueryResult<Vec<Pending>> {
+    let rows = table::pending_notifications
+        .order(table::id.asc())
+        .load::<PendingNotification>(conn)?;
+
+    let mut pending = vec![];
+    let mut broken = vec![];
+    for row in rows {
+        match decode(&row.event) {
+            Ok(event) => pending.push(Pending {
+                id: row.id,
+                sent_to: row.sent_to,
+                created_at: row.created_at,
+                event,
+            }),
+            Err(error) => {
+                warn!(id = row.id, %error, "dropped a pending notification which can't be read");
+                broken.push(row.id);
+            }
+        }
+    }
+    delete(conn, &broken)?;
+
+    Ok(pending)
+}
+
+pub fn delete(conn: &MysqlConnection, ids: &[i32]) -> QueryResult<()> {
+    if !ids.is_empty() {
+        diesel::delete(table::pending_notifications.filter(table::id.eq_any(ids))).execute(conn)?;
+    }
+    Ok(())
+}
+
+// Counts a failed attempt of each of the notifications, given by id with the sinks which have sent
+// it by now, and gives up on the ones which failed `MAX_ATTEMPTS` times.
+pub fn record_failures(conn: &MysqlConnection, failed: &[(i32, i32)]) -> QueryResult<()> {
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    for &(id, sent_to) in failed {
+        diesel::update(table::pending_notifications.find(id))
+            .set((
+                table::sent_to.eq(sent_to),
+                table::attempts.eq(table::attempts + 1),
+            ))
+            .execute(conn)?;
+    }
+    let ids = failed.iter().map(|&(id, _)| id).collect::<Vec<_>>();
+    let given_up = diesel::delete(
+        table::pending_notifications
+            .filter(table::id.eq_any(&ids))
+            .filter(table::attempts.ge(MAX_ATTEMPTS)),
+    )
+    .execute(conn)?;
+    if given_up > 0 {
+        warn!(
+            notifications = given_up,
+            attempts = MAX_ATTEMPTS,
+            "gave up on notifications which kept failing"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,568 @@
+use tracing::{error, info, warn};
+
+#[cfg(feature = "metrics")]
+use super::metrics;
+#[cfg(feature = "metrics")]
+use super::Error;
+
+use super::config::{Config, DryRunMode, NotifyMode, UnknownSoldAction};
+use super::db::{self, Pool};
+use super::diff::Diff;
+use super::digest::DigestBuilder;
+use super::fcm::Client;
+use super::models::Watch;
+use super::notifier::{
+    self, DiscordWebhook, EmailSink, FcmSink, GenericWebhook, LineNotify, MatrixSink,
+    NotificationEvent, NtfySink, Outgoing, RateLimited, Sink, SinkKind, SlackWebhook, TelegramBot,
+};
+use super::summary::RunSummary;
+use super::{alerts, diff, events, history, layout, messages, pending, runs, scrape, tokens};
+
+// One scrape of the site: diffs it against the database, records the changes and sends
+// the notifications. `main` calls this once, or once per poll with `--watch`.
+// `fcm` is the client built from `config.fcm`, if any, and `watches` the price alerts to check.
+pub async fn run(
+    config: &Config,
+    pool: &Pool,
+    fcm: Option<&Client>,
+    watches: &[Watch],
+) -> anyhow::Result<()> {
+    if config.announce_current {
+        return announce_current(config, pool, fcm).await;
+    }
+    let run_start = std::time::Instant::now();
+
+    let found_items = scrape::fetch_and_parse(&config.scrape).await;
+    #[cfg(feature = "metrics")]
+    match &found_items {
+        Ok(page) => metrics::ITEMS_PARSED.inc_by(page.items.len() as u64),
+        Err(e) => {
+            let stage = match e.downcast_ref() {
+                Some(Error::ParseFailed)
+                | Some(Error::SelectorNoMatch(_))
+                | Some(Error::TooManyCorruptedSections { .. }) => "parse",
+                Some(Error::FetchTimedOut { .. }) => "timeout",
+                _ => "fetch",
+            };
+            metrics::FAILURES.with_label_values(&[stage]).inc();
+        }
+    }
+    let scrape::Page {
+        items: found_items,
+        errors: section_errors,
+        layout_hash,
+    } = match found_items {
+        Ok(found) => found,
+        Err(e) => {
+            if config.dry_run.is_none() {
+                // The run fails with the scrape's error either way, so a failed send is only logged.
+                // What a previous run left unsent still goes out, as a run which scrapes sends it
+                // with its own notifications.
+                match flush_pending(config, pool, fcm).await {
+                    Ok((attempted, sent)) if attempted > 0 => {
+                        info!(attempted, sent, "sent the notifications left by a previous run")
+                    }
+                    Ok(_) => {}
+                    Err(flush_error) => {
+                        warn!(error = ?flush_error, "failed to send the pending notifications")
+                    }
+                }
+                if config.alert_on_failure {
+                    let alert = NotificationEvent::ScrapeFailed {
+                        error: e.to_string(),
+                    };
+                    if let Err(alert_error) = send(config, pool, fcm, &[Outgoing::new(alert)]).await {
+                        warn!(error = ?alert_error, "failed to send the failure alert");
+                    }
+                }
+            }
+            return Err(e);
+        }
+    };
+    let items_scraped = found_items.len();
+
+    let conn = db::get(pool, &config.db_retry)?;
+
+    let db_items = db::load_db_items(&conn)?;
+    let repriced = diff::repriced(&found_items, &db_items);
+    // The trend looks further back than the average, over the last `TREND_WINDOW` changes.
+    let keep_last = config.diff.trend.as_ref().map_or(0, |trend| trend.window);
+    let history =
+        history::load_for_items(&conn, &repriced, history::average_since(), keep_last)?;
+    let previous_scrape = layout::load(&conn)?;
+    let redesigned = layout::looks_redesigned(
+        previous_scrape.as_ref(),
+        &layout_hash,
+        items_scraped,
+        config.diff.min_found_ratio,
+    );
+    let mut diff = diff::diff(found_items, db_items, &history, &config.diff);
+    // The last normal run stays the one to compare with until the items are found again.
+    let layout_alert = match &previous_scrape {
+        Some(previous) if redesigned => {
+            let skipped = diff.skip_deletions();
+            error!(
+                found = items_scraped,
+                previous = previous.item_count,
+                skipped,
+                "the layout of the site may have changed, skipped deleting the missing items"
+            );
+            Some(NotificationEvent::LayoutChanged {
+                found: items_scraped,
+                previous: previous.item_count.max(0) as usize,
+            })
+        }
+        _ => {
+            if config.dry_run.is_none() {
+                layout::save(&conn, &layout_hash, items_scraped)?;
+            }
+            None
+        }
+    };
+    let mut summary = RunSummary::new(items_scraped, &section_errors, &diff);
+    summary.dry_run = config.dry_run.is_some();
+    let mut notifications = layout_alert.into_iter().collect::<Vec<_>>();
+
+    notifications.extend(plan_notifications(config, &diff, watches));
+
+    // The changes are committed with their notifications before anything is sent, so a rolled back
+    // change is never notified, and a committed one is even if the run stops before sending it.
+    match config.dry_run {
+        None => {
+            db::apply_and_enqueue(&conn, &diff.changes, &notifications)?;
+            // Each repriced item just got a price, so what it no longer needs goes.
+            history::prune(&conn, &repriced, history::average_since(), keep_last)?;
+        }
+        Some(mode) => {
+            for change in &diff.changes {
+                info!(?change, "dry run: would apply");
+            }
+            if mode == DryRunMode::Rollback {
+                db::apply_and_rollback(&conn, &diff.changes)?;
+                info!(
+                    changes = diff.changes.len(),
+                    "dry run: applied and rolled back"
+                );
+            }
+        }
+    }
+
+    let elapsed = run_start.elapsed();
+    if let Some(threshold) = config.slow_run_threshold {
+        if elapsed > threshold {
+            let baseline = runs::baseline(&conn)?;
+            warn!(
+                ?elapsed,
+                ?threshold,
+                ?baseline,
+                "run took longer than the threshold"
+            );
+
+            if config.notify_on_slow_run {
+                let slow_run = NotificationEvent::SlowRun { elapsed, baseline };
+                if config.dry_run.is_none() {
+                    pending::enqueue(&conn, std::slice::from_ref(&slow_run))?;
+                }
+                notifications.push(slow_run);
+            }
+        }
+    }
+    if config.dry_run.is_none() {
+        runs::record_run(&conn, elapsed)?;
+    }
+    // Give the connection back while notifications are sent.
+    drop(conn);
+
+    // With `--emit-events`, stdout only carries the event stream and every log goes to stderr.
+    if config.emit_events {
+        events::write_ndjson(std::io::stdout().lock(), &diff.events)?;
+    }
+
+    if config.dry_run.is_some() {
+        for event in &notifications {
+            let notification =
+                messages::notification(event, config.lang, config.use_loc_keys).to_json()?;
+            info!(%notification, "dry run: would send");
+        }
+    } else {
+        // Sends what a previous run left unsent too, oldest first.
+        let (attempted, sent) = flush_pending(config, pool, fcm).await?;
+        summary.notifications_attempted = attempted;
+        summary.notifications_sent = sent;
+    }
+
+    if let Some(output) = &config.summary {
+        summary.duration_ms = run_start.elapsed().as_millis();
+        summary.write(output)?;
+    }
+
+    Ok(())
+}
+
+// The notifications of a diff besides the layout alert: the unknown sold items, the changes the
+// filter lets through, each alone or as one digest by `NOTIFY_MODE`, then the price alerts of
+// `watches`.
+pub fn plan_notifications(
+    config: &Config,
+    diff: &Diff,
+    watches: &[Watch],
+) -> Vec<NotificationEvent> {
+    let mut notifications = vec![];
+
+    for section in &diff.unknown_sold {
+        match config.on_unknown_sold {
+            UnknownSoldAction::Ignore => {}
+            UnknownSoldAction::Log => info!(
+                order_id = section.order_id,
+                price = section.price,
+                "found sold item which has never been tracked"
+            ),
+            UnknownSoldAction::Notify => {
+                notifications.push(NotificationEvent::UnknownSold {
+                    order_id: section.order_id,
+                    price: section.price,
+                });
+            }
+        }
+    }
+
+    let notable = diff
+        .events
+        .iter()
+        .filter(|event| config.filter.should_notify(event));
+    match config.notify_mode {
+        NotifyMode::PerEvent => {
+            notifications.extend(
+                notable
+                    .filter(|event| {
+                        !(config.filter.min_price_delta_per_event && config.filter.is_minor(event))
+                    })
+                    .cloned()
+                    .map(NotificationEvent::Item),
+            );
+        }
+        NotifyMode::Digest => {
+            let mut digest = DigestBuilder::with_min_price_delta(config.filter.min_price_delta_yen);
+            for event in notable {
+                digest.push(event);
+            }
+            if digest.total() > 0 {
+                notifications.push(NotificationEvent::Digest(digest));
+            }
+        }
+    }
+
+    // Price alerts are sent on their own, whatever the filter and the notify mode.
+    notifications.extend(alerts::reached(watches, &diff.events));
+
+    notifications
+}
+
+// `--announce-current`: notifies every item in the database which is still listed, as each one
+// or as a digest by `NOTIFY_MODE`, without scraping the site or writing any item.
+async fn announce_current(
+    config: &Config,
+    pool: &Pool,
+    fcm: Option<&Client>,
+) -> anyhow::Result<()> {
+    let mut items = db::load_db_items(&*db::get(pool, &config.db_retry)?)?;
+    items.retain(|item| !item.has_sold);
+    items.sort_by_key(|item| item.order_id);
+    info!(items = items.len(), "announcing the current listing");
+
+    let notifications = match config.notify_mode {
+        NotifyMode::PerEvent => items
+            .into_iter()
+            .map(|item| NotificationEvent::Listing { item })
+            .collect::<Vec<_>>(),
+        NotifyMode::Digest => {
+            let mut digest = DigestBuilder::new();
+            for item in &items {
+                digest.push_listed(item);
+            }
+            if digest.total() > 0 {
+                vec![NotificationEvent::Digest(digest)]
+            } else {
+                vec![]
+            }
+        }
+    };
+
+    if config.dry_run.is_some() {
+        for event in &notifications {
+            let notification =
+                messages::notification(event, config.lang, config.use_loc_keys).to_json()?;
+            info!(%notification, "dry run: would send");
+        }
+    } else if !notifications.is_empty() {
+        let outgoing = notifications
+            .into_iter()
+            .map(Outgoing::new)
+            .collect::<Vec<_>>();
+        send(config, pool, fcm, &outgoing).await?;
+    }
+
+    Ok(())
+}
+
+// Sends every notification in `pending_notifications` through the sinks which haven't sent it,
+// and deletes the ones every sink has sent by now, counting a failed attempt of the others.
+// Returns the same counts as `send`.
+async fn flush_pending(
+    config: &Config,
+    pool: &Pool,
+    fcm: Option<&Client>,
+) -> anyhow::Result<(usize, usize)> {
+    let mut ids = vec![];
+    let mut outgoing = vec![];
+    for row in pending::load(&*db::get(pool, &config.db_retry)?)? {
+        ids.push(row.id);
+        outgoing.push(Outgoing {
+            key: Some(row.key()),
+            sent_to: row.sent_to,
+            event: row.event,
+        });
+    }
+    if outgoing.is_empty() {
+        return Ok((0, 0));
+    }
+
+    let sent = send(config, pool, fcm, &outgoing).await?;
+    let mut delivered = vec![];
+    let mut failed = vec![];
+    for ((id, sent_to), done) in ids.into_iter().zip(sent.sent_to).zip(sent.delivered) {
+        if done {
+            delivered.push(id);
+        } else {
+            failed.push((id, sent_to));
+        }
+    }
+    let conn = db::get(pool, &config.db_retry)?;
+    pending::delete(&conn, &delivered)?;
+    pending::record_failures(&conn, &failed)?;
+
+    Ok((sent.attempted, sent.succeeded))
+}
+
+// What became of the notifications given to `send` or `deliver`.
+#[derive(Debug)]
+pub struct Sent {
+    // How many sends were attempted, one for each notification and sink which hadn't sent it, and
+    // how many succeeded.
+    pub attempted: usize,
+    pub succeeded: usize,
+    // The bits of the sinks which have sent each notification by now, in the same order.
+    pub sent_to: Vec<i32>,
+    // Whether every sink has sent each notification by now.
+    pub delivered: Vec<bool>,
+}
+
+// Sends every notification through every configured sink which hasn't sent it, then prunes the
+// tokens FCM reported as dead.
+async fn send(
+    config: &Config,
+    pool: &Pool,
+    fcm: Option<&Client>,
+    outgoing: &[Outgoing],
+) -> anyhow::Result<Sent> {
+    if config.silence.is_active(chrono::Utc::now()) {
+        info!(
+            suppressed = outgoing.len(),
+            "notifications are silenced, suppressed them"
+        );
+        // Suppressed for good, not to be sent once the silence is over.
+        return Ok(Sent {
+            attempted: 0,
+            succeeded: 0,
+            sent_to: outgoing.iter().map(|outgoing| outgoing.sent_to).collect(),
+            delivered: vec![true; outgoing.len()],
+        });
+    }
+
+    let tokens = tokens::load(&*db::get(pool, &config.db_retry)?)?;
+    let env_ids = config
+        .fcm
+        .as_ref()
+        .map_or(&[][..], |fcm| &fcm.registration_ids);
+    let recipients = tokens::recipients(env_ids, &tokens);
+    let fcm_sink = fcm.map(|client| {
+        FcmSink::new(
+            client,
+            recipients.clone(),
+            config.lang,
+            config.use_loc_keys,
+            config.collapse_notifications,
+            config.fcm_send_data,
+            &config.fcm_high_priority,
+        )
+        .with_sounds(&config.sounds)
+    });
+
+    // Each sink with its kind and its `*_RATE_PER_SEC`.
+    let mut sinks: Vec<(SinkKind, &dyn Sink, Option<f64>)> = vec![];
+    if let Some(sink) = &fcm_sink {
+        sinks.push((SinkKind::Fcm, sink, config.rate_limits.fcm));
+    }
+    let discord = config
+        .discord_webhook_url
+        .as_ref()
+        .map(|url| DiscordWebhook::new(url, config.lang));
+    if let Some(sink) = &discord {
+        sinks.push((SinkKind::Discord, sink, config.rate_limits.discord));
+    }
+    let line = config
+        .line_notify_token
+        .as_ref()
+        .map(|token| LineNotify::new(token, config.lang));
+    if let Some(sink) = &line {
+        sinks.push((SinkKind::Line, sink, config.rate_limits.line));
+    }
+    let slack = config
+        .slack_webhook_url
+        .as_ref()
+        .map(|url| SlackWebhook::new(url, config.lang));
+    if let Some(sink) = &slack {
+        sinks.push((SinkKind::Slack, sink, config.rate_limits.slack));
+    }
+    let telegram = config
+        .telegram
+        .as_ref()
+        .map(|telegram| TelegramBot::new(&telegram.bot_token, &telegram.chat_id, config.lang));
+    if let Some(sink) = &telegram {
+        sinks.push((SinkKind::Telegram, sink, config.rate_limits.telegram));
+    }
+    let ntfy = config
+        .ntfy
+        .as_ref()
+        .map(|ntfy| NtfySink::new(&ntfy.server, &ntfy.topic, ntfy.token.clone(), config.lang));
+    if let Some(sink) = &ntfy {
+        sinks.push((SinkKind::Ntfy, sink, config.rate_limits.ntfy));
+    }
+    let matrix = config.matrix.as_ref().map(|matrix| {
+        MatrixSink::new(
+            &matrix.homeserver,
+            &matrix.room_id,
+            &matrix.access_token,
+            config.lang,
+        )
+    });
+    if let Some(sink) = &matrix {
+        sinks.push((SinkKind::Matrix, sink, config.rate_limits.matrix));
+    }
+    let email = config.email.as_ref().map(|email| {
+        EmailSink::new(
+            email.server.clone(),
+            email.credentials.clone(),
+            &email.from,
+            email.to.clone(),
+            config.lang,
+        )
+    });
+    if let Some(sink) = &email {
+        sinks.push((SinkKind::Email, sink, config.rate_limits.email));
+    }
+    let webhook = config
+        .webhook_url
+        .as_ref()
+        .map(|url| GenericWebhook::new(url, config.webhook_headers.clone(), config.lang));
+    if let Some(sink) = &webhook {
+        sinks.push((SinkKind::Webhook, sink, config.rate_limits.webhook));
+    }
+    let limited = sinks
+        .into_iter()
+        .map(|(kind, sink, rate)| (kind, RateLimited::new(sink, rate)))
+        .collect::<Vec<_>>();
+    let sinks = limited
+        .iter()
+        .map(|(kind, sink)| (*kind, sink as &dyn Sink))
+        .collect::<Vec<_>>();
+    let sent = deliver(config, &sinks, outgoing).await;
+
+    if let Some(sink) = &fcm_sink {
+        let token_changes = tokens::changes(&tokens, &recipients, &sink.take_responses());
+        if !token_changes.is_empty() {
+            info!(
+                tokens = token_changes.len(),
+                "updating dead or outdated tokens"
+            );
+            tokens::apply(&*db::get(pool, &config.db_retry)?, &token_changes)?;
+        }
+    }
+
+    Ok(sent)
+}
+
+// Sends every notification through each of `sinks` which hasn't sent it, `NOTIFY_CONCURRENCY` at a
+// time to each, logging the ones which failed.
+pub async fn deliver(
+    config: &Config,
+    sinks: &[(SinkKind, &dyn Sink)],
+    outgoing: &[Outgoing],
+) -> Sent {
+    // The indices of the notifications each sink has yet to send.
+    let unsent = sinks
+        .iter()
+        .map(|(kind, _)| {
+            (0..outgoing.len())
+                .filter(|&i| outgoing[i].sent_to & kind.bit() == 0)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let sends = sinks
+        .iter()
+        .zip(&unsent)
+        .map(|(&(_, sink), unsent)| {
+            let notifications = unsent
+                .iter()
+                .map(|&i| (&outgoing[i].event, outgoing[i].key.as_deref()))
+                .collect();
+            (sink, notifications)
+        })
+        .collect::<Vec<_>>();
+
+    info!(
+        notifications = outgoing.len(),
+        sinks = sinks.len(),
+        "sending notifications"
+    );
+    let results = notifier::dispatch_each(&sends, config.notify_concurrency).await;
+    let mut sent_to = outgoing
+        .iter()
+        .map(|outgoing| outgoing.sent_to)
+        .collect::<Vec<_>>();
+    let (mut attempted, mut succeeded) = (0, 0);
+    for ((&(kind, _), unsent), results) in sinks.iter().zip(&unsent).zip(results) {
+        for (&i, result) in unsent.iter().zip(results) {
+            let event = &outgoing[i].event;
+            attempted += 1;
+            match result {
+                Ok(()) => {
+                    succeeded += 1;
+                    sent_to[i] |= kind.bit();
+                    #[cfg(feature = "metrics")]
+                    metrics::NOTIFICATIONS_SENT
+                        .with_label_values(&[event.kind()])
+                        .inc();
+                }
+                Err(e) => warn!(
+                    error = ?e,
+                    sink = ?kind,
+                    kind = event.kind(),
+                    "failed to send a notification"
+                ),
+            }
+        }
+    }
+    info!("sent");
+
+    let every_sink = sinks.iter().fold(0, |bits, (kind, _)| bits | kind.bit());
+    Sent {
+        attempted,
+        succeeded,
+        delivered: sent_to
+            .iter()
+            .map(|&sent_to| sent_to & every_sink == every_sink)
+            .collect(),
+        sent_to,
+    }
+}
@@ -0,0 +1,37 @@
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+
+use super::models::NewRunHistory;
+use super::schema::run_history::dsl as table;
+
+// Number of previous runs averaged into the baseline.
+pub const BASELINE_RUNS: i64 = 5;
+
+pub fn record_run(conn: &MysqlConnection, duration: Duration) -> QueryResult<usize> {
+    diesel::insert_into(table::run_history)
+        .values(NewRunHistory {
+            duration_ms: i32::try_from(duration.as_millis()).unwrap_or(i32::MAX),
+            finished_at: Utc::now().naive_utc(),
+        })
+        .execute(conn)
+}
+
+// Average duration of the last `BASELINE_RUNS` runs, or None if this is the first one.
+pub fn baseline(conn: &MysqlConnection) -> QueryResult<Option<Duration>> {
+    let durations = table::run_history
+        .select(table::duration_ms)
+        .order(table::id.desc())
+        .limit(BASELINE_RUNS)
+        .load::<i32>(conn)?;
+
+    if durations.is_empty() {
+        return Ok(None);
+    }
+
+    let sum: u64 = durations.iter().map(|&ms| ms.max(0) as u64).sum();
+    Ok(Some(Duration::from_millis(sum / durations.len() as u64)))
+}
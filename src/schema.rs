@@ -7,8 +7,89 @@ table! {
         name -> Varchar,
         kind -> Nullable<Varchar>,
         exterior -> Nullable<ExteriorMapping>,
-        price -> Integer,
+        price -> Bigint,
         has_sold -> Bool,
         is_stattrak -> Bool,
+        avg_price_7d -> Nullable<Bigint>,
+        phase -> Nullable<Varchar>,
+        is_souvenir -> Bool,
+        float_value -> Nullable<Float>,
+        nametag -> Nullable<Varchar>,
     }
 }
+
+table! {
+    use diesel::sql_types::*;
+
+    pending_notifications (id) {
+        id -> Integer,
+        event -> Text,
+        attempts -> Integer,
+        sent_to -> Integer,
+        created_at -> Datetime,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    price_history (id) {
+        id -> Integer,
+        order_id -> Integer,
+        price -> Bigint,
+        recorded_at -> Datetime,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    run_history (id) {
+        id -> Integer,
+        duration_ms -> Integer,
+        finished_at -> Datetime,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    scrape_state (id) {
+        id -> Integer,
+        layout_hash -> Char,
+        item_count -> Integer,
+        updated_at -> Datetime,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    tokens (id) {
+        id -> Integer,
+        registration_id -> Varchar,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::ExteriorMapping;
+
+    watch (id) {
+        id -> Integer,
+        name -> Varchar,
+        kind -> Nullable<Varchar>,
+        exterior -> Nullable<ExteriorMapping>,
+        target_price -> Bigint,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(
+    item,
+    pending_notifications,
+    price_history,
+    run_history,
+    scrape_state,
+    tokens,
+    watch,
+);
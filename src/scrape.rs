@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use encoding_rs::{Encoding, UTF_8};
+use isahc::http::{header, Request, Uri};
+use scraper::{ElementRef, Html, Selector};
+use tracing::{info, instrument, warn};
+use url::Url;
+
+use super::http_client::{self, ProxyConfig};
+use super::layout;
+use super::parsers::{self, ItemSection, ParserConfig, SectionError};
+use super::Error;
+
+pub const DEFAULT_URL: &str = "http://steamrmt.com/skinbuy.html";
+pub const DEFAULT_SELECTOR: &str = "html > body > div.contents > div.inner > div.main > section";
+
+// Markers of the interstitial pages which Cloudflare and similar WAFs serve instead of the site.
+// Such a page would otherwise look like a listing with no items and delete everything.
+const CHALLENGE_MARKERS: &[&str] = &[
+    "<title>Just a moment...</title>",
+    "<title>Attention Required! | Cloudflare</title>",
+    "cf-browser-verification",
+    "/cdn-cgi/challenge-platform/",
+    "cf_chl_opt",
+];
+
+pub struct ScrapeConfig {
+    // `TARGET_URL`, comma-separated when the listing is split across pages
+    pub urls: Vec<String>,
+    // `TARGET_SELECTOR` or `SCRAPE_SELECTOR`, comma-separated and tried in order
+    pub selectors: Vec<String>,
+    // `USER_AGENT`
+    pub user_agent: String,
+    // `RETRY_ON_BODY_READ_FAILURE`
+    pub retry_on_body_read_failure: bool,
+    // `FETCH_TIMEOUT_SECS`, for each attempt at a page including its body
+    pub fetch_timeout: Duration,
+    pub proxy: ProxyConfig,
+    pub parser: ParserConfig,
+}
+
+// The item sections parsed from one page, or every page of the site, with the sections which
+// could not be parsed and the fingerprint of the layout they were found in.
+pub struct Page {
+    pub items: Vec<ItemSection>,
+    pub errors: Vec<SectionError>,
+    pub layout_hash: String,
+}
+
+// Fetches every page of the site concurrently and parses the item sections on them.
+// Every page has to succeed, since an item missing with its page would be taken as deleted.
+#[instrument(skip(config), fields(pages = config.urls.len()))]
+pub async fn fetch_and_parse(config: &ScrapeConfig) -> anyhow::Result<Page> {
+    let pages =
+        futures::future::join_all(config.urls.iter().map(|url| fetch_page(config, url))).await;
+
+    let mut items = vec![];
+    let mut errors = vec![];
+    let mut layout_hashes = vec![];
+    for page in pages {
+        let page = page?;
+        items.extend(page.items);
+        errors.extend(page.errors);
+        layout_hashes.push(page.layout_hash);
+    }
+    let items = dedup_by_order_id(items);
+
+    // When most sections fail, the layout has likely changed, and going on would delete every item.
+    if errors.len() > items.len() {
+        Err(Error::TooManyCorruptedSections {
+            corrupted: errors.len(),
+            parsed: items.len(),
+        })?
+    }
+    info!(
+        items = items.len(),
+        corrupted = errors.len(),
+        "successfully parsed"
+    );
+
+    Ok(Page {
+        items,
+        errors,
+        layout_hash: layout::combine(&layout_hashes),
+    })
+}
+
+async fn fetch_page(config: &ScrapeConfig, url: &str) -> anyhow::Result<Page> {
+    let body = match fetch_with_timeout(config, url).await {
+        Err(e)
+            if config.retry_on_body_read_failure
+                && matches!(e.downcast_ref(), Some(Error::BodyReadFailed { .. })) =>
+        {
+            warn!(error = %e, url, "retrying the fetch once");
+            fetch_with_timeout(config, url).await?
+        }
+        body => body?,
+    };
+
+    Ok(parse_page(&body, url, &config.selectors, &config.parser)?)
+}
+
+async fn fetch_with_timeout(config: &ScrapeConfig, url: &str) -> anyhow::Result<String> {
+    let proxy = config.proxy.for_url(url);
+    async_std::future::timeout(
+        config.fetch_timeout,
+        fetch_site(url, &config.user_agent, proxy),
+    )
+    .await
+    .map_err(|_| Error::FetchTimedOut {
+        url: url.to_owned(),
+        timeout: config.fetch_timeout,
+    })?
+}
+
+// Keeps a single section of each order id, so an item listed twice, on one page or across pages,
+// gets one change and one notification. The last section wins, unless it is only the sold
+// marker of an item already seen with its name, and it stays where the item was first seen.
+pub fn dedup_by_order_id(items: Vec<ItemSection>) -> Vec<ItemSection> {
+    let mut positions: HashMap<i32, usize> = HashMap::new();
+    let mut deduped: Vec<ItemSection> = vec![];
+
+    for item in items {
+        match positions.get(&item.order_id) {
+            Some(&i) => {
+                warn!(order_id = item.order_id, "found the same order id twice");
+                if item.item.is_some() || deduped[i].item.is_none() {
+                    deduped[i] = item;
+                }
+            }
+            None => {
+                positions.insert(item.order_id, deduped.len());
+                deduped.push(item);
+            }
+        }
+    }
+
+    deduped
+}
+
+// `parse_page`, without the layout.
+pub fn parse_document(
+    body: &str,
+    url: &str,
+    selectors: &[String],
+    config: &ParserConfig,
+) -> Result<(Vec<ItemSection>, Vec<SectionError>), Error> {
+    parse_page(body, url, selectors, config).map(|page| (page.items, page.errors))
+}
+
+// Tries each selector in order and parses the section matched by the first one that matches,
+// or fails with `SelectorNoMatch` listing every selector when none does.
+// `url` is where the document came from, used to resolve relative image links.
+pub fn parse_page(
+    body: &str,
+    url: &str,
+    selectors: &[String],
+    config: &ParserConfig,
+) -> Result<Page, Error> {
+    let dom = Html::parse_document(body);
+
+    for (i, selector) in selectors.iter().enumerate() {
+        let sel = match Selector::parse(selector) {
+            Ok(sel) => sel,
+            Err(_) => {
+                warn!(%selector, "skipped invalid selector");
+                continue;
+            }
+        };
+
+        if let Some(s) = dom.select(&sel).next() {
+            if i > 0 {
+                warn!(%selector, "primary selector matched nothing, using a fallback");
+            }
+            let (mut items, errors) = parsers::parse_items(s.text(), config);
+            attach_images(s, url, &mut items);
+            return Ok(Page {
+                items,
+                errors,
+                layout_hash: layout::fingerprint(s),
+            });
+        }
+    }
+
+    Err(Error::SelectorNoMatch(selectors.join(", ")))
+}
+
+// The sections are plain text, so an image can only be matched to its section by position.
+// Images are attached only when there is exactly one per parsed section.
+fn attach_images(section: ElementRef, url: &str, items: &mut [ItemSection]) {
+    let img = Selector::parse("img").unwrap();
+    let base = Url::parse(url).ok();
+    let images = section
+        .select(&img)
+        .filter_map(|img| img.value().attr("src"))
+        .filter_map(|src| match &base {
+            Some(base) => base.join(src).ok(),
+            None => Url::parse(src).ok(),
+        })
+        .collect::<Vec<_>>();
+
+    if images.len() != items.len() {
+        return;
+    }
+
+    for (item, image) in items.iter_mut().zip(images) {
+        item.image_url = Some(image.to_string());
+    }
+}
+
+async fn fetch_site(url: &str, user_agent: &str, proxy: Option<&Uri>) -> anyhow::Result<String> {
+    let start = std::time::Instant::now();
+    let client = http_client::build(proxy)?;
+    let request = Request::get(url)
+        .header("User-Agent", user_agent)
+        .body(())?;
+    let mut resp = client
+        .send_async(request)
+        .await
+        .map_err(|e| classify(e, url))?;
+
+    if resp
+        .headers()
+        .get("cf-mitigated")
+        .is_some_and(|value| value == "challenge")
+    {
+        Err(Error::ChallengeDetected(
+            "cf-mitigated: challenge".to_owned(),
+        ))?
+    }
+
+    let status = resp.status();
+    if status != 200 {
+        Err(Error::BadStatus(status.as_u16()))?
+    }
+    info!(url, %status, elapsed = ?start.elapsed(), "fetched site");
+
+    let content_type = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let body = decode_body(&read_body(resp.body_mut()).await?, content_type.as_deref());
+    if let Some(marker) = find_challenge_marker(&body) {
+        Err(Error::ChallengeDetected(marker.to_owned()))?
+    }
+
+    Ok(body)
+}
+
+// Tells a host which could not be resolved apart from other failures of the request.
+fn classify(e: isahc::Error, url: &str) -> Error {
+    match e {
+        isahc::Error::CouldntResolveHost => Error::DnsFailed(url.to_owned()),
+        _ => Error::FetchFailed(e.to_string()),
+    }
+}
+
+// How far into the page a `<meta charset>` is looked for, the same as browsers.
+const META_SNIFF_LEN: usize = 1024;
+
+// Decodes the page in the charset of its `Content-Type`, or else of its `<meta charset>`, and
+// as UTF-8 when neither names one, so the markers the parser looks for are never mojibake.
+// A byte order mark takes precedence over both. Malformed bytes are replaced with U+FFFD.
+pub fn decode_body(body: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(charset_param)
+        .or_else(|| meta_charset(&body[..body.len().min(META_SNIFF_LEN)]))
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+
+    let (text, used, had_errors) = encoding.decode(body);
+    if had_errors {
+        warn!(
+            encoding = used.name(),
+            "replaced bytes which are not valid in the charset of the page"
+        );
+    }
+    text.into_owned()
+}
+
+// The `charset` parameter of a `Content-Type` such as `text/html; charset=Shift_JIS`.
+fn charset_param(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("charset") {
+            Some(value.trim().trim_matches(['"', '\'']).to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+// The charset of `<meta charset="...">`, or of `<meta http-equiv="Content-Type" content="...">`.
+// Only ASCII is looked at, which every charset a Japanese site uses leaves as is.
+fn meta_charset(head: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+    head.split("<meta").skip(1).find_map(|tag| {
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        let start = tag.find("charset=")? + "charset=".len();
+        let value = tag[start..].trim_start_matches(['"', '\'']);
+        let end = value
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(value.len());
+        Some(value[..end].to_owned()).filter(|label| !label.is_empty())
+    })
+}
+
+pub fn find_challenge_marker(body: &str) -> Option<&'static str> {
+    CHALLENGE_MARKERS
+        .iter()
+        .copied()
+        .find(|marker| body.contains(marker))
+}
+
+// Reads the body chunk by chunk, so a connection dropped mid-stream can tell how much has arrived.
+async fn read_body(resp: &mut isahc::Body) -> Result<Vec<u8>, Error> {
+    use futures_preview::io::AsyncReadExt;
+
+    let mut body = Vec::with_capacity(1024);
+    let mut buf = [0; 8192];
+    loop {
+        match resp.read(&mut buf).await {
+            Ok(0) => return Ok(body),
+            Ok(n) => body.extend_from_slice(&buf[..n]),
+            Err(source) => {
+                return Err(Error::BodyReadFailed {
+                    read: body.len(),
+                    source,
+                })
+            }
+        }
+    }
+}
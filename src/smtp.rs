@@ -0,0 +1,382 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use async_std::io::{self, prelude::*, BufReader};
+use async_std::net::TcpStream;
+use chrono::Local;
+use url::Url;
+
+// A small SMTP client, just enough to hand a message to a submission server: TLS from the
+// start or with STARTTLS, AUTH PLAIN or LOGIN, and one message per connection.
+
+// Gives up on a server which stops answering, so a run never hangs on an email.
+const TIMEOUT: Duration = Duration::from_secs(30);
+// Base64 lines are kept under the 78 characters a line of a message should stay within.
+const LINE_LEN: usize = 76;
+// The most bytes of text in one encoded word of a header, so it stays within 75 characters.
+const ENCODED_WORD_LEN: usize = 45;
+
+/// Why an email could not be sent.
+#[derive(thiserror::Error, Debug)]
+pub enum SmtpError {
+    #[error("SMTP login as `{user}` failed ({reply}), check `SMTP_USERNAME` and `SMTP_PASSWORD`")]
+    AuthFailed { user: String, reply: Reply },
+    #[error("the SMTP server offers neither AUTH PLAIN nor AUTH LOGIN")]
+    NoAuthMechanism,
+    #[error("{0} does not offer STARTTLS, so nothing was sent in the clear; use smtps:// if it takes TLS from the start")]
+    StartTlsUnavailable(String),
+    #[error("the SMTP server rejected {command} with {reply}")]
+    Rejected { command: &'static str, reply: Reply },
+    #[error("the SMTP server sent a malformed reply `{0}`")]
+    Malformed(String),
+    #[error("the SMTP server did not finish within {0:?}")]
+    TimedOut(Duration),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Tls(#[from] async_native_tls::Error),
+}
+
+// The server of `SMTP_URL`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Server {
+    pub host: String,
+    pub port: u16,
+    // `smtps://`, TLS from the start (port 465 unless given), or else `smtp://`, which
+    // upgrades with STARTTLS (port 587 unless given).
+    pub implicit_tls: bool,
+}
+
+impl Server {
+    pub fn from_url(url: &str) -> Option<Server> {
+        let url = Url::parse(url).ok()?;
+        let implicit_tls = match url.scheme() {
+            "smtps" => true,
+            "smtp" => false,
+            _ => return None,
+        };
+
+        Some(Server {
+            host: url.host_str()?.to_owned(),
+            port: url.port().unwrap_or(if implicit_tls { 465 } else { 587 }),
+            implicit_tls,
+        })
+    }
+
+    // A relay on this machine, which is the one server that may be talked to without TLS.
+    fn is_loopback(&self) -> bool {
+        self.host == "localhost" || self.host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+    }
+}
+
+// `SMTP_USERNAME` and `SMTP_PASSWORD`
+#[derive(Clone, PartialEq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+// Never printed with the password.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+// An email with a plain text and an HTML version of the same content.
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+impl Mail {
+    // The message as it is sent after DATA, with CRLF line endings.
+    pub fn format(&self) -> String {
+        let id = rand::random::<u64>();
+        let boundary = format!("=_{:016x}", id);
+        let domain = self.from.rsplit('@').next().unwrap_or("localhost");
+
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{:016x}@{}>\r\n",
+            self.from,
+            self.to.join(", "),
+            encode_header(&self.subject),
+            Local::now().to_rfc2822(),
+            id,
+            domain,
+        );
+        message.push_str("MIME-Version: 1.0\r\n");
+        message.push_str(&format!(
+            "Content-Type: multipart/alternative; boundary=\"{}\"\r\n\r\n",
+            boundary
+        ));
+        for (content_type, content) in &[("text/plain", &self.text), ("text/html", &self.html)] {
+            message.push_str(&format!(
+                "--{}\r\nContent-Type: {}; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+                boundary,
+                content_type,
+                base64_lines(content.as_bytes())
+            ));
+        }
+        message.push_str(&format!("--{}--\r\n", boundary));
+
+        message
+    }
+}
+
+// Base64, broken into lines, so the text survives any server which is not 8-bit clean.
+fn base64_lines(content: &[u8]) -> String {
+    base64::encode(content)
+        .as_bytes()
+        .chunks(LINE_LEN)
+        .map(|line| std::str::from_utf8(line).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+// A header value as is if it is ASCII, or else as RFC 2047 encoded words, folded onto lines
+// of their own and never splitting a character.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_owned();
+    }
+
+    let mut words = vec![];
+    let mut word = String::new();
+    for c in value.chars() {
+        if word.len() + c.len_utf8() > ENCODED_WORD_LEN {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+    }
+    words.push(word);
+
+    words
+        .iter()
+        .map(|word| format!("=?UTF-8?B?{}?=", base64::encode(word)))
+        .collect::<Vec<_>>()
+        .join("\r\n ")
+}
+
+// A reply of the server, with the text of every line of a multiline one.
+#[derive(Debug)]
+pub struct Reply {
+    pub code: u16,
+    pub lines: Vec<String>,
+}
+
+impl Reply {
+    fn is(&self, class: u16) -> bool {
+        self.code / 100 == class / 100
+    }
+
+    // Whether an EHLO reply lists the extension, e.g. STARTTLS.
+    fn has_extension(&self, extension: &str) -> bool {
+        self.lines
+            .iter()
+            .skip(1)
+            .any(|line| line.split_whitespace().next() == Some(extension))
+    }
+
+    // The mechanisms of the AUTH extension of an EHLO reply.
+    fn auth_mechanisms(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .skip(1)
+            .filter_map(|line| line.strip_prefix("AUTH "))
+            .flat_map(str::split_whitespace)
+            .map(str::to_ascii_uppercase)
+            .collect()
+    }
+}
+
+impl fmt::Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code, self.lines.join(" "))
+    }
+}
+
+struct Connection<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: Read + Write + Unpin> Connection<S> {
+    fn new(stream: S) -> Self {
+        Connection {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    async fn reply(&mut self) -> Result<Reply, SmtpError> {
+        let mut lines = vec![];
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            let line = line.trim_end();
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(|| SmtpError::Malformed(line.to_owned()))?;
+            lines.push(line.get(4..).unwrap_or_default().to_owned());
+
+            // `250-` is followed by more lines, and `250 ` is the last one.
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(Reply { code, lines });
+            }
+        }
+    }
+
+    async fn command(&mut self, line: &str) -> Result<Reply, SmtpError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(line.as_bytes()).await?;
+        stream.write_all(b"\r\n").await?;
+        stream.flush().await?;
+        self.reply().await
+    }
+
+    // Sends the command, and fails unless the reply is of the class of `expected`, e.g. 2xx.
+    async fn expect(
+        &mut self,
+        command: &'static str,
+        line: &str,
+        expected: u16,
+    ) -> Result<Reply, SmtpError> {
+        let reply = self.command(line).await?;
+        if reply.is(expected) {
+            Ok(reply)
+        } else {
+            Err(SmtpError::Rejected { command, reply })
+        }
+    }
+
+    async fn greeting(&mut self) -> Result<(), SmtpError> {
+        let reply = self.reply().await?;
+        if reply.is(220) {
+            Ok(())
+        } else {
+            Err(SmtpError::Rejected {
+                command: "the connection",
+                reply,
+            })
+        }
+    }
+
+    async fn ehlo(&mut self) -> Result<Reply, SmtpError> {
+        self.expect("EHLO", "EHLO localhost", 250).await
+    }
+
+    async fn login(
+        &mut self,
+        extensions: &Reply,
+        credentials: &Credentials,
+    ) -> Result<(), SmtpError> {
+        let mechanisms = extensions.auth_mechanisms();
+        let reply = if mechanisms.iter().any(|m| m == "PLAIN") {
+            let token = format!("\0{}\0{}", credentials.username, credentials.password);
+            self.command(&format!("AUTH PLAIN {}", base64::encode(token)))
+                .await?
+        } else if mechanisms.iter().any(|m| m == "LOGIN") {
+            let mut reply = self.command("AUTH LOGIN").await?;
+            for answer in &[&credentials.username, &credentials.password] {
+                if reply.code != 334 {
+                    break;
+                }
+                reply = self.command(&base64::encode(answer)).await?;
+            }
+            reply
+        } else {
+            return Err(SmtpError::NoAuthMechanism);
+        };
+
+        if reply.is(235) {
+            Ok(())
+        } else {
+            Err(SmtpError::AuthFailed {
+                user: credentials.username.clone(),
+                reply,
+            })
+        }
+    }
+
+    async fn deliver(
+        &mut self,
+        extensions: &Reply,
+        credentials: Option<&Credentials>,
+        mail: &Mail,
+    ) -> Result<(), SmtpError> {
+        if let Some(credentials) = credentials {
+            self.login(extensions, credentials).await?;
+        }
+
+        self.expect("MAIL FROM", &format!("MAIL FROM:<{}>", mail.from), 250)
+            .await?;
+        for to in &mail.to {
+            self.expect("RCPT TO", &format!("RCPT TO:<{}>", to), 250)
+                .await?;
+        }
+        self.expect("DATA", "DATA", 354).await?;
+        // A line which starts with a dot gets another one, since a lone dot ends the message.
+        let data = mail.format().replace("\r\n.", "\r\n..");
+        self.expect("the message", &format!("{}.", data), 250)
+            .await?;
+
+        // The message is accepted by now, so a failed QUIT is no reason to report a failure.
+        let _ = self.command("QUIT").await;
+        Ok(())
+    }
+
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+}
+
+// Connects to the server and hands it the mail, logging in first with `credentials`.
+pub async fn send(
+    server: &Server,
+    credentials: Option<&Credentials>,
+    mail: &Mail,
+) -> Result<(), SmtpError> {
+    async_std::future::timeout(TIMEOUT, session(server, credentials, mail))
+        .await
+        .map_err(|_| SmtpError::TimedOut(TIMEOUT))?
+}
+
+async fn session(
+    server: &Server,
+    credentials: Option<&Credentials>,
+    mail: &Mail,
+) -> Result<(), SmtpError> {
+    let tcp = TcpStream::connect((server.host.as_str(), server.port)).await?;
+    if server.implicit_tls {
+        let tls = async_native_tls::connect(server.host.as_str(), tcp).await?;
+        let mut conn = Connection::new(tls);
+        conn.greeting().await?;
+        let extensions = conn.ehlo().await?;
+        return conn.deliver(&extensions, credentials, mail).await;
+    }
+
+    let mut conn = Connection::new(tcp);
+    conn.greeting().await?;
+    let extensions = conn.ehlo().await?;
+    if extensions.has_extension("STARTTLS") {
+        conn.expect("STARTTLS", "STARTTLS", 220).await?;
+        let tls = async_native_tls::connect(server.host.as_str(), conn.into_inner()).await?;
+        let mut conn = Connection::new(tls);
+        // The extensions are asked again, since a server may only offer AUTH over TLS.
+        let extensions = conn.ehlo().await?;
+        conn.deliver(&extensions, credentials, mail).await
+    } else if server.is_loopback() {
+        conn.deliver(&extensions, credentials, mail).await
+    } else {
+        Err(SmtpError::StartTlsUnavailable(server.host.clone()))
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde_derive::Serialize;
+
+use super::diff::Diff;
+use super::events::ItemEvent;
+use super::parsers::SectionError;
+
+// Where the summary of a run goes, if anywhere.
+#[derive(Debug, PartialEq)]
+pub enum SummaryOutput {
+    // `--summary`, as the last line of stdout
+    Stdout,
+    // `SUMMARY_PATH`, overwritten by every run
+    File(PathBuf),
+}
+
+// What a run did, for scripts to check instead of parsing the logs.
+#[derive(Serialize, Default, Debug, PartialEq)]
+pub struct RunSummary {
+    pub items_scraped: usize,
+    pub added: usize,
+    pub price_changed: usize,
+    pub sold: usize,
+    pub relisted: usize,
+    pub deleted: usize,
+    pub details_changed: usize,
+    pub unknown_sold: usize,
+    pub notifications_attempted: usize,
+    pub notifications_sent: usize,
+    pub parse_errors: Vec<ParseErrorSummary>,
+    pub duration_ms: u128,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ParseErrorSummary {
+    pub line: usize,
+    pub error: String,
+}
+
+impl RunSummary {
+    // The counts of the scrape and the diff. The notifications and the duration are filled in later.
+    pub fn new(items_scraped: usize, errors: &[SectionError], diff: &Diff) -> Self {
+        let mut summary = RunSummary {
+            items_scraped,
+            unknown_sold: diff.unknown_sold.len(),
+            parse_errors: errors
+                .iter()
+                .map(|e| ParseErrorSummary {
+                    line: e.line,
+                    error: e.source.to_string(),
+                })
+                .collect(),
+            ..RunSummary::default()
+        };
+
+        for event in &diff.events {
+            match event {
+                ItemEvent::Added { .. } => summary.added += 1,
+                ItemEvent::PriceChanged { .. } => summary.price_changed += 1,
+                ItemEvent::Sold { .. } => summary.sold += 1,
+                ItemEvent::Relisted { .. } => summary.relisted += 1,
+                ItemEvent::Deleted { .. } => summary.deleted += 1,
+                ItemEvent::DetailsChanged { .. } => summary.details_changed += 1,
+                // Not a change of its own, it comes with a price change.
+                ItemEvent::Trend { .. } => {}
+            }
+        }
+
+        summary
+    }
+
+    // Writes the summary as a single line of JSON.
+    pub fn write(&self, output: &SummaryOutput) -> io::Result<()> {
+        let json = serde_json::to_string(self)?;
+        match output {
+            SummaryOutput::Stdout => writeln!(io::stdout().lock(), "{}", json),
+            SummaryOutput::File(path) => writeln!(File::create(path)?, "{}", json),
+        }
+    }
+}
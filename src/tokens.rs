@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+
+use super::fcm::FcmResponse;
+use super::models::Token;
+use super::schema::tokens::dsl as table;
+
+#[derive(PartialEq, Debug)]
+pub enum TokenChange {
+    // FCM reported the token as `NotRegistered` or `InvalidRegistration`.
+    Delete(i32),
+    // FCM returned a canonical token to use instead.
+    Replace { id: i32, registration_id: String },
+}
+
+pub fn load(conn: &MysqlConnection) -> QueryResult<Vec<Token>> {
+    table::tokens.order(table::id.asc()).load(conn)
+}
+
+// The registration ids to send to: `FCM_REGISTRATION_ID(S)` followed by the registered tokens,
+// without duplicates. Empty while no token is registered, so the client's own target is used,
+// which may be a topic; topics can't be sent to along with registration ids.
+pub fn recipients(env_ids: &[String], tokens: &[Token]) -> Vec<String> {
+    if tokens.is_empty() {
+        return vec![];
+    }
+
+    let mut seen = HashSet::new();
+    env_ids
+        .iter()
+        .filter(|id| !id.starts_with("/topics/"))
+        .chain(tokens.iter().map(|token| &token.registration_id))
+        .filter(|id| seen.insert(id.as_str()))
+        .cloned()
+        .collect()
+}
+
+// Works out which tokens to drop or rewrite from the responses of messages sent to `recipients`.
+// The results of each response are in the same order as the recipients, of which only the
+// registered tokens are changed.
+pub fn changes(
+    tokens: &[Token],
+    recipients: &[String],
+    responses: &[FcmResponse],
+) -> Vec<TokenChange> {
+    let known = tokens
+        .iter()
+        .map(|token| token.registration_id.as_str())
+        .collect::<HashSet<_>>();
+    let mut changed = HashSet::new();
+    let mut changes = vec![];
+
+    for response in responses {
+        for (recipient, result) in recipients.iter().zip(&response.results) {
+            let token = match tokens.iter().find(|token| &token.registration_id == recipient) {
+                Some(token) => token,
+                None => continue,
+            };
+            if changed.contains(&token.id) {
+                continue;
+            }
+
+            if result.error.as_ref().is_some_and(|e| e.is_dead_token()) {
+                changes.push(TokenChange::Delete(token.id));
+                changed.insert(token.id);
+            } else if let Some(canonical) = &result.registration_id {
+                // The canonical token is already stored, so this one is only a duplicate.
+                if known.contains(canonical.as_str()) {
+                    changes.push(TokenChange::Delete(token.id));
+                } else {
+                    changes.push(TokenChange::Replace {
+                        id: token.id,
+                        registration_id: canonical.clone(),
+                    });
+                }
+                changed.insert(token.id);
+            }
+        }
+    }
+
+    changes
+}
+
+pub fn apply(conn: &MysqlConnection, changes: &[TokenChange]) -> QueryResult<()> {
+    for change in changes {
+        match change {
+            TokenChange::Delete(id) => {
+                diesel::delete(table::tokens.find(id)).execute(conn)?;
+            }
+            TokenChange::Replace {
+                id,
+                registration_id,
+            } => {
+                diesel::update(table::tokens.find(id))
+                    .set(table::registration_id.eq(registration_id))
+                    .execute(conn)?;
+            }
+        }
+    }
+
+    Ok(())
+}
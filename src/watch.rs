@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+// Set by SIGTERM, and checked between runs so a run is never cut off halfway through its writes.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+// Randomizes the interval by ±jitter, so the site isn't hit on a perfectly regular cadence.
+pub fn next_delay(interval: Duration, jitter: Duration) -> Duration {
+    if jitter == Duration::from_secs(0) {
+        return interval;
+    }
+
+    let jitter = jitter.as_secs_f64();
+    let offset = rand::thread_rng().gen_range(-jitter, jitter);
+
+    Duration::from_secs_f64((interval.as_secs_f64() + offset).max(0.0))
+}
+
+// Makes SIGTERM only ask the watch loop to stop after the current run.
+#[cfg(unix)]
+pub fn handle_sigterm() {
+    extern "C" fn request_stop(_: libc::c_int) {
+        STOP.store(true, Ordering::SeqCst);
+    }
+
+    // Safe, since the handler only stores to an atomic.
+    unsafe {
+        libc::signal(
+            libc::SIGTERM,
+            request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(not(unix))]
+pub fn handle_sigterm() {}
+
+pub fn stop_requested() -> bool {
+    STOP.load(Ordering::SeqCst)
+}
+
+// Sleeps for `delay`, waking up every second to see whether a stop was requested.
+// Returns false when it was, and the loop should exit instead of running again.
+pub async fn sleep(delay: Duration) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        if stop_requested() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        async_std::task::sleep((deadline - now).min(Duration::from_secs(1))).await;
+    }
+}
@@ -0,0 +1,135 @@
+use takya_notifier::alerts;
+use takya_notifier::config::Lang;
+use takya_notifier::events::ItemEvent;
+use takya_notifier::messages;
+use takya_notifier::models::{Exterior, Item, Watch};
+use takya_notifier::notifier::NotificationEvent;
+
+fn item(name: &str, kind: Option<&str>, exterior: Option<Exterior>, price: i64) -> Item {
+    Item {
+        order_id: 1,
+        name: name.to_owned(),
+        kind: kind.map(str::to_owned),
+        exterior,
+        price,
+        has_sold: false,
+        is_stattrak: false,
+        avg_price_7d: None,
+        phase: None,
+        is_souvenir: false,
+        float_value: None,
+        nametag: None,
+    }
+}
+
+fn watch(name: &str, kind: Option<&str>, exterior: Option<Exterior>, target_price: i64) -> Watch {
+    Watch {
+        id: 1,
+        name: name.to_owned(),
+        kind: kind.map(str::to_owned),
+        exterior,
+        target_price,
+    }
+}
+
+#[test]
+fn watch_without_exterior_matches_any() {
+    let redline = item("AK-47", Some("Redline"), Some(Exterior::FT), 1000);
+
+    assert!(alerts::matches(
+        &watch("AK-47", Some("Redline"), None, 0),
+        &redline
+    ));
+    assert!(alerts::matches(
+        &watch("AK-47", Some("Redline"), Some(Exterior::FT), 0),
+        &redline
+    ));
+    assert!(!alerts::matches(
+        &watch("AK-47", Some("Redline"), Some(Exterior::MW), 0),
+        &redline
+    ));
+    assert!(!alerts::matches(
+        &watch("AK-47", Some("Vulcan"), None, 0),
+        &redline
+    ));
+}
+
+#[test]
+fn vanilla_watch_only_matches_vanilla() {
+    let vanilla = item("Karambit", None, None, 1000);
+    let fade = item("Karambit", Some("Fade"), Some(Exterior::FN), 1000);
+    let watch = watch("Karambit", None, None, 0);
+
+    assert!(alerts::matches(&watch, &vanilla));
+    assert!(!alerts::matches(&watch, &fade));
+}
+
+#[test]
+fn stattrak_is_told_apart_by_the_prefix() {
+    let plain = item("AWP", Some("Asiimov"), Some(Exterior::FT), 1000);
+    let stattrak = Item {
+        is_stattrak: true,
+        ..plain.clone()
+    };
+
+    let stattrak_watch = watch("StatTrak™ AWP", Some("Asiimov"), None, 0);
+    assert!(alerts::matches(&stattrak_watch, &stattrak));
+    assert!(!alerts::matches(&stattrak_watch, &plain));
+
+    let plain_watch = watch("AWP", Some("Asiimov"), None, 0);
+    assert!(alerts::matches(&plain_watch, &plain));
+    assert!(!alerts::matches(&plain_watch, &stattrak));
+}
+
+#[test]
+fn only_crossing_the_target_is_notified() {
+    let watches = [watch("AK-47", Some("Redline"), None, 1000)];
+    let price_changed = |old_price, new_price| ItemEvent::PriceChanged {
+        item: item("AK-47", Some("Redline"), Some(Exterior::FT), new_price),
+        old_price,
+        new_price,
+        image_url: None,
+    };
+
+    let events = [
+        price_changed(1200, 1000),
+        // Already below the target before.
+        price_changed(900, 800),
+        price_changed(800, 1100),
+        ItemEvent::Added {
+            item: item("AK-47", Some("Redline"), Some(Exterior::MW), 950),
+        },
+    ];
+    let reached = alerts::reached(&watches, &events);
+
+    let prices = reached
+        .iter()
+        .map(|event| match event {
+            NotificationEvent::TargetReached { item, target_price } => {
+                assert_eq!(*target_price, 1000);
+                item.price
+            }
+            _ => panic!("unexpected event {:?}", event),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(prices, vec![1000, 950]);
+}
+
+#[test]
+fn target_reached_notification() {
+    let event = NotificationEvent::TargetReached {
+        item: item("AK-47", Some("Redline"), Some(Exterior::FT), 900),
+        target_price: 1000,
+    };
+
+    let ja = messages::notification(&event, Lang::Ja, false);
+    assert_eq!(
+        ja.title(),
+        Some("AK-47 | Redline (Field-Tested) が目標価格に到達しました")
+    );
+    assert_eq!(
+        ja.body(),
+        Some("販売価格は 900 円です。(目標価格: 1000 円)")
+    );
+    assert_eq!(event.kind(), "target_reached");
+}
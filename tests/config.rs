@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+
+use takya_notifier::config::{ConfigError, Silence, SINK_VARS};
+use takya_notifier::http_client::ProxyConfig;
+
+fn at(timestamp: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+#[test]
+fn silence_ends_at_the_timestamp() {
+    let silence = Silence {
+        until: Some(DateTime::parse_from_rfc3339("2020-09-01T00:00:00+09:00").unwrap()),
+        file: None,
+    };
+
+    assert!(silence.is_active(at("2020-08-31T14:59:59Z")));
+    assert!(!silence.is_active(at("2020-08-31T15:00:00Z")));
+}
+
+#[test]
+fn silence_lasts_while_the_file_exists() {
+    let file = std::env::temp_dir().join(format!("takya_notifier_silence_{}", std::process::id()));
+    let silence = Silence {
+        until: None,
+        file: Some(file.clone()),
+    };
+    let now = Utc::now();
+
+    assert!(!silence.is_active(now));
+    std::fs::write(&file, "").unwrap();
+    assert!(silence.is_active(now));
+    std::fs::remove_file(&file).unwrap();
+    assert!(!silence.is_active(now));
+}
+
+#[test]
+fn nothing_is_silenced_by_default() {
+    assert!(!Silence::default().is_active(Utc::now()));
+}
+
+#[test]
+fn proxy_is_picked_by_scheme() {
+    let proxy = ProxyConfig {
+        http: Some("http://plain.example.com:3128".parse().unwrap()),
+        https: Some("http://tls.example.com:3128".parse().unwrap()),
+    };
+
+    let host = |url| proxy.for_url(url).and_then(|uri| uri.host());
+    assert_eq!(
+        host("http://steamrmt.com/skinbuy.html"),
+        Some("plain.example.com")
+    );
+    assert_eq!(
+        host("https://fcm.googleapis.com/fcm/send"),
+        Some("tls.example.com")
+    );
+    assert_eq!(
+        ProxyConfig::default().for_url("https://fcm.googleapis.com"),
+        None
+    );
+}
+
+#[test]
+fn missing_sink_lists_every_sink_variable() {
+    let error = ConfigError::NoSink.to_string();
+    for var in SINK_VARS {
+        assert!(error.contains(var), "{}", error);
+    }
+}
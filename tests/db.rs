@@ -0,0 +1,48 @@
+use std::cell::Cell;
+use std::time::Duration;
+
+use takya_notifier::db::RetryPolicy;
+
+fn policy(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        base_delay: Duration::from_millis(1),
+    }
+}
+
+// A stand-in for establishing a connection, failing the first `failures` times.
+fn flaky_connect(attempts: &Cell<u32>, failures: u32) -> Result<&'static str, String> {
+    attempts.set(attempts.get() + 1);
+    if attempts.get() <= failures {
+        Err(format!("connection refused (attempt {})", attempts.get()))
+    } else {
+        Ok("connected")
+    }
+}
+
+#[test]
+fn connection_succeeds_after_failures() {
+    let attempts = Cell::new(0);
+    let result = policy(3).retry("connect", || flaky_connect(&attempts, 2));
+
+    assert_eq!(result, Ok("connected"));
+    assert_eq!(attempts.get(), 3);
+}
+
+#[test]
+fn last_error_is_returned_once_retries_run_out() {
+    let attempts = Cell::new(0);
+    let result = policy(2).retry("connect", || flaky_connect(&attempts, 5));
+
+    assert_eq!(result, Err("connection refused (attempt 3)".to_owned()));
+    assert_eq!(attempts.get(), 3);
+}
+
+#[test]
+fn no_retries_tries_once() {
+    let attempts = Cell::new(0);
+    let result = policy(0).retry("connect", || flaky_connect(&attempts, 1));
+
+    assert!(result.is_err());
+    assert_eq!(attempts.get(), 1);
+}
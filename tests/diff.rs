@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+
+use takya_notifier::config::Lang;
+use takya_notifier::db;
+use takya_notifier::diff::{Change, DiffOptions};
+use takya_notifier::events::ItemEvent;
+use takya_notifier::filter::NotifyFilter;
+use takya_notifier::ignore::IgnoreList;
+use takya_notifier::messages;
+use takya_notifier::models::{Exterior, Item};
+use takya_notifier::parsers::{ItemSection, ParserConfig};
+use takya_notifier::scrape::{dedup_by_order_id, parse_document};
+use takya_notifier::summary::RunSummary;
+
+fn item(order_id: i32, price: i64) -> Item {
+    Item {
+        order_id,
+        name: "AK-47".to_owned(),
+        kind: Some("Redline".to_owned()),
+        exterior: Some(Exterior::FT),
+        price,
+        has_sold: false,
+        is_stattrak: false,
+        avg_price_7d: Some(price),
+        phase: None,
+        is_souvenir: false,
+        float_value: None,
+        nametag: None,
+    }
+}
+
+fn section(item: Item) -> ItemSection {
+    ItemSection {
+        order_id: item.order_id,
+        price: item.price,
+        item: Some(item),
+        image_url: None,
+    }
+}
+
+fn sold(order_id: i32, price: i64) -> ItemSection {
+    ItemSection {
+        item: None,
+        order_id,
+        price,
+        image_url: None,
+    }
+}
+
+fn titles(sections: Vec<ItemSection>, db_items: Vec<Item>) -> Vec<String> {
+    let diff = takya_notifier::diff(sections, db_items, &HashMap::new(), &DiffOptions::default());
+    diff.events
+        .iter()
+        .map(|event| {
+            let json = serde_json::to_value(messages::item_event(event, Lang::Ja, false)).unwrap();
+            json["title"].as_str().unwrap().to_owned()
+        })
+        .collect()
+}
+
+// The same notifications as when every item was looked up with its own query.
+#[test]
+fn notifications_match_per_item_lookup() {
+    let db_items = vec![item(1, 1000), item(2, 2000), item(3, 3000), item(4, 4000)];
+    let sections = vec![
+        section(item(1, 1000)),
+        section(item(2, 1500)),
+        sold(3, 3000),
+        section(item(5, 5000)),
+    ];
+
+    assert_eq!(
+        titles(sections, db_items),
+        vec![
+            "AK-47 | Redline (Field-Tested) の価格が変更されました",
+            "AK-47 | Redline (Field-Tested) が売約済みになりました",
+            "AK-47 | Redline (Field-Tested) が新たに追加されました",
+            "AK-47 | Redline (Field-Tested) が削除されました",
+        ]
+    );
+}
+
+#[test]
+fn unchanged_items_are_not_written() {
+    let diff = takya_notifier::diff(
+        vec![section(item(1, 1000))],
+        vec![item(1, 1000)],
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+
+    assert!(diff.events.is_empty());
+    assert!(!diff
+        .changes
+        .iter()
+        .any(|change| matches!(change, Change::Update(_))));
+}
+
+#[test]
+fn near_empty_parse_deletes_nothing() {
+    let db_items = (1..=100).map(|id| item(id, 1000)).collect::<Vec<_>>();
+    let diff = takya_notifier::diff(
+        vec![section(item(1, 1000)), section(item(2, 1000))],
+        db_items,
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+
+    assert!(!diff
+        .changes
+        .iter()
+        .any(|change| matches!(change, Change::Delete(_))));
+    assert!(diff.events.is_empty());
+}
+
+#[test]
+fn skipped_deletions_keep_the_other_changes() {
+    let db_items = vec![item(1, 1000), item(2, 1000), item(3, 1000)];
+    let mut diff = takya_notifier::diff(
+        vec![section(item(1, 900)), section(item(2, 1000))],
+        db_items,
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+
+    assert_eq!(diff.skip_deletions(), 1);
+    assert!(!diff
+        .changes
+        .iter()
+        .any(|change| matches!(change, Change::Delete(_))));
+    assert_eq!(diff.events.len(), 1);
+    assert!(matches!(diff.events[0], ItemEvent::PriceChanged { .. }));
+}
+
+#[test]
+fn parsed_page_is_diffed_against_stored_items() {
+    let html = concat!(
+        "<section>",
+        "<span>★</span><a>AK-47 | Redline (Field-Tested) #1</a>\n<p>販売価格: 900円</p>",
+        "<span>★</span><a>(売約済み) #2</a>\n<p>販売価格: 2,000円</p>",
+        "</section>",
+    );
+    let (found, errors) = parse_document(
+        html,
+        "http://example.com/",
+        &["section".to_owned()],
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    assert!(errors.is_empty());
+
+    assert_eq!(
+        titles(found, vec![item(1, 1000), item(2, 2000)]),
+        vec![
+            "AK-47 | Redline (Field-Tested) の価格が変更されました",
+            "AK-47 | Redline (Field-Tested) が売約済みになりました",
+        ]
+    );
+}
+
+#[test]
+fn items_on_several_pages_are_merged() {
+    let page1 = vec![section(item(1, 1000)), section(item(2, 2000))];
+    let page2 = vec![section(item(2, 2000)), section(item(3, 3000))];
+    let found = dedup_by_order_id(page1.into_iter().chain(page2).collect());
+
+    assert_eq!(
+        found.iter().map(|s| s.order_id).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    let diff = takya_notifier::diff(
+        found,
+        vec![item(1, 1000), item(2, 2000), item(3, 3000)],
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+    assert!(diff.events.is_empty());
+}
+
+#[test]
+fn duplicated_order_id_gives_a_single_event() {
+    let found = dedup_by_order_id(vec![
+        section(item(1, 1000)),
+        section(item(2, 2000)),
+        section(item(1, 900)),
+    ]);
+
+    assert_eq!(
+        found
+            .iter()
+            .map(|s| (s.order_id, s.price))
+            .collect::<Vec<_>>(),
+        vec![(1, 900), (2, 2000)]
+    );
+    let diff = takya_notifier::diff(
+        found,
+        vec![item(1, 1000), item(2, 2000)],
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+    assert!(matches!(
+        diff.events.as_slice(),
+        [ItemEvent::PriceChanged {
+            old_price: 1000,
+            new_price: 900,
+            ..
+        }]
+    ));
+    assert_eq!(db::upserts(&diff.changes).len(), 1);
+}
+
+#[test]
+fn duplicated_sold_marker_does_not_replace_the_item() {
+    let found = dedup_by_order_id(vec![section(item(1, 1000)), sold(1, 1000)]);
+
+    assert_eq!(found.len(), 1);
+    assert!(found[0].item.is_some());
+
+    let found = dedup_by_order_id(vec![sold(1, 1000), section(item(1, 1000))]);
+    assert!(found[0].item.is_some());
+}
+
+#[test]
+fn float_change_is_written_without_notifying() {
+    let mut found = item(1, 1000);
+    found.float_value = Some(0.15);
+    let diff = takya_notifier::diff(
+        vec![section(found)],
+        vec![item(1, 1000)],
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+
+    assert!(diff.events.is_empty());
+    assert!(diff
+        .changes
+        .iter()
+        .any(|change| matches!(change, Change::Update(item) if item.float_value == Some(0.15))));
+}
+
+#[test]
+fn sold_item_becomes_available_again() {
+    let mut was_sold = item(1, 1000);
+    was_sold.has_sold = true;
+    let diff = takya_notifier::diff(
+        vec![section(item(1, 900))],
+        vec![was_sold],
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+
+    assert!(matches!(
+        diff.events.as_slice(),
+        [ItemEvent::Relisted { item }] if !item.has_sold && item.price == 900
+    ));
+    assert!(diff
+        .changes
+        .iter()
+        .any(|change| matches!(change, Change::Update(item) if !item.has_sold)));
+    assert!(diff
+        .changes
+        .iter()
+        .any(|change| matches!(change, Change::RecordPrice { price: 900, .. })));
+
+    let json =
+        serde_json::to_value(messages::item_event(&diff.events[0], Lang::Ja, false)).unwrap();
+    assert_eq!(
+        json["title"],
+        "AK-47 | Redline (Field-Tested) が再出品されました"
+    );
+}
+
+#[test]
+fn new_and_changed_items_are_upserted_together() {
+    let diff = takya_notifier::diff(
+        vec![section(item(1, 900)), section(item(2, 2000)), sold(3, 3000)],
+        vec![item(1, 1000), item(3, 3000)],
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+
+    let upserted = db::upserts(&diff.changes)
+        .iter()
+        .map(|item| (item.order_id, item.price))
+        .collect::<Vec<_>>();
+    assert_eq!(upserted, vec![(1, 900), (2, 2000)]);
+}
+
+#[test]
+fn summary_counts_the_changes() {
+    let mut was_sold = item(4, 4000);
+    was_sold.has_sold = true;
+    let diff = takya_notifier::diff(
+        vec![
+            section(item(1, 900)),
+            sold(2, 2000),
+            section(item(4, 4000)),
+            section(item(5, 5000)),
+        ],
+        vec![item(1, 1000), item(2, 2000), item(3, 3000), was_sold],
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+    let summary = RunSummary::new(4, &[], &diff);
+
+    assert_eq!(summary.items_scraped, 4);
+    assert_eq!(
+        (
+            summary.added,
+            summary.price_changed,
+            summary.sold,
+            summary.relisted,
+            summary.deleted
+        ),
+        (1, 1, 1, 1, 1)
+    );
+    assert_eq!(
+        serde_json::to_value(&summary).unwrap()["parse_errors"],
+        serde_json::json!([])
+    );
+}
+
+#[test]
+fn changed_exterior_is_notified_before_the_update() {
+    let mut found = item(1, 1000);
+    found.exterior = Some(Exterior::MW);
+    let options = DiffOptions {
+        notify_details_change: true,
+        ..DiffOptions::default()
+    };
+    let diff = takya_notifier::diff(
+        vec![section(found)],
+        vec![item(1, 1000)],
+        &HashMap::new(),
+        &options,
+    );
+
+    match diff.events.as_slice() {
+        [event @ ItemEvent::DetailsChanged { item, previous }] => {
+            assert_eq!(item.exterior, Some(Exterior::MW));
+            assert_eq!(previous.exterior, Some(Exterior::FT));
+            let json = serde_json::to_value(messages::item_event(event, Lang::Ja, false)).unwrap();
+            assert_eq!(json["title"], "アイテム情報が更新されました");
+        }
+        events => panic!("unexpected events {:?}", events),
+    }
+    assert!(diff.changes.iter().any(
+        |change| matches!(change, Change::Update(item) if item.exterior == Some(Exterior::MW))
+    ));
+
+    // Without the flag the row is still updated, but silently.
+    let mut found = item(1, 1000);
+    found.exterior = Some(Exterior::MW);
+    assert!(titles(vec![section(found)], vec![item(1, 1000)]).is_empty());
+}
+
+#[test]
+fn items_below_the_price_floor_are_not_notified() {
+    let mut sticker = item(1, 50);
+    sticker.name = "Sticker".to_owned();
+    let diff = takya_notifier::diff(
+        vec![section(sticker.clone()), section(item(2, 900)), sold(3, 40)],
+        vec![item(2, 1000), item(3, 40)],
+        &HashMap::new(),
+        &DiffOptions::default(),
+    );
+    assert_eq!(diff.events.len(), 3);
+
+    let notified = |filter: &NotifyFilter| {
+        diff.events
+            .iter()
+            .filter(|event| filter.should_notify(event))
+            .map(|event| event.item().order_id)
+            .collect::<Vec<_>>()
+    };
+    let mut filter = NotifyFilter {
+        min_item_price_yen: 100,
+        ..NotifyFilter::default()
+    };
+    // The sale of a cheap item is still notified, unless the floor applies to removals too.
+    assert_eq!(notified(&filter), vec![2, 3]);
+    filter.min_item_price_on_removal = true;
+    assert_eq!(notified(&filter), vec![2]);
+
+    // Every change is still written.
+    assert!(diff
+        .changes
+        .iter()
+        .any(|change| matches!(change, Change::Insert(item) if item.order_id == 1)));
+}
+
+fn ignored_diff(patterns: &[&str]) -> Vec<i32> {
+    let mut stattrak = item(2, 2000);
+    stattrak.is_stattrak = true;
+    let mut vanilla = item(3, 3000);
+    vanilla.name = "Karambit".to_owned();
+    vanilla.kind = None;
+    vanilla.exterior = None;
+    let options = DiffOptions {
+        ignore: IgnoreList::parse(
+            &patterns
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap(),
+        ..DiffOptions::default()
+    };
+
+    let diff = takya_notifier::diff(
+        vec![section(item(1, 900)), section(stattrak), section(vanilla)],
+        vec![item(1, 1000)],
+        &HashMap::new(),
+        &options,
+    );
+    // Ignored or not, every item is written.
+    assert_eq!(
+        diff.changes
+            .iter()
+            .filter(|change| matches!(change, Change::Update(_) | Change::Insert(_)))
+            .count(),
+        3
+    );
+    diff.events
+        .iter()
+        .map(|event| event.item().order_id)
+        .collect()
+}
+
+#[test]
+fn ignored_substrings_match_the_full_name() {
+    assert_eq!(ignored_diff(&[]), vec![1, 2, 3]);
+    // The exterior is only in the full name, and the case doesn't matter.
+    assert_eq!(ignored_diff(&["redline (field-tested)"]), vec![3]);
+    assert_eq!(ignored_diff(&["StatTrak™"]), vec![1, 3]);
+    assert_eq!(ignored_diff(&["karambit", "#1"]), vec![2]);
+}
+
+#[test]
+fn ignored_patterns_are_regexes() {
+    assert_eq!(ignored_diff(&["/^AK-47 \\| Redline/"]), vec![2, 3]);
+    assert_eq!(ignored_diff(&["/\\(Field-Tested\\)$/"]), vec![3]);
+    // Regexes are case-sensitive, unlike substrings.
+    assert_eq!(ignored_diff(&["/karambit/"]), vec![1, 2, 3]);
+
+    assert_eq!(
+        IgnoreList::parse(&["/(unclosed/".to_owned()]).unwrap_err(),
+        "/(unclosed/"
+    );
+}
+
+#[test]
+fn escaped_comma_stays_in_the_entry() {
+    let list = IgnoreList::from_list(r"Graffiti, /^AK-4{1\,2}7 \|/ ,, #3").unwrap();
+
+    assert!(list.matches(&item(1, 1000)));
+    assert!(list.matches_order_id(3));
+    assert!(!list.matches_order_id(4));
+    // Unescaped, the comma splits the regex into two substrings which match nothing.
+    let split = IgnoreList::from_list(r"/^AK-4{1,2}7 \|/").unwrap();
+    assert!(!split.matches(&item(1, 1000)));
+}
+
+#[test]
+fn only_repriced_items_need_their_history() {
+    let db_items = vec![item(1, 100), item(2, 100), item(3, 100)];
+    let found = vec![
+        section(item(1, 100)),
+        section(item(2, 90)),
+        sold(3, 80),
+        section(item(4, 50)),
+    ];
+
+    assert_eq!(takya_notifier::diff::repriced(&found, &db_items), vec![2]);
+}
@@ -0,0 +1,184 @@
+use std::sync::{Arc, Mutex};
+
+use async_std::io::prelude::*;
+use async_std::io::BufReader;
+use async_std::net::TcpListener;
+
+use takya_notifier::config::Lang;
+use takya_notifier::digest::DigestBuilder;
+use takya_notifier::events::ItemEvent;
+use takya_notifier::models::{Exterior, Item};
+use takya_notifier::notifier::{EmailSink, NotificationEvent, Sink};
+use takya_notifier::smtp::{Credentials, Server};
+
+const USERNAME: &str = "notifier@example.com";
+const PASSWORD: &str = "secret";
+
+// Plays an SMTP server on the loopback address, which accepts AUTH PLAIN with `USERNAME` and
+// `PASSWORD`, and records the message of each DATA.
+async fn mock_server() -> (Server, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = Server {
+        host: "127.0.0.1".to_owned(),
+        port: listener.local_addr().unwrap().port(),
+        implicit_tls: false,
+    };
+    let received = Arc::new(Mutex::new(vec![]));
+
+    let log = received.clone();
+    async_std::task::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut reader = BufReader::new(stream.clone());
+        let mut writer = stream;
+        writer.write_all(b"220 mock ESMTP\r\n").await.unwrap();
+
+        let mut line = String::new();
+        while reader.read_line(&mut line).await.unwrap() > 0 {
+            let command = line.trim_end().to_owned();
+            line.clear();
+            let reply = if command.starts_with("EHLO") {
+                "250-mock\r\n250-8BITMIME\r\n250 AUTH LOGIN PLAIN\r\n".to_owned()
+            } else if let Some(token) = command.strip_prefix("AUTH PLAIN ") {
+                let expected = base64::encode(format!("\0{}\0{}", USERNAME, PASSWORD));
+                if token == expected {
+                    "235 2.7.0 Authentication successful\r\n".to_owned()
+                } else {
+                    "535 5.7.8 Authentication credentials invalid\r\n".to_owned()
+                }
+            } else if command == "DATA" {
+                writer.write_all(b"354 go ahead\r\n").await.unwrap();
+                let mut data = String::new();
+                while !data.ends_with("\r\n.\r\n") {
+                    reader.read_line(&mut data).await.unwrap();
+                }
+                log.lock().unwrap().push(data);
+                "250 2.0.0 queued\r\n".to_owned()
+            } else if command == "QUIT" {
+                writer.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                "250 ok\r\n".to_owned()
+            };
+            writer.write_all(reply.as_bytes()).await.unwrap();
+        }
+    });
+
+    (server, received)
+}
+
+fn credentials(password: &str) -> Option<Credentials> {
+    Some(Credentials {
+        username: USERNAME.to_owned(),
+        password: password.to_owned(),
+    })
+}
+
+fn item(order_id: i32, price: i64) -> Item {
+    Item {
+        order_id,
+        name: "AK-47".to_owned(),
+        kind: Some("Redline".to_owned()),
+        exterior: Some(Exterior::FT),
+        price,
+        has_sold: false,
+        is_stattrak: false,
+        avg_price_7d: None,
+        phase: None,
+        is_souvenir: false,
+        float_value: None,
+        nametag: None,
+    }
+}
+
+// The decoded part of the message with this content type.
+fn part(message: &str, content_type: &str) -> String {
+    let header = format!(
+        "Content-Type: {}; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n",
+        content_type
+    );
+    let start = message.find(&header).unwrap() + header.len();
+    let encoded = &message[start..start + message[start..].find("\r\n--").unwrap()];
+    String::from_utf8(base64::decode(encoded.replace("\r\n", "")).unwrap()).unwrap()
+}
+
+#[async_std::test]
+async fn digest_is_sent_as_one_email_with_a_table() {
+    let (server, received) = mock_server().await;
+    let sink = EmailSink::new(
+        server,
+        credentials(PASSWORD),
+        USERNAME,
+        vec!["me@example.com".to_owned()],
+        Lang::Ja,
+    );
+    let mut digest = DigestBuilder::new();
+    digest
+        .push(&ItemEvent::PriceChanged {
+            item: item(1, 900),
+            old_price: 1000,
+            new_price: 900,
+            image_url: None,
+        })
+        .push(&ItemEvent::Added {
+            item: item(2, 5000),
+        });
+
+    sink.send(&NotificationEvent::Digest(digest)).await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let message = &received[0];
+    assert!(message.contains("To: me@example.com\r\n"));
+    // The Japanese subject is encoded, since a header can only carry ASCII.
+    assert!(message.contains("Subject: =?UTF-8?B?"));
+    assert!(part(message, "text/plain").starts_with("2 件の変更がありました"));
+
+    let html = part(message, "text/html");
+    assert!(html.contains("<th>アイテム</th>"));
+    assert!(html.contains("<td>値下げ</td><td>AK-47 | Redline (Field-Tested)</td>"));
+    assert!(html.contains("<td align=\"right\">900 円</td><td align=\"right\">-100 円</td>"));
+    assert!(html.contains("<td>新着</td>"));
+}
+
+#[async_std::test]
+async fn wrong_password_is_reported() {
+    let (server, received) = mock_server().await;
+    let sink = EmailSink::new(
+        server,
+        credentials("wrong"),
+        USERNAME,
+        vec!["me@example.com".to_owned()],
+        Lang::En,
+    );
+
+    let error = sink
+        .send(&NotificationEvent::UnknownSold {
+            order_id: 1,
+            price: 1000,
+        })
+        .await
+        .unwrap_err()
+        .to_string();
+
+    assert!(
+        error.contains("SMTP login as `notifier@example.com` failed"),
+        "{}",
+        error
+    );
+    assert!(error.contains("535"), "{}", error);
+    assert!(received.lock().unwrap().is_empty());
+}
+
+#[test]
+fn smtp_url_sets_the_tls_and_the_default_port() {
+    let server = Server::from_url("smtps://smtp.example.com").unwrap();
+    assert_eq!((server.port, server.implicit_tls), (465, true));
+
+    let server = Server::from_url("smtp://smtp.example.com").unwrap();
+    assert_eq!((server.port, server.implicit_tls), (587, false));
+
+    let server = Server::from_url("smtp://127.0.0.1:2525").unwrap();
+    assert_eq!(server.port, 2525);
+
+    assert!(Server::from_url("https://smtp.example.com").is_none());
+}
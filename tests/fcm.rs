@@ -0,0 +1,307 @@
+use std::time::Duration;
+
+use async_std::io::prelude::*;
+use async_std::net::TcpListener;
+
+use takya_notifier::build_notification;
+use takya_notifier::fcm::{
+    ApnsConfig, BuildError, Client, FcmError, LightSettings, MessageBuilder, NotificationBuilder,
+    NotificationPriority, ServiceAccount, ServiceAccountKey,
+};
+use takya_notifier::http_client;
+
+#[test]
+fn cloned_message_serializes_the_same() {
+    let notification = build_notification! {
+        title = "AK-47 | Redline (Field-Tested) の価格が変更されました";
+        body = "1000 円から 900 円になりました。";
+    };
+    let mut builder = MessageBuilder::new("key", "/topics/all_device");
+    builder.notification(notification.clone());
+    let message = builder.build().unwrap();
+    let cloned = message.clone();
+
+    assert_eq!(cloned.body, message.body);
+    assert_eq!(
+        serde_json::to_value(&cloned.body).unwrap(),
+        serde_json::to_value(&message.body).unwrap()
+    );
+    assert_eq!(
+        serde_json::to_value(&notification).unwrap()["title"],
+        "AK-47 | Redline (Field-Tested) の価格が変更されました"
+    );
+}
+
+#[test]
+fn empty_notification_is_rejected() {
+    assert_eq!(
+        NotificationBuilder::new().try_build().unwrap_err(),
+        BuildError::Empty
+    );
+
+    let result = build_notification! {
+        try;
+        color = "#ff0000";
+    };
+    assert_eq!(result.unwrap_err(), BuildError::Empty);
+}
+
+#[test]
+fn color_must_be_rrggbb() {
+    for color in &["#ff0000", "#A1b2C3"] {
+        let result = build_notification! {
+            try;
+            title = "title";
+            color = *color;
+        };
+        assert!(result.is_ok(), "{}", color);
+    }
+
+    for color in &["red", "#fff", "ff0000", "#ff00000", "#gg0000", "#ｆｆ0000"] {
+        let result = build_notification! {
+            try;
+            title = "title";
+            color = *color;
+        };
+        assert_eq!(
+            result.unwrap_err(),
+            BuildError::InvalidColor(color.to_string())
+        );
+    }
+}
+
+#[test]
+fn localization_keys_are_enough() {
+    let result = build_notification! {
+        try;
+        title_loc_key = "item_added";
+    };
+    assert!(result.is_ok());
+}
+
+#[test]
+fn mutable_content_is_sent_with_the_message() {
+    let notification = build_notification! {
+        title = "title";
+        mutable_content = true;
+        notification_priority = NotificationPriority::High;
+    };
+    let mut builder = MessageBuilder::new("key", "token");
+    builder.notification(notification);
+    let body = serde_json::to_value(&builder.build().unwrap().body).unwrap();
+
+    assert_eq!(body["mutable_content"], true);
+    assert_eq!(body["notification"].get("mutable_content"), None);
+    assert_eq!(
+        body["notification"]["notification_priority"],
+        "PRIORITY_HIGH"
+    );
+}
+
+#[test]
+fn failed_status_is_typed() {
+    assert_eq!(
+        FcmError::from_status(401, "INVALID_KEY".to_owned()),
+        FcmError::Unauthorized("INVALID_KEY".to_owned())
+    );
+    assert_eq!(
+        FcmError::from_status(400, "bad json".to_owned()),
+        FcmError::BadRequest("bad json".to_owned())
+    );
+    assert_eq!(
+        FcmError::from_status(403, String::new()),
+        FcmError::Forbidden(String::new())
+    );
+    assert_eq!(
+        FcmError::from_status(503, String::new()),
+        FcmError::Status {
+            status: 503,
+            body: String::new()
+        }
+    );
+}
+
+#[test]
+fn apns_block_is_sent_with_both_apis() {
+    let mut apns = ApnsConfig::new();
+    apns.critical_sound("alarm.caf", 0.8).badge(3);
+    let notification = build_notification! {
+        title = "title";
+        mutable_content = true;
+        apns = apns;
+    };
+    let mut builder = MessageBuilder::new("key", "token");
+    builder.notification(notification);
+    let body = builder.build().unwrap().body;
+
+    let legacy = serde_json::to_value(&body).unwrap();
+    assert_eq!(legacy["apns"]["payload"]["aps"]["badge"], 3);
+    assert_eq!(legacy["notification"].get("apns"), None);
+
+    let v1 = serde_json::to_value(&body.to_v1()[0]).unwrap();
+    let aps = &v1["message"]["apns"]["payload"]["aps"];
+    assert_eq!(
+        aps["sound"],
+        serde_json::json!({ "critical": 1, "name": "alarm.caf", "volume": 0.8f32 })
+    );
+    assert_eq!(aps["badge"], 3);
+    assert_eq!(aps["mutable-content"], 1);
+}
+
+#[test]
+fn client_with_several_ids_sends_to_each() {
+    let client = Client::new_multi("key", vec!["a".to_owned(), "b".to_owned()]).unwrap();
+    let body = serde_json::to_value(&client.message_builder().build().unwrap().body).unwrap();
+    assert_eq!(body["registration_ids"], serde_json::json!(["a", "b"]));
+    assert_eq!(body.get("to"), None);
+
+    let client = Client::new("key", "/topics/all_device").unwrap();
+    let body = serde_json::to_value(&client.message_builder().build().unwrap().body).unwrap();
+    assert_eq!(body["to"], "/topics/all_device");
+}
+
+#[test]
+fn topic_and_condition_targets_are_sent_alone() {
+    let body = serde_json::to_value(
+        &MessageBuilder::new_topic("key", "deals")
+            .build()
+            .unwrap()
+            .body,
+    )
+    .unwrap();
+    assert_eq!(body["to"], "/topics/deals");
+
+    let condition = "'deals' in topics || 'sales' in topics";
+    let message = MessageBuilder::new_condition("key", condition)
+        .build()
+        .unwrap();
+    let body = serde_json::to_value(&message.body).unwrap();
+    assert_eq!(body["condition"], condition);
+    assert_eq!(body.get("to"), None);
+}
+
+#[test]
+fn data_message_has_no_notification() {
+    let client = Client::new("key", "token").unwrap();
+    let message = client
+        .data_message(&serde_json::json!({ "event_type": "added", "order_id": 1 }))
+        .unwrap();
+    let body = serde_json::to_value(&message.body).unwrap();
+
+    assert_eq!(body.get("notification"), None);
+    assert_eq!(body["content_available"], true);
+    assert_eq!(body["data"]["order_id"], 1);
+    assert_eq!(body["to"], "token");
+}
+
+#[test]
+fn notification_only_message_omits_unset_fields() {
+    let notification = build_notification! {
+        title = "title";
+    };
+    let mut builder = MessageBuilder::new("secret", "/topics/all_device");
+    builder.notification(notification.clone());
+    let message = builder.build().unwrap();
+
+    assert_eq!(
+        message.to_json().unwrap(),
+        r#"{"notification":{"title":"title"},"to":"/topics/all_device"}"#
+    );
+    assert!(!message.to_json().unwrap().contains("secret"));
+    assert_eq!(notification.to_json().unwrap(), r#"{"title":"title"}"#);
+}
+
+#[test]
+fn android_vibration_and_light_are_sent_with_v1() {
+    let notification = build_notification! {
+        title = "title";
+        ticker = "AK-47 の価格が下がりました";
+        vibrate_timings = &[Duration::from_millis(0), Duration::from_millis(250), Duration::from_secs(1)];
+        light_settings = LightSettings::new("#00ff00", Duration::from_millis(500), Duration::from_secs(2));
+        default_sound = true;
+    };
+    let mut builder = MessageBuilder::new("key", "token");
+    builder.notification(notification);
+    let body = builder.build().unwrap().body;
+
+    let v1 = serde_json::to_value(&body.to_v1()[0]).unwrap();
+    let android = &v1["message"]["android"]["notification"];
+    assert_eq!(android["ticker"], "AK-47 の価格が下がりました");
+    assert_eq!(
+        android["vibrate_timings"],
+        serde_json::json!(["0s", "0.25s", "1s"])
+    );
+    assert_eq!(
+        android["light_settings"],
+        serde_json::json!({
+            "color": { "red": 0.0, "green": 1.0, "blue": 0.0 },
+            "light_on_duration": "0.5s",
+            "light_off_duration": "2s",
+        })
+    );
+    assert_eq!(android["default_sound"], true);
+    assert_eq!(android.get("default_vibrate_timings"), None);
+
+    // The legacy API has no such fields.
+    let legacy = serde_json::to_value(&body).unwrap();
+    assert_eq!(
+        legacy["notification"],
+        serde_json::json!({ "title": "title" })
+    );
+}
+
+#[test]
+fn light_color_must_be_rrggbb() {
+    let result = build_notification! {
+        try;
+        title = "title";
+        light_settings = LightSettings::new("green", Duration::from_secs(1), Duration::from_secs(1));
+    };
+    assert_eq!(
+        result.unwrap_err(),
+        BuildError::InvalidColor("green".to_owned())
+    );
+}
+
+#[async_std::test]
+async fn access_token_is_fetched_through_the_proxy() {
+    // Plays the proxy, which gets the token request in its absolute form.
+    let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_url = format!("http://{}", proxy.local_addr().unwrap());
+    let request_line = async_std::task::spawn(async move {
+        let (mut stream, _) = proxy.accept().await.unwrap();
+        let mut request = vec![];
+        let mut buf = [0; 4096];
+        while !String::from_utf8_lossy(&request).contains("assertion=") {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        let body = r#"{"access_token":"ya29.token","expires_in":3600}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+        let request = String::from_utf8_lossy(&request).into_owned();
+        request.lines().next().unwrap().to_owned()
+    });
+
+    let private_key = openssl::rsa::Rsa::generate(2048)
+        .unwrap()
+        .private_key_to_pem()
+        .unwrap();
+    let account = ServiceAccount::new(ServiceAccountKey {
+        project_id: "takya".to_owned(),
+        client_email: "notifier@takya.iam.gserviceaccount.com".to_owned(),
+        private_key: String::from_utf8(private_key).unwrap(),
+        token_uri: "http://oauth2.example.com/token".to_owned(),
+    });
+    let http = http_client::build(Some(&proxy_url.parse().unwrap())).unwrap();
+
+    assert_eq!(account.access_token(&http).await.unwrap(), "ya29.token");
+    assert_eq!(
+        request_line.await,
+        "POST http://oauth2.example.com/token HTTP/1.1"
+    );
+}
@@ -0,0 +1,90 @@
+use chrono::Utc;
+use scraper::{Html, Selector};
+
+use takya_notifier::layout;
+use takya_notifier::models::ScrapeState;
+
+fn fingerprint(html: &str) -> String {
+    let dom = Html::parse_document(html);
+    let section = dom
+        .select(&Selector::parse("section").unwrap())
+        .next()
+        .unwrap();
+
+    layout::fingerprint(section)
+}
+
+fn state(layout_hash: &str, item_count: i32) -> ScrapeState {
+    ScrapeState {
+        id: 1,
+        layout_hash: layout_hash.to_owned(),
+        item_count,
+        updated_at: Utc::now().naive_utc(),
+    }
+}
+
+const ONE_ITEM: &str = r##"<section>
+<div class="item"><span>★</span><a href="#">AK-47 | Redline (Field-Tested) #1001</a>
+<p>販売価格: 1,234円</p></div>
+</section>"##;
+
+const TWO_ITEMS: &str = r##"<section>
+<div class="item"><span>★</span><a href="#">AWP | Asiimov (Field-Tested) #2001</a>
+<p>販売価格: 9,800円</p></div>
+<div class="item"><span>★</span><a href="#">M4A4 | Howl (Minimal Wear) #2002</a>
+<p>販売価格: 300,000円</p></div>
+</section>"##;
+
+const REDESIGNED: &str = r##"<section>
+<ul class="items"><li class="card"><h3>AK-47 | Redline (Field-Tested) #1001</h3>
+<span class="price">1,234円</span></li></ul>
+</section>"##;
+
+#[test]
+fn fingerprint_ignores_the_items_and_their_number() {
+    assert_eq!(fingerprint(ONE_ITEM), fingerprint(TWO_ITEMS));
+    assert_eq!(fingerprint(ONE_ITEM).len(), 16);
+}
+
+#[test]
+fn fingerprint_changes_with_the_markup() {
+    assert_ne!(fingerprint(ONE_ITEM), fingerprint(REDESIGNED));
+}
+
+#[test]
+fn pages_combine_in_order() {
+    let (a, b) = (fingerprint(ONE_ITEM), fingerprint(REDESIGNED));
+
+    assert_eq!(layout::combine(std::slice::from_ref(&a)), a);
+    assert_ne!(
+        layout::combine(&[a.clone(), b.clone()]),
+        layout::combine(&[b, a])
+    );
+}
+
+#[test]
+fn redesign_needs_a_new_layout_and_far_fewer_items() {
+    let previous = state("0123456789abcdef", 100);
+
+    assert!(layout::looks_redesigned(
+        Some(&previous),
+        "fedcba9876543210",
+        10,
+        0.5
+    ));
+    // The same layout with few items is a sell-out, and a new layout with most items a tweak.
+    assert!(!layout::looks_redesigned(
+        Some(&previous),
+        "0123456789abcdef",
+        10,
+        0.5
+    ));
+    assert!(!layout::looks_redesigned(
+        Some(&previous),
+        "fedcba9876543210",
+        90,
+        0.5
+    ));
+    // The first run has nothing to compare with.
+    assert!(!layout::looks_redesigned(None, "fedcba9876543210", 0, 0.5));
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use takya_notifier::catalog::{self, Messages};
+use takya_notifier::config::{Lang, Sounds};
+use takya_notifier::digest::DigestBuilder;
+use takya_notifier::events::ItemEvent;
+use takya_notifier::fcm::Priority;
+use takya_notifier::messages;
+use takya_notifier::models::{Exterior, Item};
+use takya_notifier::notifier::{self, NotificationEvent, DEFAULT_HIGH_PRIORITY};
+
+fn price_changed() -> ItemEvent {
+    ItemEvent::PriceChanged {
+        item: Item {
+            order_id: 1,
+            name: "AK-47".to_owned(),
+            kind: Some("Redline".to_owned()),
+            exterior: Some(Exterior::FT),
+            price: 900,
+            has_sold: false,
+            is_stattrak: false,
+            avg_price_7d: None,
+            phase: None,
+            is_souvenir: false,
+            float_value: None,
+            nametag: None,
+        },
+        old_price: 1000,
+        new_price: 900,
+        image_url: None,
+    }
+}
+
+#[test]
+fn texts_follow_the_language() {
+    let ja = messages::item_event(&price_changed(), Lang::Ja, false);
+    assert_eq!(
+        ja.title(),
+        Some("AK-47 | Redline (Field-Tested) の価格が変更されました")
+    );
+    assert_eq!(ja.body(), Some("1,000 円 → 900 円 (-100 円, -10.0%)"));
+
+    let en = messages::item_event(&price_changed(), Lang::En, false);
+    assert_eq!(
+        en.title(),
+        Some("The price of AK-47 | Redline (Field-Tested) changed")
+    );
+    assert_eq!(en.body(), Some("1,000 yen → 900 yen (-100 yen, -10.0%)"));
+}
+
+#[test]
+fn price_changes_show_the_difference_and_the_percentage() {
+    let ja = Messages::for_lang(Lang::Ja);
+    assert_eq!(
+        ja.price_change(12000, 9500),
+        "12,000 円 → 9,500 円 (-2,500 円, -20.8%)"
+    );
+    assert_eq!(
+        ja.price_change(999, 1_234_567),
+        "999 円 → 1,234,567 円 (+1,233,568 円, +123480.3%)"
+    );
+    assert_eq!(ja.price_delta(0), "+0 円");
+    assert_eq!(catalog::format_yen(-1000), "-1,000");
+    assert_eq!(catalog::format_percent_change(0, 500), "+∞");
+}
+
+#[test]
+fn only_item_notifications_collapse() {
+    let event = NotificationEvent::Item(price_changed());
+    assert_eq!(event.collapse_key(), Some("item-1".to_owned()));
+
+    let slow_run = NotificationEvent::SlowRun {
+        elapsed: Duration::from_secs(60),
+        baseline: None,
+    };
+    assert_eq!(slow_run.collapse_key(), None);
+}
+
+#[test]
+fn scrape_failure_alert_carries_the_error() {
+    let event = NotificationEvent::ScrapeFailed {
+        error: "site returned 503".to_owned(),
+    };
+
+    let ja = messages::notification(&event, Lang::Ja, false);
+    assert_eq!(ja.title(), Some("スクレイピングに失敗しました"));
+    assert_eq!(ja.body(), Some("site returned 503"));
+    assert_eq!(event.kind(), "scrape_failed");
+}
+
+#[test]
+fn listing_digest_counts_the_listed_items() {
+    let item = match price_changed() {
+        ItemEvent::PriceChanged { item, .. } => item,
+        _ => unreachable!(),
+    };
+    let mut digest = DigestBuilder::new();
+    digest.push_listed(&item).push_listed(&item);
+
+    let ja = messages::digest(&digest, Lang::Ja, false);
+    assert_eq!(ja.title(), Some("現在出品中のアイテムは 2 件です"));
+    assert_eq!(
+        ja.body(),
+        Some("出品中 2 件\nAK-47 | Redline (Field-Tested)、AK-47 | Redline (Field-Tested)")
+    );
+
+    let listing = NotificationEvent::Listing { item };
+    let en = messages::notification(&listing, Lang::En, false);
+    assert_eq!(en.title(), Some("AK-47 | Redline (Field-Tested) is listed"));
+    assert_eq!(en.body(), Some("It is listed for 900 yen."));
+    assert_eq!(listing.kind(), "listing");
+}
+
+#[test]
+fn price_drops_are_high_priority_by_default() {
+    let high = DEFAULT_HIGH_PRIORITY
+        .iter()
+        .map(|&kind| kind.to_owned())
+        .collect::<Vec<_>>();
+    let drop = NotificationEvent::Item(price_changed());
+    let rise = match price_changed() {
+        ItemEvent::PriceChanged { item, .. } => NotificationEvent::Item(ItemEvent::PriceChanged {
+            item,
+            old_price: 900,
+            new_price: 1000,
+            image_url: None,
+        }),
+        _ => unreachable!(),
+    };
+
+    assert_eq!(notifier::priority(&drop, &high), Priority::High);
+    assert_eq!(notifier::priority(&rise, &high), Priority::Normal);
+    assert_eq!(
+        notifier::priority(&rise, &["price_rise".to_owned()]),
+        Priority::High
+    );
+    assert_eq!(notifier::priority(&drop, &[]), Priority::Normal);
+}
+
+#[test]
+fn sounds_are_picked_by_kind() {
+    let drop = NotificationEvent::Item(price_changed());
+    let sold = NotificationEvent::UnknownSold {
+        order_id: 1,
+        price: 1000,
+    };
+    assert_eq!(notifier::sound(&drop, &Sounds::default()), Some("default"));
+
+    let mut sounds = Sounds::default();
+    sounds
+        .by_kind
+        .insert("price_drop".to_owned(), Some("alarm.wav".to_owned()));
+    sounds.by_kind.insert("unknown_sold".to_owned(), None);
+    assert_eq!(notifier::sound(&drop, &sounds), Some("alarm.wav"));
+    assert_eq!(notifier::sound(&sold, &sounds), None);
+
+    sounds.default = None;
+    let added = NotificationEvent::Item(ItemEvent::Added {
+        item: price_changed().item().clone(),
+    });
+    assert_eq!(notifier::sound(&added, &sounds), None);
+}
+
+#[test]
+fn digest_lists_the_biggest_drops_and_only_counts_minor_changes() {
+    let change = |name: &str, old_price, new_price| match price_changed() {
+        ItemEvent::PriceChanged { mut item, .. } => {
+            item.name = name.to_owned();
+            ItemEvent::PriceChanged {
+                item,
+                old_price,
+                new_price,
+                image_url: None,
+            }
+        }
+        _ => unreachable!(),
+    };
+    let mut digest = DigestBuilder::with_min_price_delta(10);
+    digest
+        .push(&change("AK-47", 1000, 900))
+        .push(&change("AWP", 1000, 995))
+        .push(&change("M4A4", 1000, 500))
+        .push(&change("USP-S", 1000, 1200));
+
+    assert_eq!(digest.total(), 3);
+    assert_eq!(digest.minor_price_changes, 1);
+    let ja = messages::digest(&digest, Lang::Ja, false);
+    assert_eq!(ja.title(), Some("3 件の変更がありました"));
+    assert_eq!(
+        ja.body(),
+        Some(
+            "値下げ 2 件、値上げ 1 件、小幅な価格変更 1 件\n\
+             AK-47 | Redline (Field-Tested)、M4A4 | Redline (Field-Tested)、USP-S | Redline (Field-Tested)\n\
+             値下げ幅の大きい順: M4A4 | Redline (Field-Tested) (-500 円)、AK-47 | Redline (Field-Tested) (-100 円)"
+        )
+    );
+}
@@ -0,0 +1,72 @@
+use takya_notifier::models::{Exterior, Item};
+
+fn item(kind: Option<&str>, exterior: Option<Exterior>, is_stattrak: bool) -> Item {
+    Item {
+        order_id: 1,
+        name: "AK-47".to_owned(),
+        kind: kind.map(str::to_owned),
+        exterior,
+        price: 1000,
+        has_sold: false,
+        is_stattrak,
+        avg_price_7d: None,
+        phase: None,
+        is_souvenir: false,
+        float_value: None,
+        nametag: None,
+    }
+}
+
+#[test]
+fn full_name_includes_exterior() {
+    let item = item(Some("Redline"), Some(Exterior::FT), false);
+    assert_eq!(item.full_name(), "AK-47 | Redline (Field-Tested)");
+}
+
+#[test]
+fn full_name_includes_stattrak() {
+    let item = item(Some("Redline"), Some(Exterior::FT), true);
+    assert_eq!(item.full_name(), "StatTrak™ AK-47 | Redline (Field-Tested)");
+}
+
+#[test]
+fn full_name_of_vanilla() {
+    let item = item(None, None, false);
+    assert_eq!(item.full_name(), "AK-47 | Vanilla");
+}
+
+#[test]
+fn full_name_without_exterior() {
+    let item = item(Some("Redline"), None, false);
+    assert_eq!(item.full_name(), "AK-47 | Redline");
+}
+
+const EXTERIORS: &[(Exterior, &str, &str)] = &[
+    (Exterior::FN, "Factory New", "FN"),
+    (Exterior::MW, "Minimal Wear", "MW"),
+    (Exterior::FT, "Field-Tested", "FT"),
+    (Exterior::WW, "Well-Worn", "WW"),
+    (Exterior::BS, "Battle-Scarred", "BS"),
+];
+
+#[test]
+fn exterior_parses_full_names() {
+    for (exterior, full, _) in EXTERIORS {
+        assert_eq!(&full.parse::<Exterior>().unwrap(), exterior);
+    }
+}
+
+#[test]
+fn exterior_parses_abbreviations() {
+    for (exterior, _, abbreviation) in EXTERIORS {
+        assert_eq!(&abbreviation.parse::<Exterior>().unwrap(), exterior);
+    }
+}
+
+#[test]
+fn exterior_displays_the_full_name() {
+    for (exterior, full, _) in EXTERIORS {
+        assert_eq!(exterior.to_string(), *full);
+        assert_eq!(exterior.as_ref(), *full);
+    }
+}
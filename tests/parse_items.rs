@@ -0,0 +1,190 @@
+use scraper::{Html, Selector};
+
+use takya_notifier::models::Exterior;
+use takya_notifier::parsers::{
+    parse_items, ItemSection, ItemSectionIter, ParseError, ParserConfig, SectionError,
+};
+use takya_notifier::scrape::{decode_body, parse_document, DEFAULT_SELECTOR};
+
+fn fixture(name: &str) -> Html {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    Html::parse_document(&std::fs::read_to_string(&path).unwrap())
+}
+
+// Feeds `tests/fixtures/{name}` through the same selector as the site, straight into `parse_items`.
+fn parse_fixture(name: &str) -> (Vec<ItemSection>, Vec<SectionError>) {
+    let dom = fixture(name);
+    let selector = Selector::parse(DEFAULT_SELECTOR).unwrap();
+    let section = dom.select(&selector).next().unwrap();
+
+    parse_items(section.text(), &ParserConfig::default())
+}
+
+#[test]
+fn normal_items() {
+    let (items, errors) = parse_fixture("normal.html");
+    assert!(errors.is_empty());
+    assert_eq!(items.len(), 2);
+
+    assert_eq!(items[0].order_id, 1001);
+    assert_eq!(items[0].price, 1234);
+    let redline = items[0].item.as_ref().unwrap();
+    assert_eq!(redline.name, "AK-47");
+    assert_eq!(redline.kind.as_deref(), Some("Redline"));
+    assert_eq!(redline.exterior, Some(Exterior::FT));
+    assert!(!redline.is_stattrak);
+
+    let doppler = items[1].item.as_ref().unwrap();
+    assert_eq!(doppler.phase.as_deref(), Some("Phase 2"));
+    assert_eq!(doppler.price, 120_000);
+}
+
+#[test]
+fn vanilla_item() {
+    let (items, errors) = parse_fixture("vanilla.html");
+    assert!(errors.is_empty());
+
+    let karambit = items[0].item.as_ref().unwrap();
+    assert_eq!(karambit.name, "Karambit");
+    assert_eq!(karambit.kind, None);
+    assert_eq!(karambit.exterior, None);
+    assert_eq!(karambit.price, 85_000);
+}
+
+#[test]
+fn sold_item_has_only_its_order_id_and_price() {
+    let (items, errors) = parse_fixture("sold.html");
+    assert!(errors.is_empty());
+
+    assert_eq!(items.len(), 1);
+    assert!(items[0].item.is_none());
+    assert_eq!(items[0].order_id, 3001);
+    assert_eq!(items[0].price, 4500);
+}
+
+#[test]
+fn stattrak_and_souvenir_items() {
+    let (items, errors) = parse_fixture("stattrak.html");
+    assert!(errors.is_empty());
+
+    let asiimov = items[0].item.as_ref().unwrap();
+    assert_eq!(asiimov.name, "AWP");
+    assert!(asiimov.is_stattrak);
+    assert!(!asiimov.is_souvenir);
+
+    let dragon_lore = items[1].item.as_ref().unwrap();
+    assert!(dragon_lore.is_souvenir);
+    assert_eq!(dragon_lore.price, 1_500_000);
+}
+
+#[test]
+fn malformed_section_is_dropped() {
+    let (items, errors) = parse_fixture("malformed.html");
+
+    let order_ids = items.iter().map(|item| item.order_id).collect::<Vec<_>>();
+    assert_eq!(order_ids, vec![5001, 5003]);
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0].source, ParseError::InvalidItemFormat(_)));
+}
+
+#[test]
+fn missing_price_line_is_dropped() {
+    let (items, errors) = parse_fixture("missing_price.html");
+
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].order_id, 6001);
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        errors[0].source,
+        ParseError::IncompleteSection("no price line found")
+    ));
+}
+
+#[test]
+fn shift_jis_page_is_decoded() {
+    let path = format!(
+        "{}/tests/fixtures/shift_jis.html",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let bytes = std::fs::read(&path).unwrap();
+    assert!(String::from_utf8(bytes.clone()).is_err());
+
+    // From the `<meta>` of the page alone, and from the header, which is trusted over it.
+    for content_type in &[
+        None,
+        Some("text/html; charset=Shift_JIS"),
+        Some("text/html; charset=\"sjis\""),
+    ] {
+        let html = decode_body(&bytes, *content_type);
+        let (items, errors) = parse_document(
+            &html,
+            "http://example.com/",
+            &[DEFAULT_SELECTOR.to_owned()],
+            &ParserConfig::default(),
+        )
+        .unwrap();
+
+        assert!(errors.is_empty(), "{:?}", content_type);
+        let prices = items.iter().map(|item| item.price).collect::<Vec<_>>();
+        assert_eq!(prices, vec![1234, 120_000], "{:?}", content_type);
+    }
+}
+
+#[test]
+fn page_without_charset_is_read_as_utf8() {
+    let html = "<p>★ 販売価格</p>";
+    assert_eq!(decode_body(html.as_bytes(), None), html);
+    assert_eq!(decode_body(html.as_bytes(), Some("text/html")), html);
+}
+
+#[test]
+fn iterator_yields_what_parse_items_collects() {
+    let dom = fixture("malformed.html");
+    let selector = Selector::parse(DEFAULT_SELECTOR).unwrap();
+    let section = dom.select(&selector).next().unwrap();
+    let config = ParserConfig::default();
+
+    let yielded = ItemSectionIter::new(section.text(), &config)
+        .map(|section| match section {
+            Ok(item) => Ok(item.order_id),
+            Err(e) => Err(e.line),
+        })
+        .collect::<Vec<_>>();
+
+    let (items, errors) = parse_fixture("malformed.html");
+    let order_ids = yielded.iter().filter_map(|s| s.ok()).collect::<Vec<_>>();
+    let error_lines = yielded.iter().filter_map(|s| s.err()).collect::<Vec<_>>();
+    assert_eq!(
+        order_ids,
+        items.iter().map(|item| item.order_id).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        error_lines,
+        errors.iter().map(|e| e.line).collect::<Vec<_>>()
+    );
+    // The corrupted section comes out between the two good ones, where it is on the page.
+    assert!(yielded[1].is_err());
+}
+
+#[test]
+fn iterator_only_reads_as_far_as_it_is_asked() {
+    // An endless listing, which collecting everything up front would never get through.
+    let lines = [
+        "★",
+        "AK-47 | Redline (Field-Tested) #1",
+        "",
+        "販売価格: 1,000円",
+    ]
+    .iter()
+    .copied()
+    .cycle();
+    let config = ParserConfig::default();
+
+    let sections = ItemSectionIter::new(lines, &config)
+        .take(3)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(sections.len(), 3);
+    assert!(sections.iter().all(|section| section.price == 1000));
+}
@@ -0,0 +1,355 @@
+use takya_notifier::models::{Exterior, Item};
+use takya_notifier::parsers::{
+    normalize_name, normalize_width, parse_item_section, ParseError, ParserConfig,
+};
+use takya_notifier::scrape::parse_document;
+use takya_notifier::Error;
+
+#[test]
+fn malformed_price_line_is_an_error() {
+    let result = parse_item_section(
+        "AK-47 | Redline (Field-Tested) #1234",
+        "価格未定",
+        &ParserConfig::default(),
+    );
+
+    match result {
+        Err(ParseError::InvalidPriceFormat(line)) => assert_eq!(line, "価格未定"),
+        other => panic!("expected InvalidPriceFormat, got {:?}", other),
+    }
+}
+
+#[test]
+fn phase_is_parsed_from_the_suffix() {
+    let section = parse_item_section(
+        "Karambit | Doppler (Factory New) - Phase 2 #1234",
+        "販売価格: 120,000円",
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    let item = section.item.unwrap();
+
+    assert_eq!(item.kind.as_deref(), Some("Doppler"));
+    assert_eq!(item.exterior, Some(Exterior::FN));
+    assert_eq!(item.phase.as_deref(), Some("Phase 2"));
+    assert_eq!(section.order_id, 1234);
+    assert_eq!(
+        format!("{:e}", item),
+        "Karambit | Doppler (Factory New) - Phase 2"
+    );
+}
+
+#[test]
+fn phase_is_split_out_of_the_kind() {
+    let section = parse_item_section(
+        "Butterfly Knife | Gamma Doppler Emerald (Minimal Wear) #42",
+        "販売価格: 300,000円",
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    let item = section.item.unwrap();
+
+    assert_eq!(item.kind.as_deref(), Some("Gamma Doppler"));
+    assert_eq!(item.phase.as_deref(), Some("Emerald"));
+}
+
+#[test]
+fn non_doppler_items_have_no_phase() {
+    let section = parse_item_section(
+        "AK-47 | Redline (Field-Tested) #1234",
+        "販売価格: 1,234円",
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    let item = section.item.unwrap();
+
+    assert_eq!(item.kind.as_deref(), Some("Redline"));
+    assert_eq!(item.phase, None);
+}
+
+fn parse_name(line: &str) -> Item {
+    parse_item_section(line, "販売価格: 1,234円", &ParserConfig::default())
+        .unwrap()
+        .item
+        .unwrap()
+}
+
+#[test]
+fn stattrak_with_trademark_is_stripped() {
+    let item = parse_name("StatTrak™ AWP | Asiimov (Field-Tested) #1");
+    assert_eq!(item.name, "AWP");
+    assert!(item.is_stattrak);
+    assert!(!item.is_souvenir);
+}
+
+#[test]
+fn stattrak_without_trademark_is_stripped() {
+    let item = parse_name("StatTrak AWP | Asiimov (Field-Tested) #1");
+    assert_eq!(item.name, "AWP");
+    assert!(item.is_stattrak);
+}
+
+#[test]
+fn souvenir_is_stripped() {
+    let item = parse_name("Souvenir AK-47 | Safari Mesh (Battle-Scarred) #1");
+    assert_eq!(item.name, "AK-47");
+    assert!(item.is_souvenir);
+    assert!(!item.is_stattrak);
+    assert_eq!(
+        item.full_name(),
+        "Souvenir AK-47 | Safari Mesh (Battle-Scarred)"
+    );
+}
+
+#[test]
+fn stattrak_vanilla_is_stripped() {
+    let item = parse_name("StatTrak™ Karambit (Vanilla) #1");
+    assert_eq!(item.name, "Karambit");
+    assert!(item.is_stattrak);
+    assert_eq!(item.kind, None);
+}
+
+#[test]
+fn corrupted_sections_are_returned_with_their_line() {
+    let html = concat!(
+        "<section>",
+        "<span>★</span><a>AK-47 | Redline (Field-Tested) #1</a>\n<p>販売価格: 1,234円</p>",
+        "<span>★</span><a>broken</a>\n<p>販売価格: 1,000円</p>",
+        "</section>",
+    );
+    let (items, errors) = parse_document(
+        html,
+        "http://example.com/",
+        &["section".to_owned()],
+        &ParserConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(items.len(), 1);
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0].source, ParseError::InvalidItemFormat(_)));
+}
+
+#[test]
+fn price_above_i32_is_parsed() {
+    let section = parse_item_section(
+        "Karambit | Doppler (Factory New) #1",
+        "販売価格: 3,000,000,000円",
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(section.price, 3_000_000_000);
+}
+
+fn parse_price(price_line: &str) -> i64 {
+    parse_item_section(
+        "AK-47 | Redline (Field-Tested) #1",
+        price_line,
+        &ParserConfig::default(),
+    )
+    .unwrap()
+    .price
+}
+
+#[test]
+fn full_width_price_is_parsed() {
+    assert_eq!(parse_price("販売価格: １，２３４円"), 1234);
+    assert_eq!(parse_price("販売価格：１２，３４５円"), 12345);
+}
+
+#[test]
+fn ascii_price_is_parsed() {
+    assert_eq!(parse_price("販売価格: 1,234円"), 1234);
+}
+
+#[test]
+fn mixed_width_price_is_parsed() {
+    assert_eq!(parse_price("販売価格: 1，２34円"), 1234);
+    assert_eq!(parse_price("販売価格:　１,234円"), 1234);
+}
+
+#[test]
+fn normalize_width_keeps_other_characters() {
+    assert_eq!(
+        normalize_width("販売価格：１，２３４円"),
+        "販売価格:1,234円"
+    );
+}
+
+#[test]
+fn float_is_parsed_when_listed() {
+    let item = parse_name("AK-47 | Redline (Field-Tested, 0.2341) #1");
+    assert_eq!(item.exterior, Some(Exterior::FT));
+    assert_eq!(item.float_value, Some(0.2341));
+    assert_eq!(item.full_name(), "AK-47 | Redline (Field-Tested, 0.2341)");
+}
+
+#[test]
+fn float_is_none_when_not_listed() {
+    let item = parse_name("AK-47 | Redline (Field-Tested) #1");
+    assert_eq!(item.float_value, None);
+}
+
+#[test]
+fn nametag_is_split_off_the_name() {
+    let item = parse_name("AK-47 | Redline \"My Rifle\" (Field-Tested) #1");
+    assert_eq!(item.name, "AK-47");
+    assert_eq!(item.kind.as_deref(), Some("Redline"));
+    assert_eq!(item.nametag.as_deref(), Some("My Rifle"));
+    assert_eq!(item.to_string(), "AK-47 | Redline \"My Rifle\"");
+
+    let vanilla = parse_name("Karambit “Pointy” (Vanilla) #2");
+    assert_eq!(vanilla.name, "Karambit");
+    assert_eq!(vanilla.nametag.as_deref(), Some("Pointy"));
+}
+
+#[test]
+fn nametag_is_none_without_quotes() {
+    let item = parse_name("AK-47 | Redline (Field-Tested) #1");
+    assert_eq!(item.nametag, None);
+
+    let vanilla = parse_name("Karambit (Vanilla) #2");
+    assert_eq!(vanilla.nametag, None);
+
+    let sold = parse_item_section(
+        "(売約済み) #3",
+        "販売価格: 1,234円",
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    assert!(sold.item.is_none());
+    assert_eq!(sold.order_id, 3);
+}
+
+#[test]
+fn weapon_names_with_digits_are_parsed() {
+    let p250 = parse_name("P250 | Asiimov (Field-Tested) #1");
+    assert_eq!(p250.name, "P250");
+    assert_eq!(p250.kind.as_deref(), Some("Asiimov"));
+
+    let m4a1s = parse_name("StatTrak™ M4A1-S | Hyper Beast (Minimal Wear) #2");
+    assert_eq!(m4a1s.name, "M4A1-S");
+    assert_eq!(m4a1s.kind.as_deref(), Some("Hyper Beast"));
+    assert_eq!(m4a1s.exterior, Some(Exterior::MW));
+    assert!(m4a1s.is_stattrak);
+}
+
+#[test]
+fn names_with_ampersands_and_accents_are_parsed() {
+    let glove = parse_name("Sport Gloves | Black & Tan (Field-Tested) #3");
+    assert_eq!(glove.kind.as_deref(), Some("Black & Tan"));
+
+    let vanilla = parse_name("Navaja Knife & Co (Vanilla) #4");
+    assert_eq!(vanilla.name, "Navaja Knife & Co");
+
+    let accented = parse_name("Desert Eagle | Mecha Indústries (Well-Worn) #5");
+    assert_eq!(accented.name, "Desert Eagle");
+    assert_eq!(accented.kind.as_deref(), Some("Mecha Indústries"));
+}
+
+#[test]
+fn whitespace_and_dashes_are_normalized() {
+    let item = parse_name("AK-47\u{a0} |  Redline  (Field\u{2013}Tested) #6");
+    assert_eq!(item.name, "AK-47");
+    assert_eq!(item.kind.as_deref(), Some("Redline"));
+    assert_eq!(item.exterior, Some(Exterior::FT));
+
+    assert_eq!(
+        normalize_name("  M4A1\u{2010}S | Hyper\u{3000}Beast "),
+        "M4A1-S | Hyper Beast"
+    );
+}
+
+#[test]
+fn real_skin_names_parse_back_to_themselves() {
+    let corpus = include_str!("fixtures/skin_names.txt");
+    let names = corpus
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    for (order_id, name) in names.enumerate() {
+        let line = format!("{} #{}", name, order_id + 1);
+        let item = match parse_item_section(&line, "販売価格: 1,234円", &ParserConfig::default())
+        {
+            Ok(section) => section.item.unwrap(),
+            Err(e) => panic!("failed to parse `{}`: {}", line, e),
+        };
+        assert_eq!(item.full_name(), name);
+    }
+}
+
+#[test]
+fn kind_with_parentheses_keeps_them() {
+    let item = parse_name("M4A4 | 龍王 (Dragon King) (Field-Tested) #7");
+    assert_eq!(item.kind.as_deref(), Some("龍王 (Dragon King)"));
+    assert_eq!(item.exterior, Some(Exterior::FT));
+}
+
+fn parse_with(html: &str, config: &ParserConfig) -> Vec<i32> {
+    let (items, _) =
+        parse_document(html, "http://example.com/", &["section".to_owned()], config).unwrap();
+    items.iter().map(|item| item.order_id).collect()
+}
+
+#[test]
+fn default_layout_is_unchanged() {
+    let html = concat!(
+        "<section>",
+        "<span>★</span><a>AK-47 | Redline (Field-Tested) #1</a>\n<p>販売価格: 1,000円</p>",
+        "</section>",
+    );
+    assert_eq!(parse_with(html, &ParserConfig::default()), vec![1]);
+}
+
+#[test]
+fn delimiter_and_layout_are_configurable() {
+    let html = concat!(
+        "<section>",
+        "<span>●</span><a>AK-47 | Redline (Field-Tested) #1</a><p>販売価格: 1,000円</p>",
+        "</section>",
+    );
+    let config = ParserConfig {
+        section_delimiter: "●".to_owned(),
+        blank_line_before_price: false,
+        ..ParserConfig::default()
+    };
+    assert_eq!(parse_with(html, &config), vec![1]);
+}
+
+#[test]
+fn scanning_finds_the_price_past_extra_lines() {
+    let html = concat!(
+        "<section>",
+        "<span>★</span>\n<a>AK-47 | Redline (Field-Tested) #1</a>\n<i>new!</i>\n<p>販売価格: 1,000円</p>",
+        "<span>★</span><a>AWP | Asiimov (Field-Tested) #2</a>",
+        "<span>★</span><a>M4A4 | Howl (Minimal Wear) #3</a>\n<p>販売価格: 90,000円</p>",
+        "</section>",
+    );
+    let config = ParserConfig {
+        scan_for_price: true,
+        ..ParserConfig::default()
+    };
+    // The second section has no price, and must not take the one of the third.
+    assert_eq!(parse_with(html, &config), vec![1, 3]);
+    assert_eq!(
+        parse_with(html, &ParserConfig::default()),
+        Vec::<i32>::new()
+    );
+}
+
+#[test]
+fn page_without_the_section_names_the_selectors() {
+    let html = "<html><body><h1>503 Service Unavailable</h1></body></html>";
+    let selectors = ["div.main > section".to_owned(), "section".to_owned()];
+    match parse_document(
+        html,
+        "http://example.com/",
+        &selectors,
+        &ParserConfig::default(),
+    ) {
+        Err(Error::SelectorNoMatch(selector)) => {
+            assert_eq!(selector, "div.main > section, section")
+        }
+        other => panic!("expected SelectorNoMatch, got {:?}", other),
+    }
+}
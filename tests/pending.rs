@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use takya_notifier::digest::DigestBuilder;
+use takya_notifier::events::ItemEvent;
+use takya_notifier::models::{Exterior, Item};
+use takya_notifier::notifier::NotificationEvent;
+use takya_notifier::pending;
+
+fn item() -> Item {
+    Item {
+        order_id: 1,
+        name: "AK-47".to_owned(),
+        kind: Some("Redline".to_owned()),
+        exterior: Some(Exterior::FT),
+        price: 900,
+        has_sold: false,
+        is_stattrak: true,
+        avg_price_7d: Some(950),
+        phase: None,
+        is_souvenir: false,
+        float_value: Some(0.25),
+        nametag: None,
+    }
+}
+
+// Compared by their Debug output, since the events aren't PartialEq.
+fn round_trip(event: NotificationEvent) {
+    let json = pending::encode(&event).unwrap();
+    let decoded = pending::decode(&json).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", event), "{}", json);
+}
+
+#[test]
+fn pending_notifications_read_back_as_they_were() {
+    let price_changed = ItemEvent::PriceChanged {
+        item: item(),
+        old_price: 1000,
+        new_price: 900,
+        image_url: None,
+    };
+    let mut digest = DigestBuilder::with_min_price_delta(10);
+    digest
+        .push(&price_changed)
+        .push(&ItemEvent::Added { item: item() });
+
+    round_trip(NotificationEvent::Item(price_changed));
+    round_trip(NotificationEvent::Digest(digest));
+    round_trip(NotificationEvent::SlowRun {
+        elapsed: Duration::from_millis(31_500),
+        baseline: None,
+    });
+    round_trip(NotificationEvent::TargetReached {
+        item: item(),
+        target_price: 1000,
+    });
+}
+
+#[test]
+fn unreadable_notification_is_an_error() {
+    assert!(pending::decode(r#"{"Removed":{}}"#).is_err());
+}
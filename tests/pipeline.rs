@@ -0,0 +1,259 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use futures::future::{FutureExt, LocalBoxFuture};
+
+use takya_notifier::config::{Config, NotifyMode};
+use takya_notifier::diff::DiffOptions;
+use takya_notifier::events::ItemEvent;
+use takya_notifier::models::{Exterior, Item};
+use takya_notifier::notifier::{
+    self, NotificationEvent, Outgoing, RateLimited, RecordingSink, Sink, SinkKind,
+};
+use takya_notifier::parsers::ParserConfig;
+use takya_notifier::scrape::{parse_document, DEFAULT_SELECTOR};
+
+fn db_item(order_id: i32, name: &str, kind: &str, exterior: Exterior, price: i64) -> Item {
+    Item {
+        order_id,
+        name: name.to_owned(),
+        kind: Some(kind.to_owned()),
+        exterior: Some(exterior),
+        price,
+        has_sold: false,
+        is_stattrak: false,
+        avg_price_7d: None,
+        phase: None,
+        is_souvenir: false,
+        float_value: None,
+        nametag: None,
+    }
+}
+
+// The config of a run from the environment, with only the variables it requires set.
+fn config() -> Config {
+    std::env::set_var("DATABASE_URL", "mysql://localhost/takya");
+    std::env::set_var("WEBHOOK_URL", "http://localhost/hook");
+    std::env::set_var("USER_AGENT", "takya_notifier tests");
+    Config::from_env().unwrap()
+}
+
+fn run_fixture(name: &str, db_items: Vec<Item>) -> Vec<NotificationEvent> {
+    run_fixture_with(&config(), name, db_items)
+}
+
+// Parses `tests/fixtures/{name}`, diffs it against `db_items`, and plans and sends the
+// notifications as a run with `config` does, through a recording sink, returning what the sink
+// received.
+fn run_fixture_with(config: &Config, name: &str, db_items: Vec<Item>) -> Vec<NotificationEvent> {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let html = std::fs::read_to_string(&path).unwrap();
+    let (sections, errors) = parse_document(
+        &html,
+        "http://steamrmt.com/skinbuy.html",
+        &[DEFAULT_SELECTOR.to_owned()],
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    assert!(errors.is_empty());
+
+    let diff = takya_notifier::diff(sections, db_items, &HashMap::new(), &DiffOptions::default());
+    let notifications = takya_notifier::plan_notifications(config, &diff, &[])
+        .into_iter()
+        .map(Outgoing::new)
+        .collect::<Vec<_>>();
+
+    let sink = RecordingSink::new();
+    let sent = async_std::task::block_on(takya_notifier::deliver(
+        config,
+        &[(SinkKind::Webhook, &sink as &dyn Sink)],
+        &notifications,
+    ));
+    assert_eq!(sent.succeeded, notifications.len());
+    assert!(sent.delivered.iter().all(|&delivered| delivered));
+
+    sink.events()
+}
+
+// Forwards to another sink, keeping when the last of its sends finished.
+struct Timed<'a> {
+    sink: &'a dyn Sink,
+    finished: Cell<Option<Instant>>,
+}
+
+impl<'a> Timed<'a> {
+    fn new(sink: &'a dyn Sink) -> Self {
+        Timed {
+            sink,
+            finished: Cell::new(None),
+        }
+    }
+}
+
+impl Sink for Timed<'_> {
+    fn send<'a>(&'a self, event: &'a NotificationEvent) -> LocalBoxFuture<'a, anyhow::Result<()>> {
+        async move {
+            let result = self.sink.send(event).await;
+            self.finished.set(Some(Instant::now()));
+            result
+        }
+        .boxed_local()
+    }
+}
+
+// Each received event as its kind and order id, in the order they were sent.
+fn kinds(events: &[NotificationEvent]) -> Vec<(&'static str, i32)> {
+    events
+        .iter()
+        .map(|event| match event {
+            NotificationEvent::Item(ItemEvent::Added { item })
+            | NotificationEvent::Item(ItemEvent::PriceChanged { item, .. })
+            | NotificationEvent::Item(ItemEvent::Sold { item })
+            | NotificationEvent::Item(ItemEvent::Deleted { item })
+            | NotificationEvent::Item(ItemEvent::Relisted { item })
+            | NotificationEvent::Item(ItemEvent::Trend { item, .. }) => {
+                (event.kind(), item.order_id)
+            }
+            other => panic!("unexpected event {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn empty_database_adds_every_item() {
+    let events = run_fixture("normal.html", vec![]);
+
+    assert_eq!(kinds(&events), vec![("added", 1001), ("added", 1002)]);
+}
+
+#[test]
+fn changes_against_the_database_are_sent_in_order() {
+    let db_items = vec![
+        db_item(1001, "AK-47", "Redline", Exterior::FT, 1500),
+        db_item(1002, "Karambit", "Doppler", Exterior::FN, 120_000),
+        db_item(1003, "M4A4", "Howl", Exterior::MW, 300_000),
+    ];
+    let events = run_fixture("normal.html", db_items);
+
+    assert_eq!(
+        kinds(&events),
+        vec![("price_changed", 1001), ("deleted", 1003)]
+    );
+    match &events[0] {
+        NotificationEvent::Item(ItemEvent::PriceChanged {
+            old_price,
+            new_price,
+            ..
+        }) => assert_eq!((*old_price, *new_price), (1500, 1234)),
+        other => panic!("expected a price change, got {:?}", other),
+    }
+}
+
+#[test]
+fn sold_marker_sells_the_tracked_item() {
+    let db_items = vec![db_item(3001, "AWP", "Asiimov", Exterior::FT, 4500)];
+    let events = run_fixture("sold.html", db_items);
+
+    assert_eq!(kinds(&events), vec![("sold", 3001)]);
+}
+
+#[test]
+fn digest_mode_sends_one_digest_of_every_change() {
+    let mut config = config();
+    config.notify_mode = NotifyMode::Digest;
+    let db_items = vec![
+        db_item(1001, "AK-47", "Redline", Exterior::FT, 1500),
+        db_item(1002, "Karambit", "Doppler", Exterior::FN, 120_000),
+        db_item(1003, "M4A4", "Howl", Exterior::MW, 300_000),
+    ];
+    let events = run_fixture_with(&config, "normal.html", db_items);
+
+    match events.as_slice() {
+        [NotificationEvent::Digest(digest)] => {
+            assert_eq!(digest.total(), 2);
+            assert_eq!(digest.price_drops, 1);
+        }
+        other => panic!("expected one digest, got {:?}", other),
+    }
+}
+
+#[test]
+fn sink_which_sent_a_notification_is_skipped() {
+    // FCM sent the first two in an earlier run, and Discord the first.
+    let sent_to = [
+        SinkKind::Fcm.bit() | SinkKind::Discord.bit(),
+        SinkKind::Fcm.bit(),
+        0,
+    ];
+    let notifications = (1..=3)
+        .zip(&sent_to)
+        .map(|(order_id, &sent_to)| Outgoing {
+            event: NotificationEvent::UnknownSold {
+                order_id,
+                price: 1000,
+            },
+            sent_to,
+            key: Some(format!("row-{}", order_id)),
+        })
+        .collect::<Vec<_>>();
+    let (fcm, discord) = (RecordingSink::new(), RecordingSink::new());
+    let limited = RateLimited::new(&discord, None);
+
+    let sent = async_std::task::block_on(takya_notifier::deliver(
+        &config(),
+        &[
+            (SinkKind::Fcm, &fcm as &dyn Sink),
+            (SinkKind::Discord, &limited),
+        ],
+        &notifications,
+    ));
+
+    assert_eq!((sent.attempted, sent.succeeded), (3, 3));
+    assert_eq!(fcm.keys(), vec!["row-3"]);
+    // The key of the row goes through the rate limiter too.
+    assert_eq!(discord.keys(), vec!["row-2", "row-3"]);
+    assert_eq!(sent.delivered, vec![true, true, true]);
+    let every_sink = SinkKind::Fcm.bit() | SinkKind::Discord.bit();
+    assert_eq!(sent.sent_to, vec![every_sink; 3]);
+}
+
+#[test]
+fn rate_limited_sink_spaces_the_sends() {
+    let notifications = (1..=5)
+        .map(|order_id| NotificationEvent::UnknownSold {
+            order_id,
+            price: 1000,
+        })
+        .collect::<Vec<_>>();
+    let sink = RecordingSink::new();
+    let limited = RateLimited::new(&sink, Some(20.0));
+    let unlimited = RecordingSink::new();
+    let (timed_limited, timed_unlimited) = (Timed::new(&limited), Timed::new(&unlimited));
+
+    let start = Instant::now();
+    let results = async_std::task::block_on(notifier::dispatch(
+        &[&timed_limited as &dyn Sink, &timed_unlimited],
+        &notifications,
+        2,
+    ));
+
+    assert!(results.iter().all(Result::is_ok));
+    // The first send goes at once, and each of the other four waits 50ms after the one before.
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    // The unlimited sink, though it comes after, doesn't wait for the limited one.
+    let limited_finished = timed_limited.finished.get().unwrap();
+    let unlimited_finished = timed_unlimited.finished.get().unwrap();
+    assert!(unlimited_finished < limited_finished);
+    assert!(unlimited_finished - start < Duration::from_millis(50));
+    let order_ids = sink
+        .events()
+        .iter()
+        .map(|event| match event {
+            NotificationEvent::UnknownSold { order_id, .. } => *order_id,
+            other => panic!("unexpected event {:?}", other),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(order_ids, vec![1, 2, 3, 4, 5]);
+    assert_eq!(unlimited.events().len(), 5);
+}
@@ -0,0 +1,155 @@
+use takya_notifier::fcm::{ErrorReason, FcmResponse, SendResult};
+use takya_notifier::models::Token;
+use takya_notifier::tokens::{self, TokenChange};
+
+fn token(id: i32, registration_id: &str) -> Token {
+    Token {
+        id,
+        registration_id: registration_id.to_owned(),
+    }
+}
+
+#[test]
+fn not_registered_token_is_removed() {
+    let tokens = vec![token(1, "alive"), token(2, "dead")];
+    let response: FcmResponse = serde_json::from_str(
+        r#"{
+            "multicast_id": 108,
+            "success": 1,
+            "failure": 1,
+            "canonical_ids": 0,
+            "results": [
+                { "message_id": "1:0408" },
+                { "error": "NotRegistered" }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        tokens::changes(&tokens, &tokens::recipients(&[], &tokens), &[response]),
+        vec![TokenChange::Delete(2)]
+    );
+}
+
+#[test]
+fn canonical_id_replaces_token() {
+    let tokens = vec![token(1, "old")];
+    let response: FcmResponse = serde_json::from_str(
+        r#"{
+            "multicast_id": 108,
+            "success": 1,
+            "failure": 0,
+            "canonical_ids": 1,
+            "results": [
+                { "message_id": "1:2342", "registration_id": "new" }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        tokens::changes(&tokens, &tokens::recipients(&[], &tokens), &[response]),
+        vec![TokenChange::Replace {
+            id: 1,
+            registration_id: "new".to_owned()
+        }]
+    );
+}
+
+#[test]
+fn transient_failure_is_not_delivered() {
+    let response = |results: &str| -> FcmResponse {
+        serde_json::from_str(&format!(r#"{{ "results": {} }}"#, results)).unwrap()
+    };
+
+    assert!(response(r#"[{ "message_id": "1" }, { "error": "NotRegistered" }]"#).is_delivered());
+    assert!(!response(r#"[{ "message_id": "1" }, { "error": "Unavailable" }]"#).is_delivered());
+    assert!(!response(r#"[{ "error": "NotRegistered" }]"#).is_delivered());
+    // A topic send has no per-target results.
+    assert!(response("[]").is_delivered());
+}
+
+#[test]
+fn invalid_v1_token_is_removed() {
+    let tokens = vec![token(1, "alive"), token(2, "invalid")];
+    let response = FcmResponse {
+        results: vec![
+            SendResult {
+                message_id: Some("projects/takya/messages/1".to_owned()),
+                ..SendResult::default()
+            },
+            SendResult {
+                error: ErrorReason::from_v1_body(
+                    r#"{
+                        "error": {
+                            "code": 400,
+                            "message": "The registration token is not a valid FCM registration token",
+                            "status": "INVALID_ARGUMENT",
+                            "details": [{
+                                "@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError",
+                                "errorCode": "INVALID_ARGUMENT"
+                            }]
+                        }
+                    }"#,
+                ),
+                ..SendResult::default()
+            },
+        ],
+        ..FcmResponse::default()
+    };
+
+    assert_eq!(
+        tokens::changes(&tokens, &tokens::recipients(&[], &tokens), &[response]),
+        vec![TokenChange::Delete(2)]
+    );
+}
+
+#[test]
+fn malformed_v1_message_keeps_the_token() {
+    let reason = ErrorReason::from_v1_body(
+        r#"{
+            "error": {
+                "code": 400,
+                "message": "Invalid value at 'message.android.ttl'",
+                "status": "INVALID_ARGUMENT",
+                "details": [{
+                    "@type": "type.googleapis.com/google.rpc.BadRequest",
+                    "fieldViolations": [{ "field": "message.android.ttl" }]
+                }]
+            }
+        }"#,
+    );
+
+    assert!(!reason.unwrap().is_dead_token());
+}
+
+#[test]
+fn env_ids_are_sent_to_before_the_tokens() {
+    let tokens = vec![token(1, "shared"), token(2, "dead")];
+    let env_ids = vec![
+        "/topics/all_device".to_owned(),
+        "env".to_owned(),
+        "shared".to_owned(),
+    ];
+    let recipients = tokens::recipients(&env_ids, &tokens);
+    assert_eq!(recipients, vec!["env", "shared", "dead"]);
+
+    // A dead env id is left to the configuration, only the registered token is deleted.
+    let response: FcmResponse = serde_json::from_str(
+        r#"{
+            "results": [
+                { "error": "NotRegistered" },
+                { "message_id": "1:0408" },
+                { "error": "NotRegistered" }
+            ]
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        tokens::changes(&tokens, &recipients, &[response]),
+        vec![TokenChange::Delete(2)]
+    );
+
+    assert!(tokens::recipients(&env_ids, &[]).is_empty());
+}
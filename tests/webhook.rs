@@ -0,0 +1,322 @@
+use std::sync::{Arc, Mutex};
+
+use async_std::io::prelude::*;
+use async_std::net::TcpListener;
+use serde_json::{json, Value};
+
+use takya_notifier::config::Lang;
+use takya_notifier::events::ItemEvent;
+use takya_notifier::models::{Exterior, Item};
+use takya_notifier::notifier::{
+    GenericWebhook, MatrixSink, NotificationEvent, NtfySink, Sink, TelegramBot,
+};
+
+struct Received {
+    headers: String,
+    body: Value,
+}
+
+// Answers each request with the next status, and records what it received.
+async fn mock_server(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Received>>>) {
+    mock_server_with_bodies(statuses.into_iter().map(|status| (status, "")).collect()).await
+}
+
+// The same as `mock_server`, answering with a body as well.
+async fn mock_server_with_bodies(
+    responses: Vec<(u16, &'static str)>,
+) -> (String, Arc<Mutex<Vec<Received>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(vec![]));
+
+    let log = received.clone();
+    async_std::task::spawn(async move {
+        for (status, response_body) in responses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buf = [0; 4096];
+            let (headers, body) = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                if let Some(parsed) = parse_request(&request) {
+                    break parsed;
+                }
+            };
+
+            // A body which isn't JSON is kept as a string.
+            let body = serde_json::from_slice(&body)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+            log.lock().unwrap().push(Received { headers, body });
+            let response = format!(
+                "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response_body.len(),
+                response_body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    (url, received)
+}
+
+// Splits a complete request into its headers and body, or returns None if more is to come.
+// The body is sent either with a `Content-Length` or chunked.
+fn parse_request(request: &[u8]) -> Option<(String, Vec<u8>)> {
+    let text = String::from_utf8_lossy(request);
+    let end = text.find("\r\n\r\n")?;
+    let headers = text[..end].to_ascii_lowercase();
+    let rest = &request[end + 4..];
+
+    if headers.contains("transfer-encoding: chunked") {
+        let mut body = vec![];
+        let mut rest = rest;
+        loop {
+            let line_end = rest.windows(2).position(|w| w == b"\r\n")?;
+            let size =
+                usize::from_str_radix(std::str::from_utf8(&rest[..line_end]).ok()?, 16).ok()?;
+            if size == 0 {
+                return Some((headers, body));
+            }
+            let chunk = rest.get(line_end + 2..line_end + 2 + size)?;
+            body.extend_from_slice(chunk);
+            rest = rest.get(line_end + 2 + size + 2..)?;
+        }
+    }
+
+    let length = headers
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length:"))
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0);
+    Some((headers, rest.get(..length)?.to_vec()))
+}
+
+fn price_changed() -> NotificationEvent {
+    NotificationEvent::Item(ItemEvent::PriceChanged {
+        item: Item {
+            order_id: 1234,
+            name: "AK-47".to_owned(),
+            kind: Some("Redline".to_owned()),
+            exterior: Some(Exterior::FT),
+            price: 900,
+            has_sold: false,
+            is_stattrak: false,
+            avg_price_7d: None,
+            phase: None,
+            is_souvenir: false,
+            float_value: None,
+            nametag: None,
+        },
+        old_price: 1000,
+        new_price: 900,
+        image_url: None,
+    })
+}
+
+#[async_std::test]
+async fn posts_the_event_as_json_with_headers() {
+    let (url, received) = mock_server(vec![200]).await;
+    let sink = GenericWebhook::new(url, vec![("X-Token", "secret".to_owned())], Lang::En);
+
+    sink.send(&price_changed()).await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert!(received[0]
+        .headers
+        .lines()
+        .any(|line| line == "x-token: secret"));
+    assert_eq!(
+        received[0].body,
+        json!({
+            "event_type": "price_changed",
+            "order_id": 1234,
+            "name": "AK-47",
+            "kind": "Redline",
+            "exterior": "FT",
+            "old_price": 1000,
+            "new_price": 900,
+            "title": "The price of AK-47 | Redline (Field-Tested) changed",
+            "body": "1,000 yen → 900 yen (-100 yen, -10.0%)",
+        })
+    );
+}
+
+#[async_std::test]
+async fn retries_on_server_errors() {
+    let (url, received) = mock_server(vec![503, 200]).await;
+    let sink = GenericWebhook::new(url, vec![], Lang::Ja);
+
+    sink.send(&price_changed()).await.unwrap();
+
+    assert_eq!(received.lock().unwrap().len(), 2);
+}
+
+#[async_std::test]
+async fn telegram_bolds_the_name_and_shows_the_delta() {
+    let (url, received) = mock_server(vec![200]).await;
+    let sink = TelegramBot::new("token", "42", Lang::Ja).with_api_url(url);
+
+    sink.send(&price_changed()).await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert!(received[0]
+        .headers
+        .starts_with("post /hook/bottoken/sendmessage "));
+    assert_eq!(
+        received[0].body,
+        json!({
+            "chat_id": "42",
+            "text": "<b>AK-47 | Redline (Field-Tested)</b> の価格が変更されました\n1,000 円 → 900 円 (-100 円, -10.0%)",
+            "parse_mode": "HTML",
+        })
+    );
+}
+
+#[async_std::test]
+async fn telegram_waits_out_the_rate_limit() {
+    let (url, received) = mock_server_with_bodies(vec![
+        (
+            429,
+            r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 1","parameters":{"retry_after":1}}"#,
+        ),
+        (200, r#"{"ok":true}"#),
+    ])
+    .await;
+    let sink = TelegramBot::new("token", "42", Lang::Ja).with_api_url(url);
+
+    sink.send(&price_changed()).await.unwrap();
+
+    assert_eq!(received.lock().unwrap().len(), 2);
+}
+
+#[async_std::test]
+async fn telegram_fails_without_retrying_a_long_rate_limit() {
+    let (url, received) = mock_server_with_bodies(vec![(
+        429,
+        r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 3600","parameters":{"retry_after":3600}}"#,
+    )])
+    .await;
+    let sink = TelegramBot::new("token", "42", Lang::Ja).with_api_url(url);
+
+    let error = sink.send(&price_changed()).await.unwrap_err();
+
+    assert!(
+        error.to_string().contains("asks to wait 3600s"),
+        "{}",
+        error
+    );
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
+
+#[async_std::test]
+async fn ntfy_sends_the_body_with_title_priority_and_tags() {
+    let (url, received) = mock_server(vec![200]).await;
+    let sink = NtfySink::new(&url, "skins", Some("tk_secret".to_owned()), Lang::En);
+
+    sink.send(&price_changed()).await.unwrap();
+
+    let received = received.lock().unwrap();
+    let headers = &received[0].headers;
+    assert!(headers.starts_with("post /hook/skins "));
+    assert!(headers
+        .lines()
+        .any(|line| line == "title: the price of ak-47 | redline (field-tested) changed"));
+    assert!(headers.lines().any(|line| line == "priority: 4"));
+    assert!(headers
+        .lines()
+        .any(|line| line == "tags: green_circle,chart_with_downwards_trend"));
+    assert!(headers
+        .lines()
+        .any(|line| line == "authorization: bearer tk_secret"));
+    assert_eq!(
+        received[0].body,
+        json!("1,000 yen → 900 yen (-100 yen, -10.0%)")
+    );
+}
+
+#[async_std::test]
+async fn ntfy_reports_a_plain_text_error() {
+    let (url, _) = mock_server_with_bodies(vec![(502, "Bad Gateway\n")]).await;
+    let sink = NtfySink::new(&url, "skins", None, Lang::Ja);
+
+    let error = sink.send(&price_changed()).await.unwrap_err();
+
+    let error = error.to_string();
+    assert!(error.starts_with("ntfy returned 502"), "{}", error);
+    assert!(error.ends_with(": Bad Gateway"), "{}", error);
+}
+
+// The request line, e.g. `put /hook/_matrix/... http/1.1`.
+fn request_line(received: &Received) -> &str {
+    received.headers.lines().next().unwrap()
+}
+
+#[async_std::test]
+async fn matrix_sends_the_delta_as_html() {
+    let (url, received) = mock_server(vec![200]).await;
+    let sink = MatrixSink::new(url, "!room:example.com", "syt_token", Lang::Ja);
+
+    sink.send(&price_changed()).await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert!(request_line(&received[0]).starts_with(
+        "put /hook/_matrix/client/r0/rooms/!room:example.com/send/m.room.message/takya-"
+    ));
+    assert!(received[0]
+        .headers
+        .lines()
+        .any(|line| line == "authorization: bearer syt_token"));
+    assert_eq!(
+        received[0].body,
+        json!({
+            "msgtype": "m.text",
+            "body": "AK-47 | Redline (Field-Tested) の価格が変更されました\n1,000 円 → 900 円 (-100 円, -10.0%)",
+            "format": "org.matrix.custom.html",
+            "formatted_body": "<strong>AK-47 | Redline (Field-Tested)</strong> の価格が変更されました<br>1,000 円 → 900 円 (<font color=\"#2e7d32\">-100 円, -10.0%</font>)",
+        })
+    );
+}
+
+#[async_std::test]
+async fn matrix_retries_with_the_same_transaction_id() {
+    let (url, received) = mock_server_with_bodies(vec![
+        (
+            429,
+            r#"{"errcode":"M_LIMIT_EXCEEDED","error":"Too many requests","retry_after_ms":100}"#,
+        ),
+        (200, r#"{"event_id":"$event"}"#),
+    ])
+    .await;
+    let sink = MatrixSink::new(url, "!room:example.com", "syt_token", Lang::Ja);
+
+    sink.send(&price_changed()).await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_eq!(request_line(&received[0]), request_line(&received[1]));
+}
+
+#[async_std::test]
+async fn matrix_transaction_id_comes_from_the_queued_row() {
+    let (url, received) = mock_server(vec![200, 200, 200]).await;
+    let sink = MatrixSink::new(url, "!room:example.com", "syt_token", Lang::Ja);
+
+    // The second send stands for a later run retrying the first row, and the third for another
+    // row with the same content.
+    sink.send_queued(&price_changed(), "7-20200909090000")
+        .await
+        .unwrap();
+    sink.send_queued(&price_changed(), "7-20200909090000")
+        .await
+        .unwrap();
+    sink.send_queued(&price_changed(), "8-20200909091000")
+        .await
+        .unwrap();
+
+    let received = received.lock().unwrap();
+    assert!(request_line(&received[0]).contains("/m.room.message/takya-7-20200909090000 "));
+    assert_eq!(request_line(&received[0]), request_line(&received[1]));
+    assert_ne!(request_line(&received[0]), request_line(&received[2]));
+}